@@ -0,0 +1,125 @@
+// Route lookup on ~500 routes: the old per-request scan that re-splits every pattern
+// against the new table of pre-segmented patterns grouped by method and first segment.
+//
+//     cargo bench --bench routing
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{ Duration, Instant };
+
+#[path = "../src/router/pattern.rs"]
+#[allow(dead_code, unused_imports)]
+mod pattern;
+
+use pattern::{ split_path, RouteIndex, ANY_METHOD };
+
+const RESOURCES: usize = 100;
+const ITERATIONS: usize = 20_000;
+
+// The matcher routes went through before the index, one pattern at a time
+fn linear_match(route_pattern: &str, actual_path: &str) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+
+    let mut pattern_iter = route_pattern.trim_matches('/').split('/');
+    let mut path_iter = actual_path.trim_matches('/').split('/');
+
+    loop {
+        match (pattern_iter.next(), path_iter.next()) {
+            (Some(pattern), actual) if pattern.starts_with('*') => {
+                let rest: Vec<&str> = actual.into_iter().chain(path_iter).collect();
+                params.insert(pattern[1..].to_string(), rest.join("/"));
+                return Some(params);
+            }
+            (Some(pattern), Some(actual)) => {
+                if let Some(name) = pattern.strip_prefix(':') {
+                    params.insert(name.to_string(), actual.to_string());
+                } else if pattern != actual {
+                    return None;
+                }
+            }
+            (None, None) => {
+                return Some(params);
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+}
+
+fn linear_find<'a>(
+    routes: &'a [(String, String)],
+    method: &str,
+    path: &str
+) -> Option<(&'a str, HashMap<String, String>)> {
+    routes
+        .iter()
+        .filter(|(route_method, _)| route_method == method || route_method == ANY_METHOD)
+        .find_map(|(_, pattern)| linear_match(pattern, path).map(|params| (pattern.as_str(), params)))
+}
+
+// Five routes per resource, 500 in total
+fn routes() -> Vec<(String, String)> {
+    let mut routes = Vec::new();
+    for i in 0..RESOURCES {
+        routes.push(("GET".into(), format!("/api/v1/resource{i}")));
+        routes.push(("POST".into(), format!("/api/v1/resource{i}")));
+        routes.push(("GET".into(), format!("/resource{i}/:id")));
+        routes.push(("PUT".into(), format!("/resource{i}/:id")));
+        routes.push(("GET".into(), format!("/resource{i}/:id/files/*path")));
+    }
+    routes
+}
+
+// Requests spread over the table, plus one that matches nothing
+fn requests() -> Vec<(&'static str, String)> {
+    let mut requests = Vec::new();
+    for i in (0..RESOURCES).step_by(7) {
+        requests.push(("GET", format!("/api/v1/resource{i}")));
+        requests.push(("PUT", format!("/resource{i}/42")));
+        requests.push(("GET", format!("/resource{i}/42/files/a/b.txt")));
+    }
+    requests.push(("GET", "/missing/path".into()));
+    requests
+}
+
+fn time(name: &str, lookups: usize, mut run: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let elapsed = start.elapsed();
+    let per_lookup = elapsed / ((ITERATIONS * lookups) as u32);
+    println!("{name:<8} {elapsed:>12.2?} total, {per_lookup:>10.2?} per lookup");
+    elapsed
+}
+
+fn main() {
+    let routes = routes();
+    let requests = requests();
+
+    let mut index = RouteIndex::default();
+    for (method, pattern) in &routes {
+        index.insert(method, pattern, pattern.clone());
+    }
+
+    // Both matchers have to agree before their timings mean anything
+    for (method, path) in &requests {
+        let old = linear_find(&routes, method, path).map(|(pattern, _)| pattern.to_string());
+        let new = index.find(method, &split_path(path), None).map(|(pattern, _)| pattern.clone());
+        assert_eq!(old, new, "{method} {path}");
+    }
+
+    println!("{} routes, {} lookups x {ITERATIONS}", routes.len(), requests.len());
+    let old = time("linear", requests.len(), || {
+        for (method, path) in &requests {
+            black_box(linear_find(black_box(&routes), method, black_box(path)));
+        }
+    });
+    let new = time("indexed", requests.len(), || {
+        for (method, path) in &requests {
+            black_box(index.find(method, &split_path(black_box(path)), None));
+        }
+    });
+    println!("speedup  {:.1}x", old.as_secs_f64() / new.as_secs_f64());
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use crate::{ Next, Req, Res, RequestExt };
+
+#[allow(async_fn_in_trait)]
+pub trait BasicAuthExt {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next);
+}
+
+impl BasicAuthExt for Arc<BasicAuth> {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next) {
+        self.auth_middleware(req, res, next).await;
+    }
+}
+
+// Set on the request once Basic credentials check out, read it with
+// `req.ext::<AuthenticatedUser>().await`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedUser {
+    pub username: String,
+}
+
+type Verify = dyn Fn(&str, &str) -> bool + Send + Sync;
+
+// HTTP Basic auth middleware, e.g.
+// `BasicAuth::new("admin", |user, password| user == "ada" && password == "secret").build()`
+pub struct BasicAuth {
+    // Pre-rendered WWW-Authenticate value
+    challenge: String,
+    verify: Box<Verify>,
+}
+
+impl BasicAuth {
+    // `verify` gets the username and password and says whether they are valid
+    pub fn new<F>(realm: &str, verify: F) -> Self where F: Fn(&str, &str) -> bool + Send + Sync + 'static {
+        let realm = realm.replace('\\', "\\\\").replace('"', "\\\"");
+        Self {
+            challenge: format!("Basic realm=\"{realm}\""),
+            verify: Box::new(verify),
+        }
+    }
+
+    // Shared handle for use with `BasicAuthExt::run_middleware`
+    pub fn build(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub async fn auth_middleware(&self, req: Req, res: Res, next: Next) {
+        let credentials = req
+            .header("authorization").await
+            .and_then(|value| parse_basic(&value));
+
+        match credentials {
+            Some((username, password)) if (self.verify)(&username, &password) => {
+                req.set_ext(AuthenticatedUser { username }).await;
+                next().await;
+            }
+            // Missing, garbled or wrong, the client only learns it needs to log in
+            _ => {
+                let req = req.read().await;
+                res
+                    .read().await
+                    .send_error_with_headers(401, &req, &[("WWW-Authenticate", &self.challenge)]).await;
+            }
+        }
+    }
+}
+
+// Username and password from an `Authorization: Basic <base64>` value
+fn parse_basic(value: &str) -> Option<(String, String)> {
+    let (scheme, token) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(decode_base64(token.trim())?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+// Standard alphabet, padding optional. None on any other character.
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    // A lone trailing character can't encode a whole byte
+    if input.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity((input.len() * 3) / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for byte in input.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => {
+                return None;
+            }
+        };
+        acc = (acc << 6) | (value as u32);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ decode_base64, parse_basic };
+
+    #[test]
+    fn decodes_with_and_without_padding() {
+        assert_eq!(decode_base64("YWRhOnNlY3JldA==").unwrap(), b"ada:secret");
+        assert_eq!(decode_base64("YWRhOnNlY3JldA").unwrap(), b"ada:secret");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert!(decode_base64("YW*h").is_none());
+        assert!(decode_base64("YWRhO").is_none());
+    }
+
+    #[test]
+    fn parses_basic_credentials() {
+        assert_eq!(parse_basic("Basic YWRhOnNlY3JldA=="), Some(("ada".into(), "secret".into())));
+        // Only the first colon splits, passwords may contain more
+        assert_eq!(parse_basic("basic YWRhOmE6Yg=="), Some(("ada".into(), "a:b".into())));
+        assert_eq!(parse_basic("Bearer YWRhOnNlY3JldA=="), None);
+        // "adasecret", no colon
+        assert_eq!(parse_basic("Basic YWRhc2VjcmV0"), None);
+        assert_eq!(parse_basic("Basic"), None);
+    }
+}
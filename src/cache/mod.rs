@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+
+use crate::response::{ Captured, Response };
+use crate::{ Next, Req, Request, Res };
+
+#[allow(async_fn_in_trait)]
+pub trait ResponseCacheExt {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next);
+}
+
+impl ResponseCacheExt for Arc<ResponseCache> {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next) {
+        self.cache_middleware(req, res, next).await;
+    }
+}
+
+// Responses bigger than this go out uncached
+const MAX_CACHED_SIZE: usize = 1024 * 1024;
+
+type KeyFn = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+// Whole GET and HEAD responses kept in memory for `ttl`, least recently used dropped first, e.g.
+// `ResponseCache::new(Duration::from_secs(30)).build()`
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    key: Option<Box<KeyFn>>,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    // Bumped on every use, the smallest `used` is the least recently used
+    clock: u64,
+}
+
+struct Entry {
+    // Request path, for `purge`
+    path: String,
+    captured: Arc<Captured>,
+    stored: Instant,
+    used: u64,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_entries: 1000,
+            key: None,
+            entries: Mutex::default(),
+        }
+    }
+
+    // Responses kept at most, 1000 by default
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    // Key responses by something other than method, path and query, e.g. to keep one per
+    // Accept-Language. Requests where `key` returns None bypass the cache.
+    pub fn key_by<F>(mut self, key: F) -> Self where F: Fn(&Request) -> Option<String> + Send + Sync + 'static {
+        self.key = Some(Box::new(key));
+        self
+    }
+
+    // Shared handle for use with `ResponseCacheExt::run_middleware`
+    pub fn build(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    // Drop every response for a request path starting with `prefix`, "/" empties the cache
+    pub fn purge(&self, prefix: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.by_key.retain(|_, entry| !entry.path.starts_with(prefix));
+    }
+
+    pub async fn cache_middleware(&self, req: Req, res: Res, next: Next) {
+        let (key, path) = {
+            let req = req.read().await;
+            if req.method != "GET" && req.method != "HEAD" {
+                (None, String::new())
+            } else {
+                let key = match &self.key {
+                    Some(key) => key(&req).map(|key| format!("{} {key}", req.method)),
+                    None => Some(req.identity()),
+                };
+                (key, req.path.clone())
+            }
+        };
+        let Some(key) = key else {
+            next().await;
+            return;
+        };
+
+        if let Some((captured, age)) = self.lookup(&key, Instant::now()) {
+            let res = res.read().await;
+            let _ = res.set_header("Age", &age.as_secs().to_string()).await;
+            res.replay(&captured).await;
+            return;
+        }
+        if let Some(captured) = Response::capture(&res, next, MAX_CACHED_SIZE).await
+            && is_cacheable(&captured)
+        {
+            self.store(&key, &path, captured, Instant::now());
+        }
+    }
+
+    // Fresh response for `key` and how long it has been cached
+    fn lookup(&self, key: &str, now: Instant) -> Option<(Arc<Captured>, Duration)> {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let age = now.saturating_duration_since(entries.by_key.get(key)?.stored);
+        if age >= self.ttl {
+            entries.by_key.remove(key);
+            return None;
+        }
+        entries.clock += 1;
+        let used = entries.clock;
+        let entry = entries.by_key.get_mut(key)?;
+        entry.used = used;
+        Some((Arc::clone(&entry.captured), age))
+    }
+
+    fn store(&self, key: &str, path: &str, captured: Captured, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !entries.by_key.contains_key(key) && entries.by_key.len() >= self.max_entries {
+            // Expired ones first, then the least recently used
+            entries.by_key.retain(|_, entry| now.saturating_duration_since(entry.stored) < self.ttl);
+            if entries.by_key.len() >= self.max_entries
+                && let Some(oldest) = entries.by_key
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.used)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.by_key.remove(&oldest);
+            }
+        }
+        entries.clock += 1;
+        let used = entries.clock;
+        entries.by_key.insert(key.to_string(), Entry {
+            path: path.to_string(),
+            captured: Arc::new(captured),
+            stored: now,
+            used,
+        });
+    }
+}
+
+// Successful, whole, not marked private by the handler and free of cookies
+fn is_cacheable(captured: &Captured) -> bool {
+    let forbidden = captured.header("cache-control").is_some_and(|cache_control| {
+        cache_control
+            .split(',')
+            .map(|directive| directive.trim())
+            .any(|directive| directive.eq_ignore_ascii_case("no-store") || directive.eq_ignore_ascii_case("private"))
+    });
+    (200..300).contains(&captured.status) && captured.status != 206 && !forbidden && captured.is_shareable()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{ Duration, Instant };
+
+    use super::{ is_cacheable, ResponseCache };
+    use crate::response::Captured;
+
+    fn captured(status: u16, headers: &[(&str, &str)]) -> Captured {
+        Captured {
+            status,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: b"page".to_vec(),
+        }
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let cache = ResponseCache::new(Duration::from_secs(30));
+        let start = Instant::now();
+        cache.store("GET /a?", "/a", captured(200, &[]), start);
+
+        let (hit, age) = cache.lookup("GET /a?", start + Duration::from_secs(29)).unwrap();
+        assert_eq!(hit.body, b"page");
+        assert_eq!(age, Duration::from_secs(29));
+        assert!(cache.lookup("GET /a?", start + Duration::from_secs(30)).is_none());
+        // Gone for good, not just hidden
+        assert!(cache.lookup("GET /a?", start).is_none());
+    }
+
+    #[test]
+    fn least_recently_used_goes_first() {
+        let cache = ResponseCache::new(Duration::from_secs(30)).max_entries(2);
+        let now = Instant::now();
+        cache.store("a", "/a", captured(200, &[]), now);
+        cache.store("b", "/b", captured(200, &[]), now);
+        assert!(cache.lookup("a", now).is_some());
+        cache.store("c", "/c", captured(200, &[]), now);
+
+        assert!(cache.lookup("a", now).is_some());
+        assert!(cache.lookup("b", now).is_none());
+        assert!(cache.lookup("c", now).is_some());
+    }
+
+    #[test]
+    fn only_shareable_successes_are_cached() {
+        assert!(is_cacheable(&captured(200, &[("Cache-Control", "public, max-age=30")])));
+        assert!(!is_cacheable(&captured(200, &[("Cache-Control", "no-cache, no-store")])));
+        assert!(!is_cacheable(&captured(200, &[("cache-control", "private")])));
+        assert!(!is_cacheable(&captured(200, &[("Set-Cookie", "id=1")])));
+        assert!(!is_cacheable(&captured(206, &[])));
+        assert!(!is_cacheable(&captured(404, &[])));
+        assert!(!is_cacheable(&captured(500, &[])));
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::Read;
+
+use flate2::read::{ GzDecoder, ZlibDecoder };
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Unsupported,
+    TooLarge,
+    Malformed,
+}
+
+impl DecodeError {
+    pub fn status(&self) -> u16 {
+        match self {
+            DecodeError::Unsupported => 415,
+            DecodeError::TooLarge => 413,
+            DecodeError::Malformed => 400,
+        }
+    }
+}
+
+// Decode a request body, never producing more than `limit` bytes
+pub fn decode(encoding: &str, body: &[u8], limit: usize) -> Result<Vec<u8>, DecodeError> {
+    match encoding {
+        "identity" => Ok(body.to_vec()),
+        "gzip" | "x-gzip" => read_limited(GzDecoder::new(body), limit),
+        // HTTP "deflate" is the zlib format
+        "deflate" => read_limited(ZlibDecoder::new(body), limit),
+        _ => Err(DecodeError::Unsupported),
+    }
+}
+
+fn read_limited(decoder: impl Read, limit: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut decoded = Vec::new();
+    // One byte past the cap is enough to spot a zip bomb without inflating it
+    decoder
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut decoded)
+        .map_err(|_| DecodeError::Malformed)?;
+
+    if decoded.len() > limit {
+        return Err(DecodeError::TooLarge);
+    }
+
+    Ok(decoded)
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::error::Error;
+use crate::request::is_token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+// Attributes for Response::set_cookie, all left out of the header by default
+#[derive(Debug, Clone, Default)]
+pub struct CookieOptions {
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    // Seconds
+    pub max_age: Option<i64>,
+    // HTTP date, e.g. "Wed, 21 Oct 2015 07:28:00 GMT"
+    pub expires: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+// Parse a `Cookie: a=1; b=2` header value, first occurrence of a name wins
+pub(crate) fn parse_cookies(header: &str) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    for pair in header.split(';') {
+        let Some((name, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        cookies.entry(name.to_string()).or_insert_with(|| value.to_string());
+    }
+    cookies
+}
+
+// Value of a `Set-Cookie` header, refusing anything that could add attributes or lines
+pub(crate) fn serialize_cookie(name: &str, value: &str, options: &CookieOptions) -> Result<String, Error> {
+    let invalid = |reason: &str| Error::InvalidCookie { name: name.to_string(), reason: reason.to_string() };
+    if !is_token(name) {
+        return Err(invalid("name is not a token"));
+    }
+    // A value may be wrapped in double quotes, the quotes aren't cookie-octets themselves
+    let bare = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+    if !bare.bytes().all(is_cookie_octet) {
+        return Err(invalid("value has characters outside cookie-octet"));
+    }
+    for (attribute, text) in [("Path", &options.path), ("Domain", &options.domain), ("Expires", &options.expires)] {
+        if let Some(text) = text && !is_attribute_value(text) {
+            return Err(invalid(&format!("{attribute} contains ';' or a control character")));
+        }
+    }
+    if options.domain.as_deref().is_some_and(|domain| domain.contains(char::is_whitespace)) {
+        return Err(invalid("Domain contains whitespace"));
+    }
+
+    let mut cookie = format!("{name}={value}");
+    if let Some(path) = &options.path {
+        let _ = write!(cookie, "; Path={path}");
+    }
+    if let Some(domain) = &options.domain {
+        let _ = write!(cookie, "; Domain={domain}");
+    }
+    if let Some(max_age) = options.max_age {
+        let _ = write!(cookie, "; Max-Age={max_age}");
+    }
+    if let Some(expires) = &options.expires {
+        let _ = write!(cookie, "; Expires={expires}");
+    }
+    if options.secure {
+        cookie.push_str("; Secure");
+    }
+    if options.http_only {
+        cookie.push_str("; HttpOnly");
+    }
+    if let Some(same_site) = options.same_site {
+        let same_site = match same_site {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        };
+        let _ = write!(cookie, "; SameSite={same_site}");
+    }
+    Ok(cookie)
+}
+
+// RFC 6265 cookie-octet: visible ASCII except `"`, `,`, `;` and `\`
+fn is_cookie_octet(byte: u8) -> bool {
+    matches!(byte, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+// Path, Domain and Expires values end at the next `;`, and a control character could end the line
+fn is_attribute_value(text: &str) -> bool {
+    !text.chars().any(|c| c == ';' || c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ serialize_cookie, CookieOptions };
+
+    #[test]
+    fn refuses_cookies_that_would_inject() {
+        let plain = CookieOptions::default();
+        assert_eq!(serialize_cookie("id", "a1", &plain).unwrap(), "id=a1");
+        assert_eq!(serialize_cookie("id", "\"a1\"", &plain).unwrap(), "id=\"a1\"");
+
+        assert!(serialize_cookie("bad name", "a", &plain).is_err());
+        assert!(serialize_cookie("id;", "a", &plain).is_err());
+        assert!(serialize_cookie("id", "a; Domain=evil.com", &plain).is_err());
+        assert!(serialize_cookie("id", "a\r\nSet-Cookie: x=1", &plain).is_err());
+        assert!(serialize_cookie("id", "a b", &plain).is_err());
+
+        let path = CookieOptions { path: Some("/; Domain=evil.com".into()), ..Default::default() };
+        assert!(serialize_cookie("id", "a", &path).is_err());
+        let domain = CookieOptions { domain: Some("example.com\r\nX: y".into()), ..Default::default() };
+        assert!(serialize_cookie("id", "a", &domain).is_err());
+        let domain = CookieOptions { domain: Some("example .com".into()), ..Default::default() };
+        assert!(serialize_cookie("id", "a", &domain).is_err());
+        let expires = CookieOptions { expires: Some("Wed; Secure".into()), ..Default::default() };
+        assert!(serialize_cookie("id", "a", &expires).is_err());
+    }
+}
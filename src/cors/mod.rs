@@ -3,6 +3,7 @@ use tokio::sync::RwLock;
 
 use crate::{ Next, Req, Res, ResponseExt };
 
+#[allow(async_fn_in_trait)]
 pub trait CorsExt {
     async fn run_middleware(&self, req: Req, res: Res, next: Next);
 }
@@ -20,47 +21,121 @@ impl CorsExt for Arc<RwLock<Cors>> {
     }
 }
 
+// Builder for the CORS middleware, e.g.
+// `Cors::new().origins(&["https://app.example"]).methods(&["GET", "PUT"]).build()`
+#[derive(Debug, Clone)]
 pub struct Cors {
     allow_origins: Vec<String>,
+    allow_methods: Vec<String>,
+    allow_headers: Vec<String>,
+    allow_credentials: bool,
+    // Seconds a browser may cache the preflight result
+    max_age: Option<u64>,
+    // Status for unlisted origins, None lets the request through without CORS headers
+    reject_status: Option<u16>,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self {
+            allow_origins: Vec::new(),
+            allow_methods: vec!["GET".into(), "POST".into(), "OPTIONS".into()],
+            allow_headers: Vec::new(),
+            allow_credentials: false,
+            max_age: None,
+            reject_status: None,
+        }
+    }
 }
 
 impl Cors {
-    pub fn new(allow_origins: &[&str]) -> Arc<RwLock<Self>> {
-        Arc::new(
-            RwLock::new(Self {
-                allow_origins: allow_origins
-                    .iter()
-                    .map(|origins| origins.to_string())
-                    .collect(),
-            })
-        )
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Allowed origins, "*" allows every origin
+    pub fn origins(mut self, origins: &[&str]) -> Self {
+        self.allow_origins = origins.iter().map(|origin| origin.to_string()).collect();
+        self
+    }
+
+    // Access-Control-Allow-Methods, GET, POST and OPTIONS by default
+    pub fn methods(mut self, methods: &[&str]) -> Self {
+        self.allow_methods = methods.iter().map(|method| method.to_string()).collect();
+        self
+    }
+
+    // Access-Control-Allow-Headers, left out when empty
+    pub fn headers(mut self, headers: &[&str]) -> Self {
+        self.allow_headers = headers.iter().map(|header| header.to_string()).collect();
+        self
+    }
+
+    // Allow cookies and auth headers. The request's origin is echoed instead of "*",
+    // browsers refuse credentials with a wildcard.
+    pub fn credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    // Answer unlisted origins with `status` instead of leaving the browser to block them
+    pub fn reject_unlisted(mut self, status: u16) -> Self {
+        self.reject_status = Some(status);
+        self
+    }
+
+    // Shared handle for use with `CorsExt::run_middleware`
+    pub fn build(self) -> Arc<RwLock<Self>> {
+        Arc::new(RwLock::new(self))
     }
 
     pub async fn cors_middleware(&self, req: Req, res: Res, next: Next) {
         let origin = {
             let req_read = req.read().await;
-            req_read.headers.get("origin").cloned().unwrap_or_default()
+            req_read.headers.get("origin").cloned()
         };
 
-        let allow_all = self.allow_origins.contains(&"*".to_string());
+        // The answer depends on Origin, caches must not share it across origins
+        let _ = res.read().await.append_header("Vary", "Origin").await;
+
+        // Not a cross-origin request
+        let Some(origin) = origin else {
+            next().await;
+            return;
+        };
+
+        let allow_all = self.allow_origins.iter().any(|allowed| allowed == "*");
 
         // Case Unlisted Origin
         if !allow_all && !self.allow_origins.contains(&origin) {
-            res.with_write(|res| async move {
-                let mut res = res.write().await;
-                res.status(401).await;
-                res.set_header("Content-Type", "text/plain").await;
-                res.send("Unauthorized origin").await;
-            }).await;
+            if let Some(status) = self.reject_status {
+                res.send_error(status, &req).await;
+                return;
+            }
+            next().await;
             return;
         }
 
-        res.with_write(|res| async move {
-            let res = res.write().await;
-            let allow_origin = if allow_all { "*" } else { &origin };
-            res.set_header("Access-Control-Allow-Origin", allow_origin).await;
-            res.set_header("Access-Control-Allow-Methods", "GET, POST, OPTIONS").await;
-        }).await;
+        {
+            let res = res.read().await;
+            let allow_origin = if allow_all && !self.allow_credentials { "*" } else { &origin };
+            let _ = res.set_header("Access-Control-Allow-Origin", allow_origin).await;
+            let _ = res.set_header("Access-Control-Allow-Methods", &self.allow_methods.join(", ")).await;
+            if !self.allow_headers.is_empty() {
+                let _ = res.set_header("Access-Control-Allow-Headers", &self.allow_headers.join(", ")).await;
+            }
+            if self.allow_credentials {
+                let _ = res.set_header("Access-Control-Allow-Credentials", "true").await;
+            }
+            if let Some(max_age) = self.max_age {
+                let _ = res.set_header("Access-Control-Max-Age", &max_age.to_string()).await;
+            }
+        }
 
         next().await;
     }
@@ -0,0 +1,227 @@
+use std::fmt;
+
+use crate::request::Request;
+use crate::response::{ escape_html, get_status_text };
+use crate::router::MatchedRoute;
+
+// Errors Glote reports to the application, e.g. from `Glote::finalize`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidPattern {
+        pattern: String,
+        reason: String,
+    },
+    DuplicateRoute {
+        method: String,
+        pattern: String,
+        existing: String,
+    },
+    // A response header that would break the head apart, e.g. CR/LF in the value
+    InvalidHeader {
+        name: String,
+        reason: String,
+    },
+    // A cookie name, value or attribute that can't go into `Set-Cookie` as given
+    InvalidCookie {
+        name: String,
+        reason: String,
+    },
+    // `Glote::dev_errors` asked for on a server in production mode
+    DevErrorsInProduction,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidPattern { pattern, reason } => {
+                write!(f, "invalid route pattern '{pattern}': {reason}")
+            }
+            Error::DuplicateRoute { method, pattern, existing } => {
+                write!(f, "duplicate route {method} '{pattern}' conflicts with '{existing}'")
+            }
+            Error::InvalidHeader { name, reason } => {
+                write!(f, "invalid header {name:?}: {reason}")
+            }
+            Error::InvalidCookie { name, reason } => {
+                write!(f, "invalid cookie {name:?}: {reason}")
+            }
+            Error::DevErrorsInProduction => {
+                write!(f, "dev_errors can't be turned on in production mode")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Why `Request::json` couldn't produce a value
+#[derive(Debug)]
+pub enum JsonError {
+    // Content-Type isn't JSON, holds the header if one was sent
+    ContentType(Option<String>),
+    MissingBody,
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::ContentType(Some(content_type)) => {
+                write!(f, "expected application/json, got '{content_type}'")
+            }
+            JsonError::ContentType(None) => write!(f, "expected application/json, got no Content-Type"),
+            JsonError::MissingBody => write!(f, "request has no body"),
+            JsonError::Parse(e) => write!(f, "invalid JSON body: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Why `Request::new` couldn't make sense of a request, the server answers 400
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // No request line at all
+    Empty,
+    // Not `METHOD target version`, holds the start of the line
+    RequestLine(String),
+    InvalidMethod(String),
+    InvalidTarget(String),
+    // Anything but HTTP/1.0 and HTTP/1.1
+    UnsupportedVersion(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty request"),
+            ParseError::RequestLine(line) => write!(f, "malformed request line '{line}'"),
+            ParseError::InvalidMethod(method) => write!(f, "invalid method '{method}'"),
+            ParseError::InvalidTarget(target) => write!(f, "invalid request target '{target}'"),
+            ParseError::UnsupportedVersion(version) => write!(f, "unsupported HTTP version '{version}'"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Misuse or failure of a streamed response (`Response::begin` / `write_chunk` / `end`)
+#[derive(Debug)]
+pub enum StreamError {
+    // A response (streamed or not) already went out
+    AlreadySent,
+    // write_chunk or end without begin
+    NotStreaming,
+    // Writing to the client failed, usually because it went away
+    Io(std::io::Error),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::AlreadySent => write!(f, "response already sent"),
+            StreamError::NotStreaming => write!(f, "response stream not started"),
+            StreamError::Io(e) => write!(f, "writing response stream failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Renders the body of every response Glote generates on its own (404, 504, CORS rejections...)
+pub trait ErrorRenderer: Send + Sync {
+    // Returns (content type, body)
+    fn render(&self, status: u16, req: &Request) -> (String, Vec<u8>);
+}
+
+impl fmt::Debug for dyn ErrorRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ErrorRenderer")
+    }
+}
+
+// Plain "404 Not Found" style bodies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultErrorRenderer;
+
+impl ErrorRenderer for DefaultErrorRenderer {
+    fn render(&self, status: u16, _req: &Request) -> (String, Vec<u8>) {
+        let body = format!("{} {}", status, get_status_text(status));
+        ("text/html; charset=UTF-8".into(), body.into_bytes())
+    }
+}
+
+// Message of a handler or middleware panic, in the extensions of the request its 500 is
+// rendered for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerPanic {
+    pub message: String,
+    // Where it panicked, captured when the panic was caught
+    pub backtrace: Option<String>,
+}
+
+// Error pages with the request, the matched route and the panic with its backtrace if there was one,
+// see `Glote::dev_errors`. JSON for clients that ask for it over HTML.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DevErrorRenderer;
+
+impl ErrorRenderer for DevErrorRenderer {
+    fn render(&self, status: u16, req: &Request) -> (String, Vec<u8>) {
+        let title = format!("{} {}", status, get_status_text(status));
+        let route = req.extensions.get::<MatchedRoute>().map(|matched| matched.pattern.as_str());
+        let panic = req.extensions.get::<HandlerPanic>();
+        let backtrace = panic.and_then(|panic| panic.backtrace.as_deref());
+        let panic = panic.map(|panic| panic.message.as_str());
+
+        if prefers_json(req) {
+            let body = serde_json::json!({
+                "status": status,
+                "error": get_status_text(status),
+                "method": req.method,
+                "path": req.path,
+                "route": route,
+                "panic": panic,
+                "backtrace": backtrace,
+            });
+            return ("application/json".into(), body.to_string().into_bytes());
+        }
+
+        let mut details = format!(
+            "<tr><th>Method</th><td>{}</td></tr><tr><th>Path</th><td>{}</td></tr>",
+            escape_html(&req.method),
+            escape_html(&req.path)
+        );
+        if let Some(route) = route {
+            details.push_str(&format!("<tr><th>Route</th><td>{}</td></tr>", escape_html(route)));
+        }
+        let mut panic = panic
+            .map(|message| format!("<h2>Handler panicked</h2><pre>{}</pre>", escape_html(message)))
+            .unwrap_or_default();
+        if let Some(backtrace) = backtrace {
+            panic.push_str(&format!("<h2>Backtrace</h2><pre>{}</pre>", escape_html(backtrace)));
+        }
+        let body = format!(
+            "<!DOCTYPE html><html><head><title>{title}</title></head><body><h1>{title}</h1>{panic}<table>{details}</table></body></html>"
+        );
+        ("text/html; charset=UTF-8".into(), body.into_bytes())
+    }
+}
+
+// Accept names JSON and not HTML, as API clients and fetch calls do
+fn prefers_json(req: &Request) -> bool {
+    req.header("accept").is_some_and(|accept| accept.contains("application/json") && !accept.contains("text/html"))
+}
@@ -2,9 +2,33 @@ mod server;
 mod request;
 mod response;
 mod cors;
+mod auth;
+mod rate_limit;
+mod single_flight;
+mod cache;
+mod error;
+mod router;
+mod cookie;
+mod http_date;
+mod transport;
+mod sse;
+mod testing;
+#[cfg(feature = "compression")]
+mod compression;
 
 // pub use crate::{ mid, han };
-pub use server::{ Glote, Middleware, Handler, Next };
-pub use request::{ Req, Request, RequestExt };
+pub use server::{ Glote, Middleware, MiddlewareFn, Handler, Next, RequestLog, RequestLogger };
+pub use request::{ ConnectionInfo, Extensions, Req, Request, RequestExt, TlsInfo };
 pub use response::{ Res, Response, ResponseExt };
+pub use sse::SseSender;
+pub use testing::{ TestClient, TestResponse };
+pub use router::{ Deprecation, MatchedRoute, Router, TenantSelector, TrailingSlash };
+pub use cookie::{ CookieOptions, SameSite };
 pub use cors::{ Cors, CorsExt };
+pub use auth::{ AuthenticatedUser, BasicAuth, BasicAuthExt };
+pub use rate_limit::{ RateLimiter, RateLimiterExt };
+pub use single_flight::{ SingleFlight, SingleFlightExt };
+pub use cache::{ ResponseCache, ResponseCacheExt };
+#[cfg(feature = "tls")]
+pub use transport::ClientAuth;
+pub use error::{ Error, ErrorRenderer, DefaultErrorRenderer, DevErrorRenderer, HandlerPanic, JsonError, ParseError, StreamError };
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{ Hash, Hasher };
+use std::sync::{ Arc, Mutex };
+use std::time::{ Duration, Instant };
+
+use crate::{ Next, Req, Request, Res };
+
+#[allow(async_fn_in_trait)]
+pub trait RateLimiterExt {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next);
+}
+
+impl RateLimiterExt for Arc<RateLimiter> {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next) {
+        self.limit_middleware(req, res, next).await;
+    }
+}
+
+// Independent locks so clients hashing to different shards never wait on each other
+const SHARDS: usize = 16;
+
+type KeyFn = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+// Token bucket per client: `limit` requests in a burst, refilled evenly over `window`, e.g.
+// `RateLimiter::new(100, Duration::from_secs(60)).build()`
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    // Tokens regained per second
+    refill_rate: f64,
+    key: Option<Box<KeyFn>>,
+    shards: Vec<Mutex<Shard>>,
+}
+
+#[derive(Default)]
+struct Shard {
+    buckets: HashMap<String, Bucket>,
+    last_sweep: Option<Instant>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        let limit = limit.max(1);
+        let window = window.max(Duration::from_millis(1));
+        Self {
+            limit,
+            window,
+            refill_rate: (limit as f64) / window.as_secs_f64(),
+            key: None,
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    // Key clients by something other than their IP, e.g. an API key header.
+    // Requests where `key` returns None fall back to the IP.
+    pub fn key_by<F>(mut self, key: F) -> Self where F: Fn(&Request) -> Option<String> + Send + Sync + 'static {
+        self.key = Some(Box::new(key));
+        self
+    }
+
+    // Shared handle for use with `RateLimiterExt::run_middleware`
+    pub fn build(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub async fn limit_middleware(&self, req: Req, res: Res, next: Next) {
+        let key = {
+            let req = req.read().await;
+            self.key
+                .as_ref()
+                .and_then(|key| key(&req))
+                .or_else(|| req.conn_info().peer_addr.map(|addr| addr.ip().to_string()))
+                .unwrap_or_default()
+        };
+
+        let limit = self.limit.to_string();
+        match self.take(&key, Instant::now()) {
+            Ok(remaining) => {
+                {
+                    let res = res.read().await;
+                    let _ = res.set_header("X-RateLimit-Limit", &limit).await;
+                    let _ = res.set_header("X-RateLimit-Remaining", &remaining.to_string()).await;
+                }
+                next().await;
+            }
+            Err(retry_after) => {
+                // Whole seconds, rounded up so the client doesn't come back too early
+                let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                let req = req.read().await;
+                res
+                    .read().await
+                    .send_error_with_headers(429, &req, &[
+                        ("Retry-After", &retry_after.to_string()),
+                        ("X-RateLimit-Limit", &limit),
+                        ("X-RateLimit-Remaining", "0"),
+                    ]).await;
+            }
+        }
+    }
+
+    // Spend a token for `key`. Ok with the tokens left, Err with the wait for the next one.
+    fn take(&self, key: &str, now: Instant) -> Result<u32, Duration> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let shard = &self.shards[(hasher.finish() as usize) % SHARDS];
+        // Check and spend under one lock, concurrent requests can't both get the last token
+        let mut shard = shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.sweep(&mut shard, now);
+
+        let limit = self.limit as f64;
+        let bucket = shard.buckets
+            .entry(key.to_string())
+            .or_insert(Bucket { tokens: limit, updated: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_rate).min(limit);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(bucket.tokens as u32)
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.refill_rate))
+        }
+    }
+
+    // Once per window drop buckets idle for a whole window, they'd be full again anyway
+    fn sweep(&self, shard: &mut Shard, now: Instant) {
+        let due = shard.last_sweep.is_none_or(|last| now.saturating_duration_since(last) >= self.window);
+        if !due {
+            return;
+        }
+        shard.buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated) < self.window);
+        shard.last_sweep = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{ Duration, Instant };
+
+    use super::RateLimiter;
+
+    #[test]
+    fn bucket_empties_and_refills() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(3));
+        let start = Instant::now();
+        assert_eq!(limiter.take("a", start), Ok(2));
+        assert_eq!(limiter.take("a", start), Ok(1));
+        assert_eq!(limiter.take("a", start), Ok(0));
+        assert_eq!(limiter.take("a", start), Err(Duration::from_secs(1)));
+        // Other keys have their own bucket
+        assert_eq!(limiter.take("b", start), Ok(2));
+        // One token back per second
+        assert_eq!(limiter.take("a", start + Duration::from_secs(1)), Ok(0));
+        assert!(limiter.take("a", start + Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        let limiter = RateLimiter::new(5, Duration::from_secs(10));
+        let start = Instant::now();
+        for key in 0..100 {
+            let _ = limiter.take(&key.to_string(), start);
+        }
+        let later = start + Duration::from_secs(11);
+        for shard in &limiter.shards {
+            limiter.sweep(&mut shard.lock().unwrap(), later);
+        }
+        let left: usize = limiter.shards.iter().map(|shard| shard.lock().unwrap().buckets.len()).sum();
+        assert_eq!(left, 0);
+    }
+}
@@ -1,9 +1,23 @@
+use std::any::{ Any, TypeId };
 use std::collections::HashMap;
-use tokio::sync::RwLock;
-use std::sync::{ Arc };
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{ Duration, Instant };
+use tokio::io::{ AsyncBufRead, AsyncBufReadExt, AsyncReadExt };
+use tokio::sync::{ Notify, RwLock };
+
+use serde::de::DeserializeOwned;
+
+use crate::cookie::parse_cookies;
+use crate::error::{ JsonError, ParseError };
+use std::sync::{ Arc, atomic::{ AtomicBool, AtomicU64, Ordering } };
 
 pub type Req = Arc<RwLock<Request>>;
 
+// Application state registered with `Glote::state`, one value per type
+pub(crate) type StateMap = HashMap<TypeId, Arc<dyn Any + Send + Sync>>;
+
+#[allow(async_fn_in_trait)]
 pub trait RequestExt {
     async fn with_write<F, Fut>(&self, f: F)
         where F: FnOnce(Req) -> Fut + Send, Fut: std::future::Future<Output = ()> + Send;
@@ -13,8 +27,25 @@ pub trait RequestExt {
 
     async fn path(&self) -> Option<String>;
     async fn query(&self, key: &str) -> Option<String>;
+    async fn query_all(&self, key: &str) -> Vec<String>;
     fn params(&self, key: &str) -> impl std::future::Future<Output = Option<String>> + Send;
+    async fn header(&self, name: &str) -> Option<String>;
+    async fn header_all(&self, name: &str) -> Vec<String>;
     async fn body(&self) -> Option<String>;
+    async fn body_bytes(&self) -> Option<Vec<u8>>;
+    async fn form(&self, key: &str) -> Option<String>;
+    async fn json<T: DeserializeOwned>(&self) -> Result<T, JsonError>;
+    async fn json_unchecked<T: DeserializeOwned>(&self) -> Result<T, JsonError>;
+    async fn deadline(&self) -> Option<Instant>;
+    async fn remaining_time(&self) -> Option<Duration>;
+    async fn cancelled(&self);
+    async fn is_cancelled(&self) -> bool;
+    async fn conn_info(&self) -> Arc<ConnectionInfo>;
+    async fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>>;
+    async fn set_ext<T: Send + Sync + 'static>(&self, value: T);
+    async fn ext<T: Send + Sync + 'static>(&self) -> Option<Arc<T>>;
+    async fn cookies(&self) -> HashMap<String, String>;
+    async fn cookie(&self, name: &str) -> Option<String>;
 }
 
 impl RequestExt for Req {
@@ -40,12 +71,173 @@ impl RequestExt for Req {
         self.read().await.query(key).cloned()
     }
 
+    async fn query_all(&self, key: &str) -> Vec<String> {
+        self.read().await.query_all(key)
+    }
+
     async fn params(&self, key: &str) -> Option<String> {
         self.read().await.params(key).cloned()
     }
 
+    async fn header(&self, name: &str) -> Option<String> {
+        self.read().await.header(name).cloned()
+    }
+
+    async fn header_all(&self, name: &str) -> Vec<String> {
+        self.read().await.header_all(name)
+    }
+
     async fn body(&self) -> Option<String> {
-        self.read().await.body.clone()
+        self.read().await.body()
+    }
+
+    async fn body_bytes(&self) -> Option<Vec<u8>> {
+        self.read().await.body_bytes.clone()
+    }
+
+    async fn form(&self, key: &str) -> Option<String> {
+        self.read().await.form()?.remove(key)
+    }
+
+    async fn json<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        self.read().await.json()
+    }
+
+    async fn json_unchecked<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        self.read().await.json_unchecked()
+    }
+
+    async fn deadline(&self) -> Option<Instant> {
+        self.read().await.deadline()
+    }
+
+    async fn remaining_time(&self) -> Option<Duration> {
+        self.read().await.remaining_time()
+    }
+
+    async fn cancelled(&self) {
+        // Don't hold the lock while waiting
+        let cancelled = self.read().await.cancelled();
+        cancelled.await;
+    }
+
+    async fn is_cancelled(&self) -> bool {
+        self.read().await.is_cancelled()
+    }
+
+    async fn conn_info(&self) -> Arc<ConnectionInfo> {
+        self.read().await.conn_info.clone()
+    }
+
+    async fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.read().await.state::<T>()
+    }
+
+    async fn set_ext<T: Send + Sync + 'static>(&self, value: T) {
+        self.write().await.extensions.insert(value);
+    }
+
+    async fn ext<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.read().await.extensions.get_arc::<T>()
+    }
+
+    async fn cookies(&self) -> HashMap<String, String> {
+        self.read().await.cookies()
+    }
+
+    async fn cookie(&self, name: &str) -> Option<String> {
+        self.read().await.cookie(name)
+    }
+}
+
+// Per-request values keyed by type, e.g. the user an auth middleware resolved.
+// Clones of a Request share the stored values.
+#[derive(Debug, Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    // Replaces any earlier value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref::<T>()
+    }
+
+    // Shared handle to the value, lives on after the request lock is released
+    pub fn get_arc<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        let value = self.map.get(&TypeId::of::<T>())?;
+        Arc::clone(value).downcast::<T>().ok()
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.map.remove(&TypeId::of::<T>())?.downcast::<T>().ok()
+    }
+}
+
+// Details of the connection a request arrived on, filled in by the listener
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionInfo {
+    pub local_addr: Option<SocketAddr>,
+    pub peer_addr: Option<SocketAddr>,
+    // Negotiated ALPN protocol, e.g. "http/1.1"
+    pub alpn_protocol: Option<String>,
+    // Only set for TLS connections
+    pub tls: Option<TlsInfo>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsInfo {
+    pub version: String,
+    pub cipher: String,
+    // DER of the client certificate when client auth is configured
+    pub peer_certificate: Option<Vec<u8>>,
+    // Its subject, e.g. `CN=ada,O=Acme`
+    pub peer_subject: Option<String>,
+}
+
+// Ids for requests that didn't bring one, unique within the process
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+// A proxy's or client's X-Request-Id is kept so log lines match across services
+fn request_id(headers: &HashMap<String, String>) -> String {
+    match headers.get("x-request-id") {
+        Some(id) if !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic()) => {
+            id.clone()
+        }
+        _ => format!("{:08x}", NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)),
+    }
+}
+
+// Fires once the client goes away while the request is in flight
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl Cancellation {
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) async fn wait(self) {
+        loop {
+            // Register before checking so a cancel in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
     }
 }
 
@@ -54,17 +246,27 @@ pub struct Request {
     pub method: String,
     pub path: String,
     pub path_params: HashMap<String, String>,
-    pub query: HashMap<String, String>,
-    pub body: Option<String>,
+    pub query: HashMap<String, Vec<String>>,
+    pub body_bytes: Option<Vec<u8>>,
+    // Lowercased names, repeated headers joined with ", " ("; " for Cookie)
     pub headers: HashMap<String, String>,
+    pub extensions: Extensions,
+    // Every header line as received, lowercased names, for `header_all`
+    header_lines: Vec<(String, String)>,
+    // See `id`
+    id: String,
+    deadline: Option<Instant>,
+    cancellation: Cancellation,
+    conn_info: Arc<ConnectionInfo>,
+    state: Arc<StateMap>,
 }
 
 impl Request {
-    pub fn new(req: &[String]) -> Self {
-        let (method, full_path) = {
-            let parts: Vec<&str> = req[0].split_whitespace().collect();
-            (parts[0].to_string(), parts[1])
-        };
+    // Request from its request line and header lines. Lines after a blank one are ignored,
+    // the raw body goes in `body_bytes`.
+    pub fn new(req: &[String]) -> Result<Self, ParseError> {
+        let (method, full_path) = parse_request_line(req.first().ok_or(ParseError::Empty)?)?;
+        let method = method.to_string();
 
         let (path, query) = if let Some(pos) = full_path.find('?') {
             (full_path[..pos].to_string(), parse_query(&full_path[pos + 1..]))
@@ -73,82 +275,709 @@ impl Request {
         };
 
         let mut headers = HashMap::<String, String>::new();
-        let mut body_lines = Vec::new();
-        let mut is_body = false;
-
-        for line in req[1..].iter() {
-            if is_body {
-                body_lines.push(line.clone());
-                continue;
-            }
+        let mut header_lines = Vec::new();
 
-            if line.is_empty() {
-                is_body = true;
-                continue;
-            }
-
-            if let Some((k, v)) = line.split_once(": ") {
-                headers.insert(k.to_string().to_lowercase(), v.to_string());
+        // The head ends at the blank line, the body is never rebuilt from lines
+        for line in req[1..].iter().take_while(|line| !line.is_empty()) {
+            if let Some((name, value)) = parse_header_line(line) {
+                let separator = if name == "cookie" { "; " } else { ", " };
+                headers
+                    .entry(name.clone())
+                    .and_modify(|joined| {
+                        joined.push_str(separator);
+                        joined.push_str(&value);
+                    })
+                    .or_insert_with(|| value.clone());
+                header_lines.push((name, value));
             }
         }
 
-        let body = if body_lines.is_empty() { None } else { Some(body_lines.join("\n")) };
-
-        Self {
+        Ok(Self {
             method,
             path,
             path_params: HashMap::new(),
             query,
-            body,
+            body_bytes: None,
+            id: request_id(&headers),
             headers,
+            extensions: Extensions::default(),
+            header_lines,
+            deadline: None,
+            cancellation: Cancellation::default(),
+            conn_info: Arc::new(ConnectionInfo::default()),
+            state: Arc::new(HashMap::new()),
+        })
+    }
+
+    // Stand-in for a request refused before its head parsed, for the ErrorRenderer.
+    // Method and path come from the request line when it has them.
+    pub(crate) fn refused(request_line: Option<&str>) -> Self {
+        let (method, path) = request_line
+            .and_then(|line| parse_request_line(line).ok())
+            .map(|(method, target)| {
+                (method.to_string(), target.split('?').next().unwrap_or_default().to_string())
+            })
+            .unwrap_or_default();
+        Self {
+            method,
+            path,
+            path_params: HashMap::new(),
+            query: HashMap::new(),
+            body_bytes: None,
+            headers: HashMap::new(),
+            extensions: Extensions::default(),
+            header_lines: Vec::new(),
+            id: request_id(&HashMap::new()),
+            deadline: None,
+            cancellation: Cancellation::default(),
+            conn_info: Arc::new(ConnectionInfo::default()),
+            state: Arc::new(HashMap::new()),
+        }
+    }
+
+    // Copy without the body, kept by the Response for error pages it renders on its own
+    pub(crate) fn without_body(&self) -> Self {
+        Self {
+            method: self.method.clone(),
+            path: self.path.clone(),
+            path_params: self.path_params.clone(),
+            query: self.query.clone(),
+            body_bytes: None,
+            headers: self.headers.clone(),
+            extensions: Extensions::default(),
+            header_lines: self.header_lines.clone(),
+            id: self.id.clone(),
+            deadline: self.deadline,
+            cancellation: self.cancellation.clone(),
+            conn_info: Arc::clone(&self.conn_info),
+            state: Arc::clone(&self.state),
         }
     }
 
+    // "GET /path?a=1&b=2" with the query sorted, so requests asking for the same thing share it
+    pub(crate) fn identity(&self) -> String {
+        let mut query: Vec<_> = self.query
+            .iter()
+            .flat_map(|(name, values)| values.iter().map(move |value| format!("{name}={value}")))
+            .collect();
+        query.sort();
+        format!("{} {}?{}", self.method, self.path, query.join("&"))
+    }
+
+    // First value for a key, `tag[]` and `tag` are the same key
     pub fn query(&self, key: &str) -> Option<&String> {
-        self.query.get(key)
+        self.query.get(array_key(key))?.first()
+    }
+
+    // Every value for a key, in the order sent
+    pub fn query_all(&self, key: &str) -> Vec<String> {
+        self.query.get(array_key(key)).cloned().unwrap_or_default()
     }
 
     pub fn params(&self, key: &str) -> Option<&String> {
         self.path_params.get(key)
     }
+
+    // Header value by case-insensitive name, repeated headers come joined
+    pub fn header(&self, name: &str) -> Option<&String> {
+        self.headers.get(&name.to_ascii_lowercase())
+    }
+
+    // Every value of a repeated header, one per line as the client sent them
+    pub fn header_all(&self, name: &str) -> Vec<String> {
+        self.header_lines
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    // Body as text, invalid UTF-8 replaced
+    pub fn body(&self) -> Option<String> {
+        self.body_bytes.as_ref().map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    // Fields of an urlencoded form body, None for other content types
+    pub fn form(&self) -> Option<HashMap<String, String>> {
+        let content_type = self.headers.get("content-type")?;
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        if !mime.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+            return None;
+        }
+        Some(self.body().map(|body| parse_form(&body)).unwrap_or_default())
+    }
+
+    // Body deserialized from JSON, the Content-Type must be application/json or +json
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        let content_type = self.headers.get("content-type");
+        let mime = content_type
+            .and_then(|value| value.split(';').next())
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if mime != "application/json" && !mime.ends_with("+json") {
+            return Err(JsonError::ContentType(content_type.cloned()));
+        }
+        self.json_unchecked()
+    }
+
+    // Same as `json` without looking at the Content-Type
+    pub fn json_unchecked<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        let bytes = self.body_bytes.as_deref().unwrap_or_default();
+        // A UTF-8 BOM isn't valid JSON but some clients send one
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        if bytes.is_empty() {
+            return Err(JsonError::MissingBody);
+        }
+        serde_json::from_slice(bytes).map_err(JsonError::Parse)
+    }
+
+    // Cookies from the Cookie header, parsed on each call
+    pub fn cookies(&self) -> HashMap<String, String> {
+        self.headers.get("cookie").map(|header| parse_cookies(header)).unwrap_or_default()
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.cookies().remove(name)
+    }
+
+    // Point in time by which the request must be answered, if any layer set one
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    // Layers can only tighten the deadline, never extend it
+    pub fn tighten_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(match self.deadline {
+            Some(current) => current.min(deadline),
+            None => deadline,
+        });
+    }
+
+    // Tighten the deadline to `timeout` from now
+    pub fn tighten_timeout(&mut self, timeout: Duration) {
+        self.tighten_deadline(Instant::now() + timeout);
+    }
+
+    // Time left before the deadline, zero once it has passed
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // Resolves when the client disconnects, or a write to it fails, before the response is done
+    pub fn cancelled(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.cancellation.clone().wait()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    // Ties the request to its log lines, the X-Request-Id it came with when that's usable
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn conn_info(&self) -> &ConnectionInfo {
+        &self.conn_info
+    }
+
+    pub(crate) fn set_conn_info(&mut self, conn_info: Arc<ConnectionInfo>) {
+        self.conn_info = conn_info;
+    }
+
+    // Value of type T registered with `Glote::state`, shared by every request
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        let value = self.state.get(&TypeId::of::<T>())?;
+        Arc::clone(value).downcast::<T>().ok()
+    }
+
+    pub(crate) fn set_state(&mut self, state: Arc<StateMap>) {
+        self.state = state;
+    }
+
+    pub(crate) fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+}
+
+// RFC 9110 token, what methods and header names are made of
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+// `Name:value` with optional whitespace around the value. Lines whose name isn't a
+// plain token (empty, whitespace before the colon, obs-fold continuations) are dropped.
+fn parse_header_line(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once(':')?;
+    if !is_token(name) {
+        return None;
+    }
+    Some((name.to_ascii_lowercase(), value.trim_matches([' ', '\t']).to_string()))
+}
+
+// Split `METHOD target HTTP/1.x` into method and target, refusing anything else
+fn parse_request_line(line: &str) -> Result<(&str, &str), ParseError> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let [method, target, version] = parts[..] else {
+        return Err(ParseError::RequestLine(line.chars().take(64).collect()));
+    };
+
+    if !is_token(method) {
+        return Err(ParseError::InvalidMethod(method.chars().take(64).collect()));
+    }
+    // Origin form, or `*` for server-wide OPTIONS
+    let printable = target.chars().all(|c| c.is_ascii_graphic());
+    if !printable || !(target.starts_with('/') || target == "*") {
+        return Err(ParseError::InvalidTarget(target.chars().take(64).collect()));
+    }
+    if !matches!(version, "HTTP/1.0" | "HTTP/1.1") {
+        return Err(ParseError::UnsupportedVersion(version.chars().take(64).collect()));
+    }
+
+    Ok((method, target))
+}
+
+// Why a request's body boundaries couldn't be trusted
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum FramingError {
+    InvalidContentLength,
+    ConflictingContentLength,
+    ContentLengthWithTransferEncoding,
+    UnsupportedTransferEncoding,
+    ChunkedNotFinal,
+    MalformedChunk,
+    BodyTooLarge,
+    FoldedFramingHeader,
+    WhitespaceBeforeColon,
 }
 
-fn parse_query(query_line: &str) -> HashMap<String, String> {
-    let mut querys = HashMap::<String, String>::new();
+// How the body of a request is delimited
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum BodyFraming {
+    Empty,
+    Length(usize),
+    Chunked,
+}
 
-    for query in query_line.split('&') {
-        let mut parts = query.splitn(2, '=');
-        if let (Some(key), Some(val)) = (parts.next(), parts.next()) {
-            querys.insert(key.to_string(), val.to_string());
+impl FramingError {
+    pub(crate) fn status(&self) -> u16 {
+        match self {
+            FramingError::UnsupportedTransferEncoding => 501,
+            FramingError::BodyTooLarge => 413,
+            _ => 400,
+        }
+    }
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            FramingError::InvalidContentLength => "invalid Content-Length",
+            FramingError::ConflictingContentLength => "conflicting Content-Length values",
+            FramingError::ContentLengthWithTransferEncoding => "both Content-Length and Transfer-Encoding",
+            FramingError::UnsupportedTransferEncoding => "unsupported Transfer-Encoding",
+            FramingError::ChunkedNotFinal => "chunked is not the final Transfer-Encoding",
+            FramingError::MalformedChunk => "malformed chunked body",
+            FramingError::BodyTooLarge => "body over the size limit",
+            FramingError::FoldedFramingHeader => "folded framing header",
+            FramingError::WhitespaceBeforeColon => "whitespace before a header colon",
+        };
+        f.write_str(reason)
+    }
+}
+
+// Work out the body framing from the header lines, refusing anything a proxy in
+// front of us could read differently
+pub(crate) fn body_framing(header_lines: &[String]) -> Result<BodyFraming, FramingError> {
+    let mut content_length: Option<usize> = None;
+    let mut transfer_codings: Vec<String> = Vec::new();
+    let mut transfer_encoding = false;
+    let mut previous_is_framing = false;
+
+    for line in header_lines {
+        // obs-fold continuation of Content-Length/Transfer-Encoding
+        if line.starts_with([' ', '\t']) {
+            if previous_is_framing {
+                return Err(FramingError::FoldedFramingHeader);
+            }
+            continue;
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            previous_is_framing = false;
+            continue;
+        };
+
+        let trimmed = name.trim_end();
+        let is_content_length = trimmed.eq_ignore_ascii_case("content-length");
+        let is_transfer_encoding = trimmed.eq_ignore_ascii_case("transfer-encoding");
+        previous_is_framing = is_content_length || is_transfer_encoding;
+
+        if !previous_is_framing {
+            continue;
+        }
+        // "Content-Length : 5" is read as a different header by some proxies
+        if trimmed.len() != name.len() {
+            return Err(FramingError::WhitespaceBeforeColon);
+        }
+
+        if is_transfer_encoding {
+            transfer_encoding = true;
+            transfer_codings.extend(
+                value
+                    .split(',')
+                    .map(|coding| coding.trim().to_ascii_lowercase())
+                    .filter(|coding| !coding.is_empty())
+            );
+            continue;
+        }
+
+        // A list like "5, 5" is allowed as long as every value agrees
+        for value in value.split(',') {
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(FramingError::InvalidContentLength);
+            }
+            let len = value.parse::<usize>().map_err(|_| FramingError::InvalidContentLength)?;
+            match content_length {
+                Some(existing) if existing != len => {
+                    return Err(FramingError::ConflictingContentLength);
+                }
+                _ => {
+                    content_length = Some(len);
+                }
+            }
+        }
+    }
+
+    if transfer_encoding {
+        // Both present is the CL.TE / TE.CL smuggling setup
+        if content_length.is_some() {
+            return Err(FramingError::ContentLengthWithTransferEncoding);
+        }
+        // Without chunked last the body would run until the connection closes
+        if transfer_codings.last().map(String::as_str) != Some("chunked") {
+            return Err(FramingError::ChunkedNotFinal);
+        }
+        // Other codings (gzip, ...) under chunked aren't decoded
+        if transfer_codings.len() > 1 {
+            return Err(FramingError::UnsupportedTransferEncoding);
+        }
+        return Ok(BodyFraming::Chunked);
+    }
+
+    Ok(content_length.map_or(BodyFraming::Empty, BodyFraming::Length))
+}
+
+// Longest chunk-size or trailer line accepted, extensions included
+const MAX_CHUNK_LINE: u64 = 4096;
+// Trailer fields accepted after the last chunk
+const MAX_TRAILERS: usize = 64;
+
+// Decode a chunked body: hex size lines, CRLF after each chunk, then trailers.
+// Chunks adding up to more than `max_size` are refused before they're read.
+pub(crate) async fn read_chunked<R>(reader: &mut R, max_size: usize) -> Result<Vec<u8>, FramingError>
+    where R: AsyncBufRead + Unpin
+{
+    let mut body = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        let size_line = read_chunk_line(reader, &mut line).await?;
+        // Chunk extensions (";name=value") are ignored
+        let size = size_line.split(';').next().unwrap_or_default().trim();
+        if size.is_empty() || size.len() > 16 || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(FramingError::MalformedChunk);
+        }
+        let size = usize::from_str_radix(size, 16).map_err(|_| FramingError::MalformedChunk)?;
+
+        if size == 0 {
+            // Trailers are read to keep the connection in sync but not exposed
+            for _ in 0..=MAX_TRAILERS {
+                if read_chunk_line(reader, &mut line).await?.is_empty() {
+                    return Ok(body);
+                }
+            }
+            return Err(FramingError::MalformedChunk);
+        }
+
+        let start = body.len();
+        if size > max_size - start {
+            return Err(FramingError::BodyTooLarge);
+        }
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await.map_err(|_| FramingError::MalformedChunk)?;
+
+        let mut crlf = [0u8; 2];
+        reader.read_exact(&mut crlf).await.map_err(|_| FramingError::MalformedChunk)?;
+        if &crlf != b"\r\n" {
+            return Err(FramingError::MalformedChunk);
+        }
+    }
+}
+
+// One CRLF-terminated line without its terminator
+async fn read_chunk_line<'a, R>(reader: &mut R, line: &'a mut String) -> Result<&'a str, FramingError>
+    where R: AsyncBufRead + Unpin
+{
+    line.clear();
+    let read = reader.take(MAX_CHUNK_LINE).read_line(line).await;
+    match read {
+        Ok(_) if line.ends_with("\r\n") => Ok(&line[..line.len() - 2]),
+        _ => Err(FramingError::MalformedChunk),
+    }
+}
+
+// `key=value` pairs separated by `&`, value is None for a bare `key`
+fn split_pairs(line: &str) -> impl Iterator<Item = (&str, Option<&str>)> {
+    line.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            match pair.split_once('=') {
+                Some((key, val)) => (key, Some(val)),
+                None => (pair, None),
+            }
+        })
+}
+
+fn parse_query(query_line: &str) -> HashMap<String, Vec<String>> {
+    let mut querys = HashMap::<String, Vec<String>>::new();
+
+    for (key, val) in split_pairs(query_line) {
+        if let Some(val) = val {
+            let key = form_decode(key);
+            querys.entry(array_key(&key).to_string()).or_default().push(form_decode(val));
         }
     }
 
     querys
 }
 
-pub fn parse_path_params(
-    route_pattern: &str,
-    actual_path: &str
-) -> Option<HashMap<String, String>> {
-    let mut params = HashMap::new();
+// `tag[]=a&tag[]=b` is stored under `tag`
+fn array_key(key: &str) -> &str {
+    key.strip_suffix("[]").unwrap_or(key)
+}
 
-    let pattern_parts = route_pattern.trim_matches('/').split('/');
-    let path_parts = actual_path.trim_matches('/').split('/');
+// application/x-www-form-urlencoded: `+` is a space, keys and values are percent-decoded
+fn parse_form(body: &str) -> HashMap<String, String> {
+    split_pairs(body)
+        .map(|(key, val)| (form_decode(key), val.map(form_decode).unwrap_or_default()))
+        .collect()
+}
 
-    let mut pattern_iter = pattern_parts.peekable();
-    let mut path_iter = path_parts.peekable();
+// Query and form component: `+` is a space, escapes that don't decode to UTF-8 are kept
+fn form_decode(part: &str) -> String {
+    let spaced = part.replace('+', " ");
+    percent_decode(&spaced).unwrap_or(spaced)
+}
 
-    while let (Some(pattern), Some(actual)) = (pattern_iter.next(), path_iter.next()) {
-        if pattern.starts_with(':') {
-            params.insert(pattern[1..].to_string(), actual.to_string());
-        } else if pattern != actual {
-            return None;
+// Decode %XX escapes, None when the result isn't UTF-8. Malformed escapes are kept as is.
+pub(crate) fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = bytes.get(i + 1..i + 3)
+            && hex.iter().all(u8::is_ascii_hexdigit)
+        {
+            let hex = std::str::from_utf8(hex).unwrap();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            i += 3;
+            continue;
         }
+        decoded.push(bytes[i]);
+        i += 1;
     }
+    String::from_utf8(decoded).ok()
+}
 
-    if pattern_iter.next().is_some() || path_iter.next().is_some() {
-        return None;
+// Path component, `+` is literal here
+fn path_decode(part: &str) -> String {
+    percent_decode(part).unwrap_or_else(|| part.to_string())
+}
+
+// Param values captured by a route pattern, percent-decoded. Matching ran on the raw
+// path so an encoded `%2F` stays inside its param.
+pub(crate) fn decode_path_params(captures: Vec<(&str, String)>) -> HashMap<String, String> {
+    captures
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), path_decode(&value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{ decode_path_params, parse_form, parse_query, percent_decode, Request };
+    use crate::error::ParseError;
+    use crate::router::{ split_path, Pattern };
+
+    fn parse(line: &str) -> Result<Request, ParseError> {
+        Request::new(&[line.to_string(), "Host: x".to_string()])
+    }
+
+    fn parse_path_params(pattern: &str, path: &str) -> Option<HashMap<String, String>> {
+        Pattern::parse(pattern).captures(&split_path(path), None).map(decode_path_params)
+    }
+
+    fn param(pattern: &str, path: &str, name: &str) -> Option<String> {
+        parse_path_params(pattern, path).map(|params| params.get(name).cloned().unwrap_or_default())
+    }
+
+    #[test]
+    fn lines_after_the_head_are_not_a_body() {
+        let lines = ["POST /upload HTTP/1.1", "Host: x", "", "a\r", "b", "X-Late: 1"].map(String::from);
+        let mut req = Request::new(&lines).unwrap();
+        assert_eq!(req.body_bytes, None);
+        assert_eq!(req.header("x-late"), None);
+
+        // The caller brings the raw bytes, kept as they are
+        req.body_bytes = Some(b"a\r\nb\0\r\n".to_vec());
+        assert_eq!(req.body().as_deref(), Some("a\r\nb\0\r\n"));
+    }
+
+    #[test]
+    fn wildcard_matches_zero_segments() {
+        assert_eq!(param("/files/*p", "/files", "p"), Some(String::new()));
+        assert_eq!(param("/files/*p", "/files/", "p"), Some(String::new()));
+    }
+
+    #[test]
+    fn wildcard_takes_nested_rest() {
+        assert_eq!(param("/files/*p", "/files/a/b/c.png", "p"), Some("a/b/c.png".into()));
+        assert_eq!(param("/files/*p", "/files/a.png", "p"), Some("a.png".into()));
+        assert_eq!(param("/*all", "/x/y", "all"), Some("x/y".into()));
+    }
+
+    #[test]
+    fn wildcard_after_params_and_literals() {
+        let params = parse_path_params("/u/:id/files/*path", "/u/7/files/docs/cv.pdf").unwrap();
+        assert_eq!(params["id"], "7");
+        assert_eq!(params["path"], "docs/cv.pdf");
+
+        assert!(parse_path_params("/u/:id/files/*path", "/u/7/other/cv.pdf").is_none());
+        assert!(parse_path_params("/files/*p", "/static/a.png").is_none());
+    }
+
+    #[test]
+    fn params_and_literals_still_exact() {
+        assert_eq!(param("/users/:id", "/users/42", "id"), Some("42".into()));
+        assert!(parse_path_params("/users/:id", "/users").is_none());
+        assert!(parse_path_params("/users/:id", "/users/42/x").is_none());
+        assert!(parse_path_params("/", "/").is_some());
+    }
+
+    #[test]
+    fn percent_decode_handles_escapes() {
+        assert_eq!(percent_decode("/%2e%2E%2Fa%20b").as_deref(), Some("/../a b"));
+        assert_eq!(percent_decode("/100%/%zz/%+1").as_deref(), Some("/100%/%zz/%+1"));
+        assert_eq!(percent_decode("/%C3%A9").as_deref(), Some("/\u{e9}"));
+        assert_eq!(percent_decode("/%FF"), None);
+    }
+
+    #[test]
+    fn form_keeps_empty_and_flag_keys() {
+        let form = parse_form("a=&b=1&flag&c%2Bd=x+y%26z&&");
+        assert_eq!(form.get("a").map(String::as_str), Some(""));
+        assert_eq!(form.get("b").map(String::as_str), Some("1"));
+        assert_eq!(form.get("flag").map(String::as_str), Some(""));
+        assert_eq!(form.get("c+d").map(String::as_str), Some("x y&z"));
+        assert_eq!(form.len(), 4);
     }
 
-    Some(params)
+    #[test]
+    fn query_values_are_decoded() {
+        let query = parse_query("q=hello%20world+again&name=J%C3%BCrgen&bad=%G1%&raw=%FF");
+        assert_eq!(query["q"], ["hello world again"]);
+        assert_eq!(query["name"], ["Jürgen"]);
+        assert_eq!(query["bad"], ["%G1%"]);
+        assert_eq!(query["raw"], ["%FF"]);
+    }
+
+    #[test]
+    fn repeated_query_keys_keep_every_value() {
+        let query = parse_query("tag=a&page=2&tag[]=b&tag%5B%5D=c&tag=&flag");
+        assert_eq!(query["tag"], ["a", "b", "c", ""]);
+        assert_eq!(query["page"], ["2"]);
+        assert!(!query.contains_key("flag"));
+        assert_eq!(query.len(), 2);
+    }
+
+    #[test]
+    fn path_params_are_decoded_after_matching() {
+        assert_eq!(param("/users/:name", "/users/J%C3%BCrgen", "name").as_deref(), Some("Jürgen"));
+        assert_eq!(param("/users/:name", "/users/a%20b+c", "name").as_deref(), Some("a b+c"));
+        assert_eq!(param("/users/:name", "/users/a%2Fb", "name").as_deref(), Some("a/b"));
+        assert_eq!(param("/users/:name", "/users/%G1", "name").as_deref(), Some("%G1"));
+        assert_eq!(param("/files/*p", "/files/a%20b/c", "p").as_deref(), Some("a b/c"));
+        assert!(parse_path_params("/users/:name", "/users/a/b").is_none());
+    }
+
+    #[test]
+    fn request_line_needs_method_target_and_version() {
+        let req = parse("GET  /a?b=1   HTTP/1.0").unwrap();
+        assert_eq!((req.method.as_str(), req.path.as_str()), ("GET", "/a"));
+        assert!(parse("OPTIONS * HTTP/1.1").is_ok());
+
+        assert_eq!(Request::new(&[]).unwrap_err(), ParseError::Empty);
+        assert!(matches!(parse(""), Err(ParseError::RequestLine(_))));
+        assert!(matches!(parse("GET /"), Err(ParseError::RequestLine(_))));
+        assert!(matches!(parse("GET / HTTP/1.1 extra"), Err(ParseError::RequestLine(_))));
+        assert!(matches!(parse("G(T / HTTP/1.1"), Err(ParseError::InvalidMethod(_))));
+        assert!(matches!(parse("GET users HTTP/1.1"), Err(ParseError::InvalidTarget(_))));
+        assert!(matches!(parse("GET /\u{7f} HTTP/1.1"), Err(ParseError::InvalidTarget(_))));
+        assert!(matches!(parse("GET / HTTP/2.0"), Err(ParseError::UnsupportedVersion(_))));
+        assert!(matches!(parse("GET / http/1.1"), Err(ParseError::UnsupportedVersion(_))));
+    }
+
+    #[test]
+    fn truncated_request_lines_never_panic() {
+        let line = "PATCH /users/7/files/a%20b?x=1&y[]=2 HTTP/1.1";
+        for end in 0..line.len() {
+            let _ = parse(&line[..end]);
+            let _ = parse(&line[end..]);
+        }
+        assert!(parse(line).is_ok());
+    }
+
+    #[test]
+    fn binary_junk_never_panics() {
+        // TLS ClientHello start, NULs and stray UTF-8
+        let fixed: [&[u8]; 4] = [
+            b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03",
+            b"\0\0\0 \0 \0",
+            "GET /é HTTP/1.1".as_bytes(),
+            b"\r\n\t \x0b\x0c",
+        ];
+        for junk in fixed {
+            let _ = parse(&String::from_utf8_lossy(junk));
+        }
+
+        // Pseudo-random bytes from a fixed seed, so failures reproduce
+        let mut seed: u32 = 0x9e37_79b9;
+        for _ in 0..2000 {
+            let len = (seed % 48) as usize;
+            let junk: Vec<u8> = (0..len)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    // Bias towards bytes that look like a request line
+                    match seed % 4 {
+                        0 => b' ',
+                        1 => b"GET/HTP1.?%*"[(seed >> 8) as usize % 12],
+                        _ => (seed >> 16) as u8,
+                    }
+                })
+                .collect();
+            let _ = parse(&String::from_utf8_lossy(&junk));
+        }
+    }
 }
@@ -1,16 +1,58 @@
-use tokio::{ net::TcpStream, io::{ AsyncWriteExt }, sync::RwLock };
-use std::{ collections::HashMap, sync::Arc };
+use tokio::{
+    fs::File,
+    io::{ self, duplex, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, ReadBuf },
+    net::TcpStream,
+    sync::{ watch, RwLock },
+};
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    path::Path,
+    sync::{ Arc, atomic::{ AtomicU16, Ordering } },
+    task::{ Context, Poll, Waker },
+    time::{ SystemTime, UNIX_EPOCH },
+};
 
 use serde::Serialize;
 
+use crate::cookie::{ CookieOptions, serialize_cookie };
+use crate::http_date::{ format_http_date, parse_http_date };
+use crate::error::{ DefaultErrorRenderer, Error, ErrorRenderer, StreamError };
+use crate::request::{ is_token, Cancellation, Req, Request };
+use crate::server::Next;
+use crate::sse::SseSender;
+use crate::testing::parse_response;
+use crate::transport::Transport;
+
 pub type Res = Arc<RwLock<Response>>;
 
+// Room in the pipe a captured response goes through, drained as it fills
+const CAPTURE_PIPE_CAPACITY: usize = 64 * 1024;
+
+#[allow(async_fn_in_trait)]
 pub trait ResponseExt {
     async fn with_write<F, Fut>(&self, f: F)
         where F: FnOnce(Res) -> Fut + Send, Fut: Future<Output = ()> + Send;
     async fn status(&self, code: u16);
+    async fn status_code(&self) -> u16;
     async fn send(&self, body: &str);
+    async fn html(&self, body: &str);
+    async fn text(&self, body: &str);
+    async fn send_status(&self, code: u16, body: &str);
     async fn json<T: Serialize>(&self, data: &T);
+    async fn json_with_status<T: Serialize>(&self, code: u16, data: &T);
+    async fn send_with_etag(&self, req: &Req, body: &[u8], content_type: &str);
+    async fn json_with_etag<T: Serialize>(&self, req: &Req, data: &T);
+    async fn send_error(&self, status: u16, req: &Req);
+    async fn redirect(&self, location: &str);
+    async fn redirect_with_status(&self, status: u16, location: &str);
+    async fn set_cookie(&self, name: &str, value: &str, options: &CookieOptions) -> Result<(), Error>;
+    async fn send_file(&self, path: impl AsRef<Path>);
+    async fn download(&self, path: impl AsRef<Path>, filename: &str);
+    async fn begin(&self, status: u16, headers: &[(&str, &str)]) -> Result<(), StreamError>;
+    async fn write_chunk(&self, bytes: &[u8]) -> Result<(), StreamError>;
+    async fn end(&self) -> Result<(), StreamError>;
+    async fn sse(&self) -> Result<SseSender, StreamError>;
 }
 
 impl ResponseExt for Res {
@@ -23,130 +65,958 @@ impl ResponseExt for Res {
     }
 
     async fn status(&self, code: u16) {
-        let mut res = self.write().await;
+        let res = self.read().await;
         res.status(code).await;
     }
 
+    async fn status_code(&self) -> u16 {
+        self.read().await.status_code()
+    }
+
     async fn send(&self, body: &str) {
         let res = self.read().await;
         res.send(body).await;
     }
 
+    async fn html(&self, body: &str) {
+        let res = self.read().await;
+        res.html(body).await;
+    }
+
+    async fn text(&self, body: &str) {
+        let res = self.read().await;
+        res.text(body).await;
+    }
+
+    async fn send_status(&self, code: u16, body: &str) {
+        let res = self.read().await;
+        res.send_status(code, body).await;
+    }
+
     async fn json<T: Serialize>(&self, data: &T) {
         let res = self.read().await;
         res.json(data).await;
     }
+
+    async fn json_with_status<T: Serialize>(&self, code: u16, data: &T) {
+        let res = self.read().await;
+        res.json_with_status(code, data).await;
+    }
+
+    async fn send_with_etag(&self, req: &Req, body: &[u8], content_type: &str) {
+        let req = req.read().await;
+        self.read().await.send_with_etag(body, content_type, &req).await;
+    }
+
+    async fn json_with_etag<T: Serialize>(&self, req: &Req, data: &T) {
+        let req = req.read().await;
+        self.read().await.json_with_etag(data, &req).await;
+    }
+
+    async fn send_error(&self, status: u16, req: &Req) {
+        let req = req.read().await;
+        let res = self.read().await;
+        res.send_error(status, &req).await;
+    }
+
+    async fn redirect(&self, location: &str) {
+        let res = self.read().await;
+        res.redirect(location).await;
+    }
+
+    async fn redirect_with_status(&self, status: u16, location: &str) {
+        let res = self.read().await;
+        res.redirect_with_status(status, location).await;
+    }
+
+    async fn set_cookie(&self, name: &str, value: &str, options: &CookieOptions) -> Result<(), Error> {
+        let res = self.read().await;
+        res.set_cookie(name, value, options).await
+    }
+
+    async fn send_file(&self, path: impl AsRef<Path>) {
+        let res = self.read().await;
+        res.send_file(path).await;
+    }
+
+    async fn download(&self, path: impl AsRef<Path>, filename: &str) {
+        let res = self.read().await;
+        res.download(path, filename).await;
+    }
+
+    async fn begin(&self, status: u16, headers: &[(&str, &str)]) -> Result<(), StreamError> {
+        let res = self.read().await;
+        res.begin(status, headers).await
+    }
+
+    async fn write_chunk(&self, bytes: &[u8]) -> Result<(), StreamError> {
+        let res = self.read().await;
+        res.write_chunk(bytes).await
+    }
+
+    async fn end(&self) -> Result<(), StreamError> {
+        let res = self.read().await;
+        res.end().await
+    }
+
+    async fn sse(&self) -> Result<SseSender, StreamError> {
+        let res = self.read().await;
+        res.sse().await
+    }
+}
+
+// How far a response got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SendState {
+    Pending,
+    // Head written by `begin`, body still going out
+    Streaming {
+        status: u16,
+        chunked: bool,
+    },
+    Sent(u16),
 }
 
 #[derive(Debug, Clone)]
 pub struct Response {
-    stream: Arc<RwLock<TcpStream>>,
-    status: u16,
-    pub headers: Arc<RwLock<HashMap<String, String>>>,
-    stopped: Arc<RwLock<bool>>,
+    stream: Arc<RwLock<Transport>>,
+    // Shared like the headers, so setting it only needs `&self`
+    status: Arc<AtomicU16>,
+    // A list rather than a map so names like Set-Cookie can repeat
+    pub headers: Arc<RwLock<Vec<(String, String)>>>,
+    state: Arc<RwLock<SendState>>,
+    error_renderer: Arc<dyn ErrorRenderer>,
+    // What the ErrorRenderer sees for errors raised without a Request at hand (send_file,
+    // redirect), set by the server to the request being answered
+    error_request: Arc<Request>,
+    // Set by the server, None for a Response built by hand
+    keep_alive: Option<bool>,
+    // Server shutdown in progress, the connection won't be reused
+    stopping: Option<watch::Receiver<bool>>,
+    // Answering a HEAD request: headers as for GET, body never written
+    head_only: bool,
+    // A clean EOF from the client is a half-close, not a disconnect, see `Glote::allow_half_close`
+    half_close: bool,
+    // The request's, fired when a write to the client fails
+    cancellation: Option<Cancellation>,
 }
 
 impl Response {
     pub fn new(stream: TcpStream) -> Self {
+        Self::from_shared(Arc::new(RwLock::new(Transport::Plain(stream))))
+    }
+
+    // Response on a connection the server keeps reading from
+    pub(crate) fn from_shared(stream: Arc<RwLock<Transport>>) -> Self {
         Self {
-            stream: Arc::new(RwLock::new(stream)),
-            status: 200,
-            headers: Arc::new(RwLock::new(HashMap::new())),
-            stopped: Arc::new(RwLock::new(false)),
+            stream,
+            status: Arc::new(AtomicU16::new(200)),
+            headers: Arc::new(RwLock::new(Vec::new())),
+            state: Arc::new(RwLock::new(SendState::Pending)),
+            error_renderer: Arc::new(DefaultErrorRenderer),
+            error_request: Arc::new(Request::refused(None)),
+            keep_alive: None,
+            stopping: None,
+            head_only: false,
+            half_close: false,
+            cancellation: None,
         }
     }
 
+    pub(crate) fn set_head_only(&mut self) {
+        self.head_only = true;
+    }
+
+    pub(crate) fn set_half_close(&mut self) {
+        self.half_close = true;
+    }
+
+    pub(crate) fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.cancellation = Some(cancellation);
+    }
+
+    // A failed write means the client is gone, the handler can stop
+    fn check_write<T>(&self, result: io::Result<T>) -> io::Result<T> {
+        if result.is_err()
+            && let Some(cancellation) = &self.cancellation
+        {
+            cancellation.cancel();
+        }
+        result
+    }
+
+    pub(crate) fn set_keep_alive(&mut self, keep_alive: bool, stopping: watch::Receiver<bool>) {
+        self.keep_alive = Some(keep_alive);
+        self.stopping = Some(stopping);
+    }
+
+    // Close the connection after this response, whatever the client asked for
+    pub(crate) fn close_connection(&mut self) {
+        self.keep_alive = Some(false);
+    }
+
+    // None when the server isn't managing the connection
+    fn connection(&self) -> Option<bool> {
+        let stopping = self.stopping.as_ref().is_some_and(|stopping| *stopping.borrow());
+        self.keep_alive.map(|keep_alive| keep_alive && !stopping)
+    }
+
+    // Whether the connection can carry another request: the response went out in full
+    // and neither side asked to close
+    pub(crate) async fn keeps_alive(&self) -> bool {
+        if self.connection() != Some(true) || !self.is_finished().await {
+            return false;
+        }
+        let headers = self.headers.read().await;
+        !headers
+            .iter()
+            .any(|(k, v)| k.eq_ignore_ascii_case("connection") && v.eq_ignore_ascii_case("close"))
+    }
+
+    pub(crate) fn set_error_renderer(&mut self, renderer: Arc<dyn ErrorRenderer>) {
+        self.error_renderer = renderer;
+    }
+
+    pub(crate) fn set_error_request(&mut self, req: Arc<Request>) {
+        self.error_request = req;
+    }
+
+    // Answer with an error status, body rendered by the server's ErrorRenderer
+    pub async fn send_error(&self, status: u16, req: &Request) {
+        self.send_error_with_headers(status, req, &[]).await;
+    }
+
+    pub(crate) async fn send_error_with_headers(
+        &self,
+        status: u16,
+        req: &Request,
+        extra: &[(&str, &str)]
+    ) {
+        let (content_type, body) = self.error_renderer.render(status, req);
+        self.status.store(status, Ordering::Relaxed);
+        self.send_bytes_with_headers(&body, &content_type, extra).await;
+    }
+
     pub async fn send_bytes(&self, bytes: &[u8], content_type: &str) {
-        let headers = format!(
-            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
-            self.status,
-            get_status_text(self.status),
-            content_type,
-            bytes.len()
-        );
+        self.send_bytes_with_headers(bytes, content_type, &[]).await;
+    }
+
+    // send_bytes plus headers only this response needs (e.g. Allow on a 405).
+    // Headers set by the user win over the ones derived from the send call.
+    async fn send_bytes_with_headers(&self, bytes: &[u8], content_type: &str, extra: &[(&str, &str)]) {
+        self.write_response(self.status_code(), bytes, content_type, extra).await;
+    }
+
+    // 304 head only, `validators` are the ETag and friends the full response would carry
+    async fn write_not_modified(&self, validators: &[(&str, &str)]) {
+        if !self.claim(304).await {
+            return;
+        }
+        let head = self.head(304, validators).await;
+        let mut stream = self.stream.write().await;
+        let _ = self.check_write(stream.write_all(head.as_bytes()).await);
+        let _ = self.check_write(stream.flush().await);
+    }
+
+    async fn write_response(
+        &self,
+        status: u16,
+        bytes: &[u8],
+        content_type: &str,
+        extra: &[(&str, &str)]
+    ) {
+        if !self.claim(status).await {
+            return;
+        }
+        let content_length = bytes.len().to_string();
+        // A 204 carries neither a body nor the headers describing one
+        let no_content = status == 204;
+        let mut defaults = Vec::new();
+        if !no_content {
+            defaults.extend([("Content-Type", content_type), ("Content-Length", content_length.as_str())]);
+        }
+        defaults.extend_from_slice(extra);
+        let head = self.head(status, &defaults).await;
+        let mut stream = self.stream.write().await;
+
+        let _ = self.check_write(stream.write_all(head.as_bytes()).await);
+        if !self.head_only && !no_content {
+            let _ = self.check_write(stream.write_all(bytes).await);
+        }
+        // TLS buffers records until flushed
+        let _ = self.check_write(stream.flush().await);
+    }
+
+    // Status line and headers, up to and including the blank line. `defaults` are
+    // left out when the user set a header of the same name.
+    async fn head(&self, status: u16, defaults: &[(&str, &str)]) -> String {
+        let user_headers = self.headers.read().await;
+        let user_set = |key: &str| user_headers.iter().any(|(k, _)| k.eq_ignore_ascii_case(key));
+
+        let mut head = format!("HTTP/1.1 {} {}\r\n", status, get_status_text(status));
+        let connection = self.connection().map(|keep| if keep { "keep-alive" } else { "close" });
+        // Anything that could end the line early is left out rather than written
+        for (key, value) in defaults.iter().chain(connection.map(|c| ("Connection", c)).as_ref()) {
+            if !user_set(key) && check_header(key, value).is_ok() {
+                head.push_str(&format!("{key}: {value}\r\n"));
+            }
+        }
+        for (key, value) in user_headers.iter() {
+            if check_header(key, value).is_ok() {
+                head.push_str(&format!("{key}: {value}\r\n"));
+            }
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    // Stream a file from disk, 404 when it doesn't exist
+    pub async fn send_file(&self, path: impl AsRef<Path>) {
+        self.send_file_with_headers(path.as_ref(), &[]).await;
+    }
+
+    // send_file as an attachment the browser saves under `filename`
+    pub async fn download(&self, path: impl AsRef<Path>, filename: &str) {
+        let disposition = content_disposition(filename);
+        self.send_file_with_headers(path.as_ref(), &[("Content-Disposition", &disposition)]).await;
+    }
+
+    async fn send_file_with_headers(&self, path: &Path, extra: &[(&str, &str)]) {
+        let file = match File::open(path).await {
+            Ok(file) => file,
+            Err(e) => {
+                let status = match e.kind() {
+                    io::ErrorKind::NotFound => 404,
+                    io::ErrorKind::PermissionDenied => 403,
+                    _ => 500,
+                };
+                self.write_error(status, &[]).await;
+                return;
+            }
+        };
+        if !self.send_open_file(file, path, extra, None).await {
+            self.write_error(404, &[]).await;
+        }
+    }
+
+    // Returns false without writing anything if `file` isn't a regular file. With the
+    // request's headers, a matching If-None-Match/If-Modified-Since gets a 304.
+    pub(crate) async fn send_open_file(
+        &self,
+        mut file: File,
+        path: &Path,
+        extra: &[(&str, &str)],
+        request_headers: Option<&HashMap<String, String>>
+    ) -> bool {
+        let Ok(metadata) = file.metadata().await else {
+            return false;
+        };
+        if !metadata.is_file() {
+            return false;
+        }
+
+        let modified = metadata.modified().ok();
+        let etag = weak_etag(metadata.len(), modified);
+        let last_modified = modified.map(format_http_date);
+        let mut validators = vec![("ETag", etag.as_str())];
+        if let Some(last_modified) = &last_modified {
+            validators.push(("Last-Modified", last_modified));
+        }
+        validators.extend_from_slice(extra);
+
+        if let Some(headers) = request_headers
+            && not_modified(headers, &etag, modified)
+        {
+            self.write_not_modified(&validators).await;
+            return true;
+        }
+
+        let len = metadata.len();
+        let range = request_headers
+            .and_then(|headers| {
+                let range = headers.get("range")?;
+                // A stale If-Range means the client wants the whole new file
+                match headers.get("if-range") {
+                    Some(if_range) if !if_range_matches(if_range, &etag) => None,
+                    _ => Some(parse_range(range, len)),
+                }
+            })
+            .unwrap_or(ByteRange::Full);
 
+        let (status, start, end, content_range) = match range {
+            ByteRange::Full => (self.status_code(), 0, len, None),
+            ByteRange::Partial(start, end) => {
+                (206, start, end + 1, Some(format!("bytes {start}-{end}/{len}")))
+            }
+            ByteRange::Unsatisfiable => {
+                let content_range = format!("bytes */{len}");
+                self.write_error(416, &[("Content-Range", &content_range)]).await;
+                return true;
+            }
+        };
+
+        let content_type = mime_guess::from_path(path).first_or_text_plain();
+        let content_length = (end - start).to_string();
+        let mut defaults = vec![
+            ("Content-Type", content_type.as_ref()),
+            ("Content-Length", content_length.as_str()),
+            ("Accept-Ranges", "bytes"),
+        ];
+        if let Some(content_range) = &content_range {
+            defaults.push(("Content-Range", content_range));
+        }
+        defaults.extend_from_slice(&validators);
+        if !self.claim(status).await {
+            return true;
+        }
+        let head = self.head(status, &defaults).await;
         let mut stream = self.stream.write().await;
 
-        let _ = stream.write_all(headers.as_bytes()).await;
-        let _ = stream.write_all(bytes).await;
+        let _ = self.check_write(stream.write_all(head.as_bytes()).await);
+        if !self.head_only {
+            if start > 0 {
+                let _ = file.seek(SeekFrom::Start(start)).await;
+            }
+            let _ = self.check_write(io::copy(&mut (&mut file).take(end - start), &mut *stream).await);
+        }
+        let _ = self.check_write(stream.flush().await);
+        true
+    }
+
+    // Error page through the ErrorRenderer, for callers without the Request at hand
+    async fn write_error(&self, status: u16, extra: &[(&str, &str)]) {
+        let (content_type, body) = self.error_renderer.render(status, &self.error_request);
+        self.write_response(status, &body, &content_type, extra).await;
+    }
 
-        self.stop().await;
+    // Header names are case-insensitive, so setting one replaces any other spelling of it.
+    // Names must be tokens and values free of CR, LF and other control characters,
+    // anything else is refused and nothing is set.
+    pub async fn set_header(&self, key: &str, value: &str) -> Result<(), Error> {
+        check_header(key, value)?;
+        let mut headers = self.headers.write().await;
+        headers.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        headers.push((key.to_string(), value.to_string()));
+        Ok(())
     }
 
-    pub async fn set_header(&self, key: &str, value: &str) {
+    // Add another header line without replacing existing ones with the same name
+    pub async fn append_header(&self, key: &str, value: &str) -> Result<(), Error> {
+        check_header(key, value)?;
         let mut headers = self.headers.write().await;
-        headers.insert(key.to_string(), value.to_string());
+        headers.push((key.to_string(), value.to_string()));
+        Ok(())
     }
 
+    // Refused like a bad header when the name isn't a token, the value isn't made of
+    // cookie-octets or an attribute could smuggle in `;` or control characters
+    pub async fn set_cookie(&self, name: &str, value: &str, options: &CookieOptions) -> Result<(), Error> {
+        self.append_header("Set-Cookie", &serialize_cookie(name, value, options)?).await
+    }
+
+    // Removes every line with this name
     pub async fn remove_header(&self, key: &str) {
         let mut headers = self.headers.write().await;
-        headers.remove(key);
+        headers.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
     }
 
-    async fn stop(&self) {
-        let mut s = self.stopped.write().await;
-        *s = true;
+    // Mark the response as sent before writing it. A second send would put another
+    // response on the wire mid-stream, so it's dropped with a warning instead.
+    async fn claim(&self, status: u16) -> bool {
+        let mut state = self.state.write().await;
+        if *state != SendState::Pending {
+            eprintln!("Response already sent, ignoring another send");
+            return false;
+        }
+        *state = SendState::Sent(status);
+        true
     }
 
+    // True once the head went out, streamed responses included
     pub async fn is_stopped(&self) -> bool {
-        let stopped = *self.stopped.read().await;
-        stopped.clone()
+        *self.state.read().await != SendState::Pending
     }
 
-    pub async fn status(&mut self, code: u16) {
-        self.status = code;
+    // Head and body are out, a stream has been ended
+    pub(crate) async fn is_finished(&self) -> bool {
+        matches!(*self.state.read().await, SendState::Sent(_))
     }
 
-    pub async fn send(&self, body: &str) {
-        let res = format!(
-            "HTTP/1.1 {} {}\r\nContent-Type: text/html; charset=UTF-8\r\nContent-Length: {}\r\n\r\n{}",
-            self.status,
-            get_status_text(self.status),
-            body.len(),
-            body
-        );
+    // Status line that actually went out, e.g. 304 or 206 for a file
+    pub async fn sent_status(&self) -> Option<u16> {
+        match *self.state.read().await {
+            SendState::Pending => None,
+            SendState::Streaming { status, .. } | SendState::Sent(status) => Some(status),
+        }
+    }
+
+    // Start a streamed response: status line and headers now, body through `write_chunk`.
+    // Without a Content-Length among the headers the body is sent chunked.
+    pub async fn begin(&self, status: u16, headers: &[(&str, &str)]) -> Result<(), StreamError> {
+        let mut state = self.state.write().await;
+        if *state != SendState::Pending {
+            return Err(StreamError::AlreadySent);
+        }
+        let has_length = headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("content-length")) ||
+            self.headers.read().await.iter().any(|(k, _)| k.eq_ignore_ascii_case("content-length"));
+        let mut defaults = headers.to_vec();
+        if has_length {
+            // Nothing checks the written length, so don't reuse the connection after it
+            let _ = self.set_header("Connection", "close").await;
+        } else {
+            defaults.push(("Transfer-Encoding", "chunked"));
+        }
+        let head = self.head(status, &defaults).await;
+        *state = SendState::Streaming { status, chunked: !has_length };
+        drop(state);
+
+        let mut stream = self.stream.write().await;
+        self.check_write(stream.write_all(head.as_bytes()).await).map_err(StreamError::Io)?;
+        self.check_write(stream.flush().await).map_err(StreamError::Io)
+    }
+
+    // Send part of a streamed body, flushed right away
+    pub async fn write_chunk(&self, bytes: &[u8]) -> Result<(), StreamError> {
+        let SendState::Streaming { chunked, .. } = *self.state.read().await else {
+            return Err(StreamError::NotStreaming);
+        };
+        // An empty chunk would end the body
+        if bytes.is_empty() || self.head_only {
+            return Ok(());
+        }
+        let mut stream = self.stream.write().await;
+        if chunked {
+            let size = format!("{:X}\r\n", bytes.len());
+            self.check_write(stream.write_all(size.as_bytes()).await).map_err(StreamError::Io)?;
+            self.check_write(stream.write_all(bytes).await).map_err(StreamError::Io)?;
+            self.check_write(stream.write_all(b"\r\n").await).map_err(StreamError::Io)?;
+        } else {
+            self.check_write(stream.write_all(bytes).await).map_err(StreamError::Io)?;
+        }
+        self.check_write(stream.flush().await).map_err(StreamError::Io)
+    }
+
+    // Switch to a Server-Sent Events stream, see `SseSender`
+    pub async fn sse(&self) -> Result<SseSender, StreamError> {
+        self.begin(200, &[("Content-Type", "text/event-stream"), ("Cache-Control", "no-cache")]).await?;
+        Ok(SseSender::new(self.clone()))
+    }
+
+    // Finish a streamed response
+    pub async fn end(&self) -> Result<(), StreamError> {
+        let mut state = self.state.write().await;
+        let SendState::Streaming { status, chunked } = *state else {
+            return Err(StreamError::NotStreaming);
+        };
+        *state = SendState::Sent(status);
+        drop(state);
 
         let mut stream = self.stream.write().await;
-        let _ = stream.write_all(res.as_bytes()).await;
-        // stream.flush().await;
+        if chunked && !self.head_only {
+            self.check_write(stream.write_all(b"0\r\n\r\n").await).map_err(StreamError::Io)?;
+        }
+        self.check_write(stream.flush().await).map_err(StreamError::Io)
+    }
 
-        self.stop().await;
+    // Non-blocking check for the client having closed or reset the connection. The request
+    // is read in full by now, so a clean EOF means it went away, unless half-closes are allowed.
+    pub(crate) async fn peer_closed(&self) -> bool {
+        // Already seen, the socket only reports a reset once
+        if self.cancellation.as_ref().is_some_and(Cancellation::is_cancelled) {
+            return true;
+        }
+        let stream = self.stream.read().await;
+        let mut byte = [0u8; 1];
+        let mut buf = ReadBuf::new(&mut byte);
+        let mut cx = Context::from_waker(Waker::noop());
+        // An in-memory client waits for its response, it never goes away early
+        let Some(tcp) = stream.tcp() else {
+            return false;
+        };
+        match tcp.poll_peek(&mut cx, &mut buf) {
+            Poll::Ready(Ok(0)) => !self.half_close,
+            Poll::Ready(Err(_)) => true,
+            _ => false,
+        }
+    }
+
+    // Run `next` with this response written into an in-memory pipe instead of the connection,
+    // then send what it wrote on here. Returns it for replaying to other requests, None when
+    // nothing was sent or it grew past `limit` bytes (the rest then streams straight through).
+    pub(crate) async fn capture(res: &Res, next: Next, limit: usize) -> Option<Captured> {
+        let original = res.read().await.clone();
+        let before = original.headers.read().await.clone();
+        let (sink_side, mut pipe) = duplex(CAPTURE_PIPE_CAPACITY);
+        // Same status, connection handling and error pages, its own stream and send state
+        let sink = Response {
+            stream: Arc::new(RwLock::new(Transport::Memory(sink_side))),
+            headers: Arc::new(RwLock::new(before.clone())),
+            state: Arc::new(RwLock::new(SendState::Pending)),
+            ..original.clone()
+        };
+        *res.write().await = sink.clone();
+        let mut restore = Restore { res: Arc::clone(res), original: Some(original.clone()) };
+
+        let run = async {
+            next().await;
+            // What the server would do for a stream the handler didn't end
+            if sink.is_stopped().await && !sink.is_finished().await {
+                let _ = sink.end().await;
+            }
+            let _ = sink.stream.write().await.shutdown().await;
+        };
+        let collect = async {
+            let mut buffered = Vec::new();
+            let mut overflowed = false;
+            let mut chunk = [0u8; 8192];
+            loop {
+                let n = match pipe.read(&mut chunk).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                buffered.extend_from_slice(&chunk[..n]);
+                // The sink renders exactly what the connection would get, so it can be
+                // forwarded as is
+                if overflowed || buffered.len() > limit {
+                    overflowed = true;
+                    let _ = original.check_write(original.stream.write().await.write_all(&buffered).await);
+                    buffered.clear();
+                }
+            }
+            (!overflowed).then_some(buffered)
+        };
+        let ((), buffered) = tokio::join!(run, collect);
+
+        restore.original = None;
+        *res.write().await = original.clone();
+
+        let Some(raw) = buffered else {
+            // Already on the wire, only the bookkeeping is left
+            *original.state.write().await = *sink.state.read().await;
+            *original.headers.write().await = sink.headers.read().await.clone();
+            let _ = original.check_write(original.stream.write().await.flush().await);
+            return None;
+        };
+        if !sink.is_stopped().await {
+            *original.headers.write().await = sink.headers.read().await.clone();
+            return None;
+        }
+        let received = parse_response(&raw, sink.head_only);
+        let captured = Captured {
+            status: received.status,
+            headers: received.headers
+                .into_iter()
+                .filter(|header| !before.contains(header) && !is_hop_by_hop(&header.0))
+                .collect(),
+            body: received.body,
+        };
+        original.replay(&captured).await;
+        Some(captured)
+    }
+
+    // Send a captured response as this one's, on top of the headers already set here
+    pub(crate) async fn replay(&self, captured: &Captured) {
+        {
+            let mut headers = self.headers.write().await;
+            headers.retain(|(key, _)| !captured.headers.iter().any(|(name, _)| key.eq_ignore_ascii_case(name)));
+            headers.extend(captured.headers.iter().cloned());
+        }
+        if captured.status == 304 {
+            self.write_not_modified(&[]).await;
+            return;
+        }
+        // The captured Content-Type wins over this fallback
+        self.write_response(captured.status, &captured.body, "application/octet-stream", &[]).await;
+    }
+
+    // Status for the next send. Returns the response so a send can follow,
+    // e.g. `res.status(201).await.json(&user).await`
+    pub async fn status(&self, code: u16) -> &Self {
+        self.status.store(code, Ordering::Relaxed);
+        self
+    }
+
+    // Status the next send will use
+    pub fn status_code(&self) -> u16 {
+        self.status.load(Ordering::Relaxed)
+    }
+
+    pub async fn send(&self, body: &str) {
+        self.html(body).await;
+    }
+
+    pub async fn html(&self, body: &str) {
+        self.send_bytes(body.as_bytes(), "text/html; charset=UTF-8").await;
+    }
+
+    pub async fn text(&self, body: &str) {
+        self.send_bytes(body.as_bytes(), "text/plain; charset=UTF-8").await;
+    }
+
+    // Status and body in one go
+    pub async fn send_status(&self, code: u16, body: &str) {
+        self.status(code).await.send(body).await;
     }
 
     pub async fn json<T: Serialize>(&self, data: &T) {
         let body = serde_json::to_string(data).unwrap();
+        self.send_bytes(body.as_bytes(), "application/json; charset=UTF-8").await;
+    }
 
-        let res = format!(
-            "HTTP/1.1 {} {}\r\nContent-Type: application/json; charset=UTF-8\r\nContent-Length: {}\r\n\r\n{}",
-            self.status,
-            get_status_text(self.status),
-            body.len(),
-            body
+    pub async fn json_with_status<T: Serialize>(&self, code: u16, data: &T) {
+        self.status(code).await.json(data).await;
+    }
+
+    // Body with a strong ETag from its hash. A GET or HEAD whose If-None-Match already
+    // has it gets 304 Not Modified without a body.
+    pub async fn send_with_etag(&self, body: &[u8], content_type: &str, req: &Request) {
+        let etag = strong_etag(body);
+        let revalidating = matches!(req.method.as_str(), "GET" | "HEAD") && self.status_code() == 200;
+        if revalidating
+            && req.headers.get("if-none-match").is_some_and(|if_none_match| none_match(if_none_match, &etag))
+        {
+            self.write_not_modified(&[("ETag", &etag)]).await;
+            return;
+        }
+        self.send_bytes_with_headers(body, content_type, &[("ETag", &etag)]).await;
+    }
+
+    pub async fn json_with_etag<T: Serialize>(&self, data: &T, req: &Request) {
+        let body = serde_json::to_string(data).unwrap();
+        self.send_with_etag(body.as_bytes(), "application/json; charset=UTF-8", req).await;
+    }
+
+    // 302 Found to `location`
+    pub async fn redirect(&self, location: &str) {
+        self.redirect_with_status(302, location).await;
+    }
+
+    // Redirect with an explicit status, e.g. 301, 303, 307 or 308. A location that can't be
+    // a header value (CR, LF, other control characters) is answered with 500 instead.
+    pub async fn redirect_with_status(&self, status: u16, location: &str) {
+        if self.set_header("Location", location).await.is_err() {
+            self.write_error(500, &[]).await;
+            return;
+        }
+        let body = format!(
+            "<a href=\"{}\">{} {}</a>",
+            escape_html(location),
+            status,
+            get_status_text(status)
         );
+        self.write_response(status, body.as_bytes(), "text/html; charset=UTF-8", &[]).await;
+    }
+}
 
-        let mut stream = self.stream.write().await;
-        let _ = stream.write_all(res.as_bytes()).await;
-        // stream.flush().await;
+// A response as a downstream chain sent it, see `Response::capture`
+#[derive(Debug, Clone)]
+pub(crate) struct Captured {
+    pub(crate) status: u16,
+    // Headers the chain added, Content-Type and Content-Length included
+    pub(crate) headers: Vec<(String, String)>,
+    // De-chunked, empty for HEAD
+    pub(crate) body: Vec<u8>,
+}
 
-        self.stop().await;
+impl Captured {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Cookies belong to the client they were set for, never hand them to another
+    pub(crate) fn is_shareable(&self) -> bool {
+        self.header("set-cookie").is_none()
+    }
+}
+
+// Puts the real response back when a capture ends early (panic, timeout, cancellation), so
+// the server answers on the connection rather than into the pipe
+struct Restore {
+    res: Res,
+    original: Option<Response>,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take()
+            && let Ok(mut res) = self.res.try_write()
+        {
+            *res = original;
+        }
     }
 }
 
-fn get_status_text(code: u16) -> &'static str {
+pub(crate) fn get_status_text(code: u16) -> &'static str {
     match code {
+        100 => "Continue",
         200 => "OK",
         201 => "Created",
         204 => "No Content",
+        206 => "Partial Content",
         301 => "Moved Permanently",
         302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
         400 => "Bad Request",
         401 => "Unauthorized",
         403 => "Forbidden",
         404 => "Not Found",
         405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        417 => "Expectation Failed",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
         500 => "Internal Server Error",
+        501 => "Not Implemented",
         502 => "Bad Gateway",
         503 => "Service Unavailable",
+        504 => "Gateway Timeout",
         _ => "Unknown",
     }
 }
+
+// RFC 9110 field name and value. Tabs are the only control character a value may hold.
+pub(crate) fn check_header(name: &str, value: &str) -> Result<(), Error> {
+    let invalid = |reason: &str| Error::InvalidHeader { name: name.to_string(), reason: reason.to_string() };
+    if !is_token(name) {
+        return Err(invalid("name is not a token"));
+    }
+    if value.chars().any(|c| c != '\t' && c.is_ascii_control()) {
+        return Err(invalid("value contains CR, LF or another control character"));
+    }
+    Ok(())
+}
+
+// Headers about this connection rather than the response, rewritten for each one sent
+fn is_hop_by_hop(name: &str) -> bool {
+    ["connection", "keep-alive", "transfer-encoding"].iter().any(|hop| name.eq_ignore_ascii_case(hop))
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    // Inclusive bounds
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// Single `bytes=` ranges only. Anything malformed or multi-range is served in full.
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    let Some((first, last)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let (first, last) = (first.trim(), last.trim());
+    // u64::from_str alone would accept "+5"
+    let parse = |n: &str| {
+        if n.bytes().all(|b| b.is_ascii_digit()) { n.parse::<u64>().ok() } else { None }
+    };
+
+    match (parse(first), parse(last)) {
+        // bytes=-500: the last 500 bytes
+        (None, Some(suffix)) if first.is_empty() => {
+            if suffix == 0 || len == 0 {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial(len.saturating_sub(suffix), len - 1)
+            }
+        }
+        // bytes=500-
+        (Some(start), None) if last.is_empty() => {
+            if start >= len { ByteRange::Unsatisfiable } else { ByteRange::Partial(start, len - 1) }
+        }
+        (Some(start), Some(end)) if start <= end => {
+            if start >= len { ByteRange::Unsatisfiable } else { ByteRange::Partial(start, end.min(len - 1)) }
+        }
+        _ => ByteRange::Full,
+    }
+}
+
+// W/"<size>-<mtime>" in hex, changes whenever the file is rewritten
+fn weak_etag(len: u64, modified: Option<SystemTime>) -> String {
+    let mtime = modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos())
+        .unwrap_or(0);
+    format!("W/\"{len:x}-{mtime:x}\"")
+}
+
+// "<len>-<FNV-1a of the body>" in hex, the same bytes always get the same tag
+fn strong_etag(body: &[u8]) -> String {
+    let hash = body
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| (hash ^ (byte as u64)).wrapping_mul(0x0100_0000_01b3));
+    format!("\"{:x}-{hash:016x}\"", body.len())
+}
+
+// Weak comparison against every tag in If-None-Match, "*" matches anything
+fn none_match(if_none_match: &str, etag: &str) -> bool {
+    let ours = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == ours)
+}
+
+// If-Range only takes a strong match (RFC 9110 13.1.5), a weak tag on either side never
+// satisfies it. Our ETags are weak, so any If-Range gets the whole file.
+fn if_range_matches(if_range: &str, etag: &str) -> bool {
+    let if_range = if_range.trim();
+    !if_range.starts_with("W/") && !etag.starts_with("W/") && if_range == etag
+}
+
+// If-None-Match wins over If-Modified-Since when both are sent
+fn not_modified(headers: &HashMap<String, String>, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get("if-none-match") {
+        return none_match(if_none_match, etag);
+    }
+    match (headers.get("if-modified-since").and_then(|v| parse_http_date(v)), modified) {
+        // HTTP dates have whole-second precision
+        (Some(since), Some(modified)) => {
+            let modified_secs = modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs());
+            let since_secs = since.duration_since(UNIX_EPOCH).map(|d| d.as_secs());
+            matches!((modified_secs, since_secs), (Ok(m), Ok(s)) if m <= s)
+        }
+        _ => false,
+    }
+}
+
+// attachment; filename="..." with an RFC 5987 filename* for non-ASCII names
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    if filename.is_ascii() && fallback == filename {
+        return format!("attachment; filename=\"{fallback}\"");
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
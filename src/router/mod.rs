@@ -0,0 +1,401 @@
+use std::{ any::TypeId, future::Future, sync::Arc, time::{ Duration, SystemTime, UNIX_EPOCH } };
+use tokio::sync::RwLock;
+
+use crate::error::ErrorRenderer;
+use crate::http_date::format_http_date;
+use crate::request::{ Request, StateMap };
+use crate::response::Response;
+use crate::server::{ wrap_handler, wrap_middleware, Handler, Middleware, MiddlewareFn, Next, Route };
+
+mod pattern;
+
+pub(crate) use pattern::{ collapse_slashes, split_path, split_path_strict, RouteIndex, ANY_METHOD };
+#[cfg(test)]
+pub(crate) use pattern::Pattern;
+
+// How request paths with extra slashes meet route patterns, see `Glote::trailing_slash`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    // "/users/", "//users" and "/users" all match "/users"
+    #[default]
+    Trim,
+    // Like Trim, but answered with a redirect to the canonical "/users"
+    Redirect,
+    // Every slash counts: "/users/" only matches "/users/", "//users" matches nothing
+    Strict,
+}
+
+// A group of routes sharing a path prefix and middleware, registered with `Glote::mount`
+pub struct Router {
+    prefix: String,
+    host: Option<String>,
+    deprecation: Option<Deprecation>,
+    cancel_on_disconnect: bool,
+    timeout: Option<Duration>,
+    middleware: Vec<Arc<Middleware>>,
+    routes: Vec<Route>,
+    // Only used when mounted as a tenant
+    state: StateMap,
+    not_found: Option<Arc<Handler>>,
+    error_renderer: Option<Arc<dyn ErrorRenderer>>,
+}
+
+// Which requests belong to a tenant, see `Glote::mount_tenant`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantSelector {
+    // Compared like `Router::host`, "*.example.com" included
+    Host(String),
+    // "/acme" takes "/acme" and everything under "/acme/", the tenant's routes are mounted there
+    Prefix(String),
+}
+
+impl TenantSelector {
+    // `host` normalized, `path` with slashes collapsed
+    pub(crate) fn matches(&self, host: Option<&str>, path: &str) -> bool {
+        match self {
+            TenantSelector::Host(pattern) => host.is_some_and(|host| host_matches(pattern, host)),
+            TenantSelector::Prefix(prefix) => {
+                let prefix = prefix.trim_end_matches('/');
+                path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }
+        }
+    }
+}
+
+// A router mounted with `Glote::mount_tenant`, everything it brings for its own requests
+pub(crate) struct Tenant {
+    pub(crate) selector: TenantSelector,
+    pub(crate) routes: Vec<Route>,
+    // Runs after global middleware for every request of the tenant, not_found included
+    pub(crate) middleware: Vec<Arc<Middleware>>,
+    pub(crate) state: StateMap,
+    pub(crate) not_found: Option<Arc<Handler>>,
+    pub(crate) error_renderer: Option<Arc<dyn ErrorRenderer>>,
+}
+
+// Deprecation notice sent with every response of a deprecated route, e.g.
+// `Deprecation::new(since).sunset(removal).link("https://example.com/migrate")`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    pub since: SystemTime,
+    // When the route is going away
+    pub sunset: Option<SystemTime>,
+    // Page explaining the deprecation or how to migrate
+    pub link: Option<String>,
+}
+
+impl Deprecation {
+    pub fn new(since: SystemTime) -> Self {
+        Self { since, sunset: None, link: None }
+    }
+
+    pub fn sunset(mut self, date: SystemTime) -> Self {
+        self.sunset = Some(date);
+        self
+    }
+
+    pub fn link(mut self, url: &str) -> Self {
+        self.link = Some(url.to_string());
+        self
+    }
+
+    // `Deprecation` as an RFC 9745 date, `Sunset` (RFC 8594) and a `Link` with rel="deprecation"
+    pub(crate) fn headers(&self) -> Vec<(&'static str, String)> {
+        let since = self.since.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut headers = vec![("Deprecation", format!("@{since}"))];
+        if let Some(sunset) = self.sunset {
+            headers.push(("Sunset", format_http_date(sunset)));
+        }
+        if let Some(link) = &self.link {
+            headers.push(("Link", format!("<{link}>; rel=\"deprecation\"")));
+        }
+        headers
+    }
+}
+
+// The route a request matched, in its extensions: `req.ext::<MatchedRoute>().await`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedRoute {
+    pub pattern: String,
+    pub deprecation: Option<Deprecation>,
+}
+
+impl Router {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+            middleware: Vec::new(),
+            routes: Vec::new(),
+            state: StateMap::new(),
+            not_found: None,
+            error_renderer: None,
+        }
+    }
+
+    // Middleware for every route in the group, after global middleware and before route middleware
+    pub fn use_middleware<F, Fut>(&mut self, middleware: F) -> &mut Self
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        self.middleware.push(wrap_middleware(middleware));
+        self
+    }
+
+    // Only match requests for `host`, compared case-insensitively without the port.
+    // "*.example.com" matches any subdomain. Nested groups keep their own host if set.
+    pub fn host(&mut self, host: &str) -> &mut Self {
+        self.host = Some(host.to_ascii_lowercase());
+        self
+    }
+
+    // Announce every route of the group as deprecated, see `Deprecation`. Nested groups
+    // keep their own notice if set.
+    pub fn deprecated(&mut self, deprecation: Deprecation) -> &mut Self {
+        self.deprecation = Some(deprecation);
+        self
+    }
+
+    // Drop a handler's future when its client resets the connection or a write to it fails,
+    // instead of letting it run to the end. Only for handlers that are safe to stop at any
+    // await, `req.cancelled()` works either way.
+    pub fn cancel_on_disconnect(&mut self) -> &mut Self {
+        self.cancel_on_disconnect = true;
+        self
+    }
+
+    // Answer 504 when a route of the group takes longer than `timeout`, counted like
+    // `Glote::request_timeout` and capped by it. Nested groups keep the tighter of the two.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // State for a tenant's handlers, on top of (and in place of same-typed) server state.
+    // Only used when mounted with `Glote::mount_tenant`.
+    pub fn state<T: Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+        self.state.insert(TypeId::of::<T>(), Arc::new(value));
+        self
+    }
+
+    // A tenant's own handler for requests none of its routes matched, like `Glote::not_found`.
+    // Only used when mounted with `Glote::mount_tenant`.
+    pub fn not_found<F, Fut>(&mut self, handler: F) -> &mut Self
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        self.not_found = Some(wrap_handler(handler));
+        self
+    }
+
+    // A tenant's own error pages, like `Glote::set_error_renderer`. Only used when mounted
+    // with `Glote::mount_tenant`.
+    pub fn set_error_renderer<R: ErrorRenderer + 'static>(&mut self, renderer: R) -> &mut Self {
+        self.error_renderer = Some(Arc::new(renderer));
+        self
+    }
+
+    // Nest another group under this one's prefix
+    pub fn mount(&mut self, router: Router) -> &mut Self {
+        self.routes.extend(router.into_routes());
+        self
+    }
+
+    // Route for an arbitrary method string
+    pub fn route<F, Fut>(&mut self, method: &str, path: &str, handler: F) -> &mut Self
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        self.routes.push(Route {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            middleware: Vec::new(),
+            handler: wrap_handler(handler),
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+        });
+        self
+    }
+
+    // Route for an arbitrary method string with middleware
+    pub fn route_with_middleware<Mfut, F, Ffut>(
+        &mut self,
+        method: &str,
+        path: &str,
+        middleware: Vec<MiddlewareFn<Mfut>>,
+        handler: F
+    ) -> &mut Self
+        where
+            Mfut: Future<Output = ()> + Send + 'static,
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+            Ffut: Future<Output = ()> + Send + 'static
+    {
+        self.routes.push(Route {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            middleware: middleware.into_iter().map(wrap_middleware).collect(),
+            handler: wrap_handler(handler),
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+        });
+        self
+    }
+
+    // Routes with the prefix applied and group middleware composed in front
+    pub(crate) fn into_routes(self) -> Vec<Route> {
+        let Router { prefix, host, deprecation, cancel_on_disconnect, timeout, middleware, routes, .. } = self;
+        routes
+            .into_iter()
+            .map(|route| {
+                let mut composed = middleware.clone();
+                composed.extend(route.middleware);
+                Route {
+                    path: join_paths(&prefix, &route.path),
+                    middleware: composed,
+                    host: route.host.or_else(|| host.clone()),
+                    deprecation: route.deprecation.or_else(|| deprecation.clone()),
+                    cancel_on_disconnect: route.cancel_on_disconnect || cancel_on_disconnect,
+                    timeout: route.timeout.into_iter().chain(timeout).min(),
+                    ..route
+                }
+            })
+            .collect()
+    }
+
+    // Routes as `into_routes`, under the selector's path for a prefix tenant. The group's
+    // middleware is kept apart to wrap the tenant's not_found too.
+    pub(crate) fn into_tenant(mut self, selector: TenantSelector) -> Tenant {
+        let state = std::mem::take(&mut self.state);
+        let not_found = self.not_found.take();
+        let error_renderer = self.error_renderer.take();
+        let middleware = std::mem::take(&mut self.middleware);
+        let mut routes = self.into_routes();
+        if let TenantSelector::Prefix(prefix) = &selector {
+            for route in &mut routes {
+                route.path = join_paths(prefix, &route.path);
+            }
+        }
+        Tenant { selector, routes, middleware, state, not_found, error_renderer }
+    }
+}
+
+// Per-method registration, same shapes as on Glote
+macro_rules! router_methods {
+    ($($method:expr => $name:ident, $with_middleware:ident;)*) => {
+        impl Router {
+            $(
+                pub fn $name<F, Fut>(&mut self, path: &str, handler: F) -> &mut Self
+                    where
+                        F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+                        Fut: Future<Output = ()> + Send + 'static
+                {
+                    self.route($method, path, handler)
+                }
+
+                pub fn $with_middleware<Mfut, F, Ffut>(
+                    &mut self,
+                    path: &str,
+                    middleware: Vec<MiddlewareFn<Mfut>>,
+                    handler: F
+                ) -> &mut Self
+                    where
+                        Mfut: Future<Output = ()> + Send + 'static,
+                        F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+                        Ffut: Future<Output = ()> + Send + 'static
+                {
+                    self.route_with_middleware($method, path, middleware, handler)
+                }
+            )*
+        }
+    };
+}
+
+router_methods! {
+    "GET" => get, get_with_middleware;
+    "POST" => post, post_with_middleware;
+    "PUT" => put, put_with_middleware;
+    "DELETE" => delete, delete_with_middleware;
+    "PATCH" => patch, patch_with_middleware;
+    "HEAD" => head, head_with_middleware;
+    "OPTIONS" => options, options_with_middleware;
+    ANY_METHOD => all, all_with_middleware;
+}
+
+// "/api/v1" + "/users" -> "/api/v1/users", "/api/v1" + "/" -> "/api/v1"
+fn join_paths(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    let path = path.trim_start_matches('/');
+
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, true) => "/".to_string(),
+        (_, true) => prefix.to_string(),
+        _ => format!("{prefix}/{path}"),
+    }
+}
+
+// Host header without the port and trailing dot, lowercased. IPv6 literals keep their brackets.
+pub(crate) fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split_once(']').map(|(addr, _)| &host[..addr.len() + 2]).unwrap_or(host),
+        None => host.split_once(':').map(|(name, _)| name).unwrap_or(host),
+    };
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+// `pattern` as given to `Router::host`, `host` normalized. "*.example.com" matches
+// "api.example.com" and "a.b.example.com", not "example.com".
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host.len() > suffix.len() + 1
+            && host.ends_with(suffix)
+            && host.as_bytes()[host.len() - suffix.len() - 1] == b'.',
+        None => pattern == host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ host_matches, normalize_host, TenantSelector };
+
+    #[test]
+    fn hosts_lose_port_and_case() {
+        assert_eq!(normalize_host("API.Example.com:8080"), "api.example.com");
+        assert_eq!(normalize_host("example.com."), "example.com");
+        assert_eq!(normalize_host("[::1]:3000"), "[::1]");
+        assert_eq!(normalize_host("localhost"), "localhost");
+    }
+
+    #[test]
+    fn wildcard_hosts_match_subdomains_only() {
+        assert!(host_matches("api.example.com", "api.example.com"));
+        assert!(!host_matches("api.example.com", "www.example.com"));
+        assert!(host_matches("*.example.com", "api.example.com"));
+        assert!(host_matches("*.example.com", "a.b.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+    }
+
+    #[test]
+    fn prefix_tenants_take_whole_segments() {
+        let acme = TenantSelector::Prefix("/acme/".to_string());
+        assert!(acme.matches(None, "/acme"));
+        assert!(acme.matches(None, "/acme/users"));
+        assert!(!acme.matches(None, "/acmeco/users"));
+        assert!(!acme.matches(None, "/"));
+
+        let host = TenantSelector::Host("*.example.com".to_string());
+        assert!(host.matches(Some("a.example.com"), "/"));
+        assert!(!host.matches(None, "/"));
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+// Method of routes registered through `all`, matches every request method
+pub(crate) const ANY_METHOD: &str = "*";
+
+// One `/`-separated piece of a route pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Literal(String),
+    // `:name`, exactly one path segment
+    Param(String),
+    // `*name`, the rest of the path, zero or more segments
+    Wildcard(String),
+}
+
+// Route pattern split into segments once, when the route table is built
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    segments: Vec<Segment>,
+    // "/users/" rather than "/users", only checked by strict matching
+    trailing_slash: bool,
+}
+
+// Split a path the way patterns are split, "/a/b/" -> ["a", "b"] and "/" -> [""]
+pub(crate) fn split_path(path: &str) -> Vec<&str> {
+    path.trim_matches('/').split('/').collect()
+}
+
+// Split a path keeping every slash significant: "//a" -> ["", "a"], and the trailing
+// slash of "/a/" is returned on its own
+pub(crate) fn split_path_strict(path: &str) -> (Vec<&str>, bool) {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    match path.strip_suffix('/') {
+        Some(trimmed) if !trimmed.is_empty() => (trimmed.split('/').collect(), true),
+        _ => (path.split('/').collect(), false),
+    }
+}
+
+// "/a//b/" -> "/a/b/"
+pub(crate) fn collapse_slashes(path: &str) -> String {
+    let mut collapsed = String::with_capacity(path.len());
+    for c in path.chars() {
+        if !(c == '/' && collapsed.ends_with('/')) {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+impl Pattern {
+    pub(crate) fn parse(pattern: &str) -> Self {
+        let segments = split_path(pattern)
+            .into_iter()
+            .map(|segment| {
+                if let Some(name) = segment.strip_prefix('*') {
+                    Segment::Wildcard(name.to_string())
+                } else if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else {
+                    Segment::Literal(segment.to_string())
+                }
+            })
+            .collect();
+        let trailing_slash = pattern.len() > 1 && pattern.ends_with('/');
+        Self { segments, trailing_slash }
+    }
+
+    // Raw (name, value) pairs when `path` matches. A wildcard takes the rest of the path,
+    // so "/files/*p" also matches "/files" and "/files/" with p = "". With
+    // `trailing_slash` given, it has to agree with the pattern's (wildcards aside).
+    pub(crate) fn captures(
+        &self,
+        path: &[&str],
+        trailing_slash: Option<bool>
+    ) -> Option<Vec<(&str, String)>> {
+        let mut captures = Vec::new();
+        let mut path_iter = path.iter();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Wildcard(name) => {
+                    let rest: Vec<&str> = path_iter.copied().collect();
+                    captures.push((name.as_str(), rest.join("/")));
+                    return Some(captures);
+                }
+                Segment::Param(name) => {
+                    captures.push((name.as_str(), path_iter.next()?.to_string()));
+                }
+                Segment::Literal(literal) => {
+                    if path_iter.next()? != literal {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        if trailing_slash.is_some_and(|trailing| trailing != self.trailing_slash) {
+            return None;
+        }
+        path_iter.next().is_none().then_some(captures)
+    }
+
+    fn first_literal(&self) -> Option<&str> {
+        match self.segments.first() {
+            Some(Segment::Literal(literal)) => Some(literal),
+            _ => None,
+        }
+    }
+}
+
+// Routes grouped by method and first literal segment, so a lookup only tries the routes
+// that could match. Candidates are tried in registration order: the first registered
+// matching route wins, as with a plain scan.
+#[derive(Debug, Clone)]
+pub(crate) struct RouteIndex<T> {
+    routes: Vec<(String, Pattern, T)>,
+    // method -> first literal -> route ids
+    by_literal: HashMap<String, HashMap<String, Vec<usize>>>,
+    // method -> ids of routes starting with a param or wildcard
+    dynamic: HashMap<String, Vec<usize>>,
+}
+
+impl<T> Default for RouteIndex<T> {
+    fn default() -> Self {
+        Self {
+            routes: Vec::new(),
+            by_literal: HashMap::new(),
+            dynamic: HashMap::new(),
+        }
+    }
+}
+
+impl<T> RouteIndex<T> {
+    pub(crate) fn insert(&mut self, method: &str, pattern: &str, value: T) {
+        let id = self.routes.len();
+        let pattern = Pattern::parse(pattern);
+        match pattern.first_literal() {
+            Some(literal) => {
+                self.by_literal
+                    .entry(method.to_string())
+                    .or_default()
+                    .entry(literal.to_string())
+                    .or_default()
+                    .push(id);
+            }
+            None => self.dynamic.entry(method.to_string()).or_default().push(id),
+        }
+        self.routes.push((method.to_string(), pattern, value));
+    }
+
+    // Ids of `method`'s routes that could match a path starting with `first`
+    fn candidates(&self, method: &str, first: &str, ids: &mut Vec<usize>) {
+        if let Some(ids_by_literal) = self.by_literal.get(method).and_then(|by| by.get(first)) {
+            ids.extend_from_slice(ids_by_literal);
+        }
+        if let Some(dynamic) = self.dynamic.get(method) {
+            ids.extend_from_slice(dynamic);
+        }
+    }
+
+    // First registered route for `method` (or any method) matching the split path
+    pub(crate) fn find(
+        &self,
+        method: &str,
+        path: &[&str],
+        trailing_slash: Option<bool>
+    ) -> Option<(&T, Vec<(&str, String)>)> {
+        let first = path.first().copied().unwrap_or_default();
+        let mut ids = Vec::new();
+        self.candidates(method, first, &mut ids);
+        if method != ANY_METHOD {
+            self.candidates(ANY_METHOD, first, &mut ids);
+        }
+        ids.sort_unstable();
+
+        ids.into_iter().find_map(|id| {
+            let (_, pattern, value) = &self.routes[id];
+            pattern.captures(path, trailing_slash).map(|captures| (value, captures))
+        })
+    }
+
+    // Methods with at least one route, in registration order. Routes for any method don't
+    // name one and are left out.
+    pub(crate) fn methods(&self) -> Vec<&str> {
+        let mut methods: Vec<&str> = Vec::new();
+        for (method, _, _) in &self.routes {
+            if method != ANY_METHOD && !methods.contains(&method.as_str()) {
+                methods.push(method);
+            }
+        }
+        methods
+    }
+
+    // Other methods with a route matching the path, in registration order (for 405)
+    pub(crate) fn allowed_methods(
+        &self,
+        method: &str,
+        path: &[&str],
+        trailing_slash: Option<bool>
+    ) -> Vec<&str> {
+        let first = path.first().copied().unwrap_or_default();
+        let mut ids = Vec::new();
+        let methods = self.by_literal.keys().chain(self.dynamic.keys());
+        let mut seen: Vec<&str> = Vec::new();
+        for other in methods {
+            if other != method && other != ANY_METHOD && !seen.contains(&other.as_str()) {
+                seen.push(other);
+                self.candidates(other, first, &mut ids);
+            }
+        }
+        ids.sort_unstable();
+
+        let mut allowed: Vec<&str> = Vec::new();
+        for id in ids {
+            let (other, pattern, _) = &self.routes[id];
+            if !allowed.contains(&other.as_str()) && pattern.captures(path, trailing_slash).is_some() {
+                allowed.push(other);
+            }
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ collapse_slashes, split_path, split_path_strict, RouteIndex, ANY_METHOD };
+
+    fn index(routes: &[(&str, &str)]) -> RouteIndex<usize> {
+        let mut index = RouteIndex::default();
+        for (id, (method, pattern)) in routes.iter().enumerate() {
+            index.insert(method, pattern, id);
+        }
+        index
+    }
+
+    fn find(index: &RouteIndex<usize>, method: &str, path: &str) -> Option<usize> {
+        index.find(method, &split_path(path), None).map(|(id, _)| *id)
+    }
+
+    fn find_strict(index: &RouteIndex<usize>, path: &str) -> Option<usize> {
+        let (segments, trailing_slash) = split_path_strict(path);
+        index.find("GET", &segments, Some(trailing_slash)).map(|(id, _)| *id)
+    }
+
+    #[test]
+    fn first_registered_route_wins() {
+        let index = index(&[
+            ("GET", "/users/:id"),
+            ("GET", "/users/me"),
+            (ANY_METHOD, "/*all"),
+            ("GET", "/"),
+        ]);
+        assert_eq!(find(&index, "GET", "/users/me"), Some(0));
+        assert_eq!(find(&index, "GET", "/users"), Some(2));
+        assert_eq!(find(&index, "POST", "/users/7"), Some(2));
+        assert_eq!(find(&index, "GET", "/"), Some(2));
+    }
+
+    #[test]
+    fn literal_buckets_dont_hide_dynamic_routes() {
+        let index = index(&[("GET", "/:section/list"), ("GET", "/posts/:id"), ("GET", "/")]);
+        assert_eq!(find(&index, "GET", "/posts/list"), Some(0));
+        assert_eq!(find(&index, "GET", "/posts/7"), Some(1));
+        assert_eq!(find(&index, "GET", "/"), Some(2));
+        assert_eq!(find(&index, "GET", "/posts/7/x"), None);
+    }
+
+    #[test]
+    fn allowed_methods_in_registration_order() {
+        let index = index(&[
+            ("PUT", "/items/:id"),
+            ("GET", "/items"),
+            ("DELETE", "/items/:id"),
+            ("PUT", "/items/:id/x"),
+            (ANY_METHOD, "/other"),
+        ]);
+        assert_eq!(index.allowed_methods("GET", &split_path("/items/7"), None), ["PUT", "DELETE"]);
+        assert!(index.allowed_methods("GET", &split_path("/nothing"), None).is_empty());
+    }
+
+    #[test]
+    fn strict_matching_keeps_every_slash() {
+        let index = index(&[("GET", "/users"), ("GET", "/posts/"), ("GET", "/"), ("GET", "/files/*p")]);
+        assert_eq!(find_strict(&index, "/users"), Some(0));
+        assert_eq!(find_strict(&index, "/users/"), None);
+        assert_eq!(find_strict(&index, "//users"), None);
+        assert_eq!(find_strict(&index, "/posts/"), Some(1));
+        assert_eq!(find_strict(&index, "/posts"), None);
+        assert_eq!(find_strict(&index, "/"), Some(2));
+        assert_eq!(find_strict(&index, "/files/a/"), Some(3));
+    }
+
+    #[test]
+    fn trimmed_matching_ignores_extra_slashes() {
+        let index = index(&[("GET", "/users"), ("GET", "/posts/"), ("GET", "/users/:id")]);
+        let find_collapsed = |path: &str| find(&index, "GET", &collapse_slashes(path));
+        assert_eq!(find_collapsed("/users/"), Some(0));
+        assert_eq!(find_collapsed("//users"), Some(0));
+        assert_eq!(find_collapsed("/posts"), Some(1));
+        assert_eq!(find_collapsed("/users//7/"), Some(2));
+        assert_eq!(collapse_slashes("//a///b/"), "/a/b/");
+    }
+}
@@ -1,20 +1,51 @@
 use tokio::{
     fs::File,
-    io::{ AsyncBufReadExt, AsyncReadExt, BufReader, ErrorKind },
-    net::TcpListener,
-    runtime::Runtime,
-    sync::RwLock,
+    io::{ AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, ErrorKind, ReadBuf },
+    net::{ lookup_host, TcpListener, TcpSocket, TcpStream, ToSocketAddrs },
+    runtime::{ Builder, Handle, Runtime },
+    sync::{ watch, OwnedRwLockWriteGuard, RwLock },
+    task::{ JoinHandle, JoinSet },
 };
-use std::{ future::Future, path::PathBuf, pin::Pin };
-use std::sync::{ Arc };
-use std::time::Instant;
-use mime_guess;
+use std::{ any::{ Any, TypeId }, backtrace::Backtrace, cell::{ Cell, RefCell }, collections::HashMap, future::Future, net::SocketAddr, panic::{ catch_unwind, AssertUnwindSafe }, path::{ Path, PathBuf }, pin::Pin, task::{ Context, Poll } };
+use std::sync::{ Arc, Once };
+use std::time::{ Duration, Instant };
 
 pub mod macros;
 
-use crate::request::{ parse_path_params, Request };
-use crate::response::Response;
-// use crate::workerpool::WorkerPool;
+use crate::cache::ResponseCache;
+#[cfg(feature = "compression")]
+use crate::compression;
+use crate::error::{ DefaultErrorRenderer, DevErrorRenderer, Error, ErrorRenderer, HandlerPanic };
+use crate::request::{
+    body_framing,
+    decode_path_params,
+    read_chunked,
+    BodyFraming,
+    percent_decode,
+    Cancellation,
+    ConnectionInfo,
+    Request,
+    StateMap,
+};
+use crate::response::{ get_status_text, Response, ResponseExt };
+use crate::router::{
+    collapse_slashes,
+    host_matches,
+    Deprecation,
+    MatchedRoute,
+    normalize_host,
+    split_path,
+    split_path_strict,
+    RouteIndex,
+    Router,
+    Tenant,
+    TenantSelector,
+    TrailingSlash,
+    ANY_METHOD,
+};
+use crate::transport::{ Acceptor, Transport };
+#[cfg(feature = "tls")]
+use crate::transport::ClientAuth;
 
 pub type Next = Box<dyn (FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>>) + Send + Sync>;
 
@@ -33,51 +64,633 @@ pub type Handler = dyn (Fn(
     Send +
     Sync;
 
+// Hook called once per answered request, see `Glote::on_request`
+pub type RequestLogger = dyn Fn(RequestLog) + Send + Sync;
+
+// What `on_request` hooks get to see about a finished request
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    // `Request::id`, what to grep for when a client reports a failed request
+    pub request_id: String,
+    pub method: String,
+    pub path: String,
+    // Pattern of the route that handled the request, None for static files and errors
+    pub route: Option<String>,
+    pub status: u16,
+    pub duration: Duration,
+    pub peer_addr: Option<SocketAddr>,
+    // File sent by the static handler
+    pub static_file: Option<PathBuf>,
+    // Message of a handler or middleware panic, answered with a 500
+    pub panic: Option<String>,
+    // Why the request was refused before reaching routing, e.g. a header timeout
+    pub error: Option<String>,
+}
+
+// Colored line per request on stdout, what Glote logs unless told otherwise
+fn default_logger(log: RequestLog) {
+    if let Some(file) = &log.static_file {
+        println!("\x1b[34mSTATIC {}: {:?}\x1b[0m", file.display(), log.duration);
+        return;
+    }
+    let color = if log.status >= 400 { 31 } else { 32 };
+    println!(
+        "\x1b[{color}m{} {}: {} {:?}\x1b[0m ",
+        log.method,
+        log.path,
+        log.status,
+        log.duration
+    );
+    if let Some(message) = &log.panic {
+        println!("\x1b[31m  handler panicked (request {}): {message}\x1b[0m", log.request_id);
+    }
+    if let Some(reason) = &log.error {
+        println!("\x1b[31m  refused: {reason}\x1b[0m");
+    }
+}
+
+// Plain fn middleware accepted by the *_with_middleware registrations
+pub type MiddlewareFn<Fut> = fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Fut;
+
+// Default cap on the request target, longer ones get 414
+const DEFAULT_MAX_URI_LENGTH: usize = 8 * 1024;
+// Default caps on header lines, past any of them the client gets 431
+const DEFAULT_MAX_HEADER_LINE_LENGTH: usize = 8 * 1024;
+const DEFAULT_MAX_HEADER_COUNT: usize = 100;
+const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+
+// Default cap on request bodies, larger ones get 413
+const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+// Time a client gets to send the request line and headers, and then the body
+const DEFAULT_READ_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_READ_BODY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Idle time after which a keep-alive connection is closed
+const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Time a client gets to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long shutdown waits for in-flight requests before aborting them
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+// How often an in-flight request checks whether its client is still there
+const DISCONNECT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 // Metadata of routes
 #[derive(Clone)]
-struct Route {
-    method: String,
-    path: String,
-    middleware: Vec<Arc<Middleware>>,
-    handler: Arc<Handler>,
+pub(crate) struct Route {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) middleware: Vec<Arc<Middleware>>,
+    pub(crate) handler: Arc<Handler>,
+    // Lowercase host name or "*.suffix" wildcard, None matches any host
+    pub(crate) host: Option<String>,
+    pub(crate) deprecation: Option<Deprecation>,
+    // Drop the handler future once the client is gone, see `Router::cancel_on_disconnect`
+    pub(crate) cancel_on_disconnect: bool,
+    // Tighter cap than `request_timeout` for this route, see `Router::timeout`
+    pub(crate) timeout: Option<Duration>,
+}
+
+// Routes as dispatched: global middleware already composed in
+#[derive(Clone)]
+struct DispatchTable {
+    // Routes for any host
+    routes: Arc<RouteIndex<Route>>,
+    // Host-constrained routes per host pattern, exact names before wildcards
+    host_routes: Arc<Vec<(String, RouteIndex<Route>)>>,
+    global_middleware: Arc<Vec<Arc<Middleware>>>,
+    not_found: Option<Arc<Handler>>,
+    trailing_slash: TrailingSlash,
+    // Tenants in mount order, each with a table of its own
+    tenants: Arc<Vec<(TenantSelector, DispatchTable)>>,
+    // Set on a tenant's table: the state its requests see, static files are never served
+    tenant_state: Option<Arc<StateMap>>,
+    error_renderer: Option<Arc<dyn ErrorRenderer>>,
+    stale: bool,
+}
+
+impl DispatchTable {
+    // The table a request is dispatched from: its tenant's, else this one. A tenant's
+    // requests never fall through to routes outside it.
+    fn for_request(&self, host: Option<&str>, path: &str) -> &DispatchTable {
+        self.tenants
+            .iter()
+            .find(|(selector, _)| selector.matches(host, path))
+            .map(|(_, tenant)| tenant)
+            .unwrap_or(self)
+    }
+
+    // Indexes to try for `host` in order: its own routes, then routes for any host
+    fn indexes(&self, host: Option<&str>) -> impl Iterator<Item = &RouteIndex<Route>> {
+        self.host_routes
+            .iter()
+            .filter(move |(pattern, _)| host.is_some_and(|host| host_matches(pattern, host)))
+            .map(|(_, index)| index)
+            .chain(std::iter::once(&*self.routes))
+    }
+
+    fn find(
+        &self,
+        host: Option<&str>,
+        method: &str,
+        path: &[&str],
+        trailing_slash: Option<bool>
+    ) -> Option<(&Route, Vec<(&str, String)>)> {
+        self.indexes(host).find_map(|index| index.find(method, path, trailing_slash))
+    }
+
+    fn allowed_methods(
+        &self,
+        host: Option<&str>,
+        method: &str,
+        path: &[&str],
+        trailing_slash: Option<bool>
+    ) -> Vec<&str> {
+        let mut allowed: Vec<&str> = Vec::new();
+        for index in self.indexes(host) {
+            for other in index.allowed_methods(method, path, trailing_slash) {
+                if !allowed.contains(&other) {
+                    allowed.push(other);
+                }
+            }
+        }
+        allowed
+    }
+
+    // Every method with a route reachable from `host`, for `OPTIONS *`
+    fn methods(&self, host: Option<&str>) -> Vec<&str> {
+        let mut methods: Vec<&str> = Vec::new();
+        for index in self.indexes(host) {
+            for method in index.methods() {
+                if !methods.contains(&method) {
+                    methods.push(method);
+                }
+            }
+        }
+        methods
+    }
+}
+
+// Every pattern valid and none registered twice for the same method and host
+fn check_routes(routes: &[Route], trailing_slash: TrailingSlash) -> Result<(), Error> {
+    for (idx, route) in routes.iter().enumerate() {
+        validate_pattern(&route.path)?;
+
+        let key = pattern_key(&route.path, trailing_slash);
+        if let Some(existing) = routes[..idx]
+            .iter()
+            .find(|other| {
+                other.method == route.method &&
+                    other.host == route.host &&
+                    pattern_key(&other.path, trailing_slash) == key
+            })
+        {
+            return Err(Error::DuplicateRoute {
+                method: route.method.clone(),
+                pattern: route.path.clone(),
+                existing: existing.path.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Routes for any host and per host pattern, global middleware composed in front
+fn index_routes(
+    routes: &[Route],
+    global_middleware: &[Arc<Middleware>]
+) -> (RouteIndex<Route>, Vec<(String, RouteIndex<Route>)>) {
+    let mut index = RouteIndex::default();
+    let mut host_routes: Vec<(String, RouteIndex<Route>)> = Vec::new();
+    for route in routes {
+        let mut middleware = global_middleware.to_vec();
+        middleware.extend(route.middleware.iter().cloned());
+        let dispatched = Route { middleware, ..route.clone() };
+        let index = match &route.host {
+            None => &mut index,
+            Some(host) => {
+                let at = match host_routes.iter().position(|(pattern, _)| pattern == host) {
+                    Some(at) => at,
+                    None => {
+                        host_routes.push((host.clone(), RouteIndex::default()));
+                        host_routes.len() - 1
+                    }
+                };
+                &mut host_routes[at].1
+            }
+        };
+        index.insert(&route.method, &route.path, dispatched);
+    }
+    // Exact hosts first, then wildcards from the longest suffix down. Stable, so ties
+    // keep registration order.
+    host_routes.sort_by_key(|(pattern, _)| {
+        match pattern.strip_prefix("*.") {
+            Some(suffix) => (1, std::cmp::Reverse(suffix.len())),
+            None => (0, std::cmp::Reverse(0)),
+        }
+    });
+    (index, host_routes)
+}
+
+// Allow value for the methods a path takes. HEAD is answered by GET routes and OPTIONS by
+// the automatic responder, so both are listed when they would be.
+fn allow_list(mut methods: Vec<&str>, auto_options: bool) -> String {
+    if let Some(get) = methods.iter().position(|&method| method == "GET") && !methods.contains(&"HEAD") {
+        methods.insert(get + 1, "HEAD");
+    }
+    if auto_options && !methods.contains(&"OPTIONS") {
+        methods.push("OPTIONS");
+    }
+    methods.join(", ")
 }
 
 pub struct Glote {
     routes: Arc<RwLock<Vec<Route>>>,
+    tenants: Arc<RwLock<Vec<Tenant>>>,
+    dispatch: Arc<RwLock<DispatchTable>>,
+    // A finalize succeeded, routes registered from now on are checked as they come
+    finalized: Arc<RwLock<bool>>,
     middleware: Arc<RwLock<Vec<Arc<Middleware>>>>,
-    // pool: WorkerPool,
-    static_path: Arc<RwLock<Option<String>>>,
-    runtime: Runtime,
+    not_found: Arc<RwLock<Option<Arc<Handler>>>>,
+    // Canonical static root
+    static_path: Arc<RwLock<Option<PathBuf>>>,
+    // Canonical static roots per host pattern, used before `static_path`
+    static_hosts: Arc<RwLock<Vec<(String, PathBuf)>>>,
+    static_cache_control: Arc<RwLock<Option<String>>>,
+    max_uri_length: Arc<RwLock<usize>>,
+    max_header_line_length: Arc<RwLock<usize>>,
+    max_header_count: Arc<RwLock<usize>>,
+    max_header_size: Arc<RwLock<usize>>,
+    trailing_slash: Arc<RwLock<TrailingSlash>>,
+    // OPTIONS without its own route answered with the Allow list
+    auto_options: Arc<RwLock<bool>>,
+    max_body_size: Arc<RwLock<usize>>,
+    keep_alive_timeout: Arc<RwLock<Duration>>,
+    read_header_timeout: Arc<RwLock<Duration>>,
+    read_body_timeout: Arc<RwLock<Duration>>,
+    // Clients may shut down their sending side and still wait for the answer
+    half_close: Arc<RwLock<bool>>,
+    // Cap on the whole middleware + handler chain, off by default
+    request_timeout: Arc<RwLock<Option<Duration>>>,
+    shutdown_grace_period: Arc<RwLock<Duration>>,
+    local_addr: Arc<RwLock<Option<SocketAddr>>>,
+    #[cfg(feature = "tls")]
+    client_auth: Arc<RwLock<ClientAuth>>,
+    error_renderer: Arc<RwLock<Arc<dyn ErrorRenderer>>>,
+    // DevErrorRenderer in place of `error_renderer`
+    dev_errors: Arc<RwLock<bool>>,
+    // Refuses dev_errors
+    production: Arc<RwLock<bool>>,
+    // None when quiet
+    request_logger: Arc<RwLock<Option<Arc<RequestLogger>>>>,
+    // No startup and shutdown banners either
+    quiet: Arc<RwLock<bool>>,
+    // Caches `purge_cache` reaches, see `register_cache`
+    caches: Arc<RwLock<Vec<Arc<ResponseCache>>>>,
+    // Copied on write so each request only clones the outer Arc
+    state: Arc<RwLock<Arc<StateMap>>>,
+    // Decompressed body size cap, None keeps request decompression off
+    #[cfg(feature = "compression")]
+    decompress_limit: Arc<RwLock<Option<usize>>>,
+    runtime: ServerRuntime,
+}
+
+// Either built by Glote or lent by an app that already runs tokio
+enum ServerRuntime {
+    Owned(Runtime),
+    Borrowed(Handle),
+}
+
+impl ServerRuntime {
+    fn handle(&self) -> &Handle {
+        match self {
+            ServerRuntime::Owned(runtime) => runtime.handle(),
+            ServerRuntime::Borrowed(handle) => handle,
+        }
+    }
 }
 
 impl Glote {
-    // Returns Arc self
+    // Returns Arc self, on a multi-threaded runtime with one worker per core
     pub fn new() -> Arc<Self> {
+        Self::with_runtime(
+            ServerRuntime::Owned(Runtime::new().expect("Failed to create Tokio runtime"))
+        )
+    }
+
+    // Multi-threaded runtime with `workers` worker threads, at least one
+    pub fn with_workers(workers: usize) -> Arc<Self> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(workers.max(1))
+            .enable_all()
+            .build()
+            .expect("Failed to create Tokio runtime");
+        Self::with_runtime(ServerRuntime::Owned(runtime))
+    }
+
+    // Everything runs on the thread calling `block_on`, for embedded or single-core use
+    pub fn current_thread() -> Arc<Self> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to create Tokio runtime");
+        Self::with_runtime(ServerRuntime::Owned(runtime))
+    }
+
+    // Run on a runtime the app already owns. `block_on` can't be called from inside it,
+    // await `listen` on that runtime instead.
+    pub fn with_runtime_handle(handle: Handle) -> Arc<Self> {
+        Self::with_runtime(ServerRuntime::Borrowed(handle))
+    }
+
+    fn with_runtime(runtime: ServerRuntime) -> Arc<Self> {
         Arc::new(Self {
             routes: Arc::new(RwLock::new(Vec::new())),
+            tenants: Arc::new(RwLock::new(Vec::new())),
+            dispatch: Arc::new(
+                RwLock::new(DispatchTable {
+                    routes: Arc::new(RouteIndex::default()),
+                    host_routes: Arc::new(Vec::new()),
+                    global_middleware: Arc::new(Vec::new()),
+                    not_found: None,
+                    trailing_slash: TrailingSlash::default(),
+                    tenants: Arc::new(Vec::new()),
+                    tenant_state: None,
+                    error_renderer: None,
+                    stale: true,
+                })
+            ),
+            finalized: Arc::new(RwLock::new(false)),
             middleware: Arc::new(RwLock::new(Vec::new())),
+            not_found: Arc::new(RwLock::new(None)),
             static_path: Arc::new(RwLock::new(None)),
-            runtime: tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime"),
+            static_hosts: Arc::new(RwLock::new(Vec::new())),
+            static_cache_control: Arc::new(RwLock::new(None)),
+            max_uri_length: Arc::new(RwLock::new(DEFAULT_MAX_URI_LENGTH)),
+            max_header_line_length: Arc::new(RwLock::new(DEFAULT_MAX_HEADER_LINE_LENGTH)),
+            max_header_count: Arc::new(RwLock::new(DEFAULT_MAX_HEADER_COUNT)),
+            max_header_size: Arc::new(RwLock::new(DEFAULT_MAX_HEADER_SIZE)),
+            trailing_slash: Arc::new(RwLock::new(TrailingSlash::default())),
+            auto_options: Arc::new(RwLock::new(true)),
+            max_body_size: Arc::new(RwLock::new(DEFAULT_MAX_BODY_SIZE)),
+            keep_alive_timeout: Arc::new(RwLock::new(DEFAULT_KEEP_ALIVE_TIMEOUT)),
+            read_header_timeout: Arc::new(RwLock::new(DEFAULT_READ_HEADER_TIMEOUT)),
+            read_body_timeout: Arc::new(RwLock::new(DEFAULT_READ_BODY_TIMEOUT)),
+            half_close: Arc::new(RwLock::new(false)),
+            request_timeout: Arc::new(RwLock::new(None)),
+            shutdown_grace_period: Arc::new(RwLock::new(DEFAULT_SHUTDOWN_GRACE_PERIOD)),
+            local_addr: Arc::new(RwLock::new(None)),
+            #[cfg(feature = "tls")]
+            client_auth: Arc::new(RwLock::new(ClientAuth::None)),
+            error_renderer: Arc::new(RwLock::new(Arc::new(DefaultErrorRenderer))),
+            dev_errors: Arc::new(RwLock::new(false)),
+            production: Arc::new(RwLock::new(false)),
+            request_logger: Arc::new(RwLock::new(Some(Arc::new(default_logger)))),
+            quiet: Arc::new(RwLock::new(false)),
+            caches: Arc::new(RwLock::new(Vec::new())),
+            state: Arc::new(RwLock::new(Arc::new(HashMap::new()))),
+            #[cfg(feature = "compression")]
+            decompress_limit: Arc::new(RwLock::new(None)),
+            runtime,
         })
     }
 
     pub fn block_on<F: Future>(&self, fut: F) -> F::Output {
-        self.runtime.block_on(fut)
+        match &self.runtime {
+            ServerRuntime::Owned(runtime) => runtime.block_on(fut),
+            ServerRuntime::Borrowed(handle) => handle.block_on(fut),
+        }
+    }
+
+    // Handle of the runtime the server runs on
+    pub fn runtime_handle(&self) -> Handle {
+        self.runtime.handle().clone()
+    }
+
+    // Run blocking work (file parsing, hashing, sync clients) off the async workers
+    pub fn spawn_blocking<F, R>(&self, f: F) -> JoinHandle<R>
+        where F: FnOnce() -> R + Send + 'static, R: Send + 'static
+    {
+        self.runtime.handle().spawn_blocking(f)
     }
 
+    // Directory served for requests no route matched. One that can't be resolved is
+    // reported and leaves static serving off.
     pub async fn static_path(&self, path: &str) {
-        let static_path = Arc::clone(&self.static_path);
-        *static_path.write().await = Some(path.into());
+        let root = canonical_static_root(path).await;
+        *self.static_path.write().await = root;
+    }
+
+    // Static files for requests to `host` only, same host patterns as `Router::host`.
+    // Other hosts fall back to `static_path`.
+    pub async fn static_path_for_host(&self, host: &str, path: &str) {
+        let root = canonical_static_root(path).await;
+        let host = host.to_ascii_lowercase();
+        let mut static_hosts = self.static_hosts.write().await;
+        static_hosts.retain(|(pattern, _)| *pattern != host);
+        if let Some(root) = root {
+            static_hosts.push((host, root));
+        }
+    }
+
+    // Static root for the request's host, exact names before wildcards
+    async fn static_root(&self, req: &Request) -> Option<PathBuf> {
+        if let Some(host) = req.headers.get("host").map(|host| normalize_host(host)) {
+            let static_hosts = self.static_hosts.read().await;
+            let exact = static_hosts.iter().find(|(pattern, _)| *pattern == host);
+            let root = exact.or_else(|| static_hosts.iter().find(|(pattern, _)| host_matches(pattern, &host)));
+            if let Some((_, root)) = root {
+                return Some(root.clone());
+            }
+        }
+        self.static_path.read().await.clone()
+    }
+
+    // Cache-Control sent with every static file, e.g. "public, max-age=3600"
+    pub async fn static_cache_control(&self, value: &str) {
+        *self.static_cache_control.write().await = Some(value.into());
+    }
+
+    // How long an idle keep-alive connection waits for its next request
+    pub async fn keep_alive_timeout(&self, timeout: Duration) {
+        *self.keep_alive_timeout.write().await = timeout;
+    }
+
+    // Time allowed for the request line and headers. On a new connection it runs from
+    // the accept, on a kept-alive one from the request line. Slower clients get 408.
+    pub async fn read_header_timeout(&self, timeout: Duration) {
+        *self.read_header_timeout.write().await = timeout;
+    }
+
+    // Time allowed for reading the body once the headers are in
+    pub async fn read_body_timeout(&self, timeout: Duration) {
+        *self.read_body_timeout.write().await = timeout;
+    }
+
+    // Keep requests from clients that shut down their sending side once the request is out
+    // (`shutdown(SHUT_WR)`, `nc -N`) instead of cancelling them. Off by default, such a
+    // clean EOF then counts as a disconnect like a closed tab.
+    pub async fn allow_half_close(&self, enabled: bool) {
+        *self.half_close.write().await = enabled;
+    }
+
+    // Answer 504 when middleware and handler together take longer than `timeout`.
+    // Handlers see it as the request's deadline, `Router::timeout` can tighten it per route.
+    pub async fn request_timeout(&self, timeout: Duration) {
+        *self.request_timeout.write().await = Some(timeout);
+    }
+
+    // Tighter timeout for one registered route, e.g. `route_timeout("GET", "/report", ..)`,
+    // like `Router::timeout` for a group. False when no route has that method and pattern.
+    pub async fn route_timeout(&self, method: &str, path: &str, timeout: Duration) -> bool {
+        let mut found = false;
+        for route in self.routes.write().await.iter_mut() {
+            if route.method.eq_ignore_ascii_case(method) && route.path == path {
+                route.timeout = Some(route.timeout.map_or(timeout, |current| current.min(timeout)));
+                found = true;
+            }
+        }
+        self.dispatch.write().await.stale = true;
+        found
+    }
+
+    // Time in-flight requests get to finish once shutdown is signalled
+    pub async fn shutdown_grace_period(&self, grace: Duration) {
+        *self.shutdown_grace_period.write().await = grace;
+    }
+
+    // Longest request target accepted before answering 414 URI Too Long
+    pub async fn max_uri_length(&self, length: usize) {
+        *self.max_uri_length.write().await = length;
+    }
+
+    // Longest single header line, 8 KB by default
+    pub async fn max_header_line_length(&self, length: usize) {
+        *self.max_header_line_length.write().await = length;
+    }
+
+    // Most header lines in one request, 100 by default
+    pub async fn max_header_count(&self, count: usize) {
+        *self.max_header_count.write().await = count;
+    }
+
+    // Most bytes across all header lines of one request, 64 KB by default
+    pub async fn max_header_size(&self, size: usize) {
+        *self.max_header_size.write().await = size;
+    }
+
+    // How extra and trailing slashes in request paths are matched, `Trim` by default.
+    // Handlers always see the path as the client sent it.
+    pub async fn trailing_slash(&self, policy: TrailingSlash) {
+        *self.trailing_slash.write().await = policy;
+        self.dispatch.write().await.stale = true;
+    }
+
+    // Whether OPTIONS on a path (or `*`) without an OPTIONS route gets 204 with the Allow
+    // list, on by default. Off, such requests get 405 like any other missing method.
+    pub async fn auto_options(&self, enabled: bool) {
+        *self.auto_options.write().await = enabled;
+    }
+
+    // Largest request body accepted, in bytes (before decompression). Larger ones get
+    // 413 Payload Too Large and the connection is closed.
+    pub async fn max_body_size(&self, bytes: usize) {
+        *self.max_body_size.write().await = bytes;
+    }
+
+    // Render framework-generated error responses (404, 504, CORS rejections...) yourself
+    pub async fn set_error_renderer<R: ErrorRenderer + 'static>(&self, renderer: R) {
+        *self.error_renderer.write().await = Arc::new(renderer);
+    }
+
+    // Error pages showing the panic message and backtrace, request and matched route, for
+    // development. Fails in production mode, where pages stay generic and panics only go
+    // to the log.
+    pub async fn dev_errors(&self, enabled: bool) -> Result<(), Error> {
+        if enabled && *self.production.read().await {
+            return Err(Error::DevErrorsInProduction);
+        }
+        *self.dev_errors.write().await = enabled;
+        Ok(())
+    }
+
+    // Mark the server as running in production: `dev_errors` is turned off and refused
+    pub async fn production(&self, enabled: bool) {
+        *self.production.write().await = enabled;
+        if enabled {
+            *self.dev_errors.write().await = false;
+        }
+    }
+
+    async fn current_error_renderer(&self) -> Arc<dyn ErrorRenderer> {
+        if *self.dev_errors.read().await {
+            return Arc::new(DevErrorRenderer);
+        }
+        self.error_renderer.read().await.clone()
+    }
+
+    // Replace the per-request log line, e.g. to feed a structured logger
+    pub async fn on_request<F>(&self, logger: F) where F: Fn(RequestLog) + Send + Sync + 'static {
+        *self.request_logger.write().await = Some(Arc::new(logger));
+    }
+
+    // No output at all: no per-request lines, no startup or shutdown banner
+    pub async fn quiet(&self) {
+        *self.request_logger.write().await = None;
+        *self.quiet.write().await = true;
+    }
+
+    // Share a value (DB pool, config...) with every handler, read back with `req.state::<T>()`.
+    // Registering the same type again replaces it.
+    pub async fn state<T: Send + Sync + 'static>(&self, value: T) {
+        let mut state = self.state.write().await;
+        Arc::make_mut(&mut state).insert(TypeId::of::<T>(), Arc::new(value));
+        // Tenants see it through their table
+        self.dispatch.write().await.stale = true;
+    }
+
+    // Let `purge_cache` reach a `ResponseCache`, wherever its middleware is registered
+    pub async fn register_cache(&self, cache: &Arc<ResponseCache>) {
+        let mut caches = self.caches.write().await;
+        if !caches.iter().any(|registered| Arc::ptr_eq(registered, cache)) {
+            caches.push(Arc::clone(cache));
+        }
     }
 
-    // Runs Global+route middleware and final handler
+    // Drop every cached response for a request path starting with `prefix` from all
+    // registered caches, "/" empties them
+    pub async fn purge_cache(&self, prefix: &str) {
+        for cache in self.caches.read().await.iter() {
+            cache.purge(prefix);
+        }
+    }
+
+    // Ask HTTPS clients for a certificate, read by the next `listen_tls`. The handler sees
+    // its subject in `conn_info().tls`.
+    #[cfg(feature = "tls")]
+    pub async fn tls_client_auth(&self, auth: ClientAuth) {
+        *self.client_auth.write().await = auth;
+    }
+
+    // Transparently decode gzip/deflate request bodies up to `max_size` decoded bytes
+    #[cfg(feature = "compression")]
+    pub async fn decompress_requests(&self, max_size: usize) {
+        *self.decompress_limit.write().await = Some(max_size);
+    }
+
+    // Runs Global+route middleware and final handler. A panic anywhere in the chain is
+    // answered with a 500 (if nothing was sent yet), closes the connection and its message
+    // is returned.
     async fn run_handlers(
         &self,
         req: Arc<RwLock<Request>>,
         res: Arc<RwLock<Response>>,
         middlewares: &[Arc<Middleware>],
         final_handler: Arc<Handler>
-    ) {
+    ) -> Option<String> {
         fn call_middleware(
             req: Arc<RwLock<Request>>,
             res: Arc<RwLock<Response>>,
@@ -86,7 +699,18 @@ impl Glote {
             final_handler: Arc<Handler>
         ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
             if idx == middlewares.len() {
-                Box::pin(final_handler(req, res))
+                Box::pin(async move {
+                    // A middleware already answered without returning early
+                    if res.read().await.is_stopped().await {
+                        return;
+                    }
+                    // Skip the handler when an earlier layer's deadline already passed
+                    if req.read().await.is_expired() {
+                        res.send_error(504, &req).await;
+                        return;
+                    }
+                    final_handler(req, res).await;
+                })
             } else {
                 let mw = middlewares[idx].clone();
                 let new_req = req.clone();
@@ -107,240 +731,1244 @@ impl Glote {
                 });
 
                 Box::pin(async move {
+                    if res.read().await.is_stopped().await {
+                        return;
+                    }
+                    // Like before the handler, a layer that tightened past now ends the chain
+                    if req.read().await.is_expired() {
+                        res.send_error(504, &req).await;
+                        return;
+                    }
                     mw(req, res, next).await;
                 })
             }
         }
 
-        call_middleware(req, res, middlewares, 0, final_handler).await;
+        let chain = call_middleware(req.clone(), res.clone(), middlewares, 0, final_handler);
+        let panic = CatchPanic(Some(chain)).await.err()?;
+        let message = panic.message.clone();
+
+        let mut res = res.write().await;
+        res.close_connection();
+        if !res.is_stopped().await {
+            let mut req = req.write().await;
+            req.extensions.insert(panic);
+            // Lets whoever got the 500 point at the log line
+            let _ = res.set_header("X-Request-Id", req.id()).await;
+            res.send_error(500, &req).await;
+        }
+        Some(message)
+    }
+
+    // Set Global Middleware
+    pub async fn use_middleware<F, Fut>(&self, middleware: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        let wrapped = move |req, res, next| {
+            Box::pin(middleware(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+        };
+
+        let mut middlewares = self.middleware.write().await;
+        middlewares.push(Arc::new(wrapped));
+        self.dispatch.write().await.stale = true;
+    }
+
+    // Handler for requests no route or static file matched, status defaults to 404
+    pub async fn not_found<F, Fut>(&self, handler: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        *self.not_found.write().await = Some(wrap_handler(handler));
+        self.dispatch.write().await.stale = true;
+    }
+
+    // Register every route of a group under its prefix, group middleware running after the
+    // global one and before each route's own
+    pub async fn mount(&self, router: Router) {
+        for route in router.into_routes() {
+            self.add_route(route).await;
+        }
+    }
+
+    // Mount a group as a tenant: requests `selector` picks are routed within it only, with
+    // its state, not_found and error renderer. Tenants are tried in mount order before
+    // anything else, global middleware still runs for them.
+    pub async fn mount_tenant(&self, selector: TenantSelector, router: Router) {
+        let selector = match selector {
+            TenantSelector::Host(host) => TenantSelector::Host(host.to_ascii_lowercase()),
+            prefix => prefix,
+        };
+        let tenant = router.into_tenant(selector);
+        if *self.finalized.read().await
+            && let Err(e) = check_routes(&tenant.routes, *self.trailing_slash.read().await)
+        {
+            eprintln!("Tenant not mounted: {e}");
+            return;
+        }
+        self.tenants.write().await.push(tenant);
+        self.dispatch.write().await.stale = true;
+    }
+
+    /**
+     * Validate every route pattern, reject duplicates and build the table `listen` dispatches
+     * from (global middleware composed in front of each route's own).
+     * `listen` calls this if you didn't. Registering routes or middleware afterwards marks the
+     * table stale and it is rebuilt on the next connection. A route registered afterwards that
+     * is invalid or a duplicate is rejected right away and logged, the rest keep working.
+     */
+    pub async fn finalize(&self) -> Result<(), Error> {
+        let routes = self.routes.read().await;
+        let tenants = self.tenants.read().await;
+        let trailing_slash = *self.trailing_slash.read().await;
+        check_routes(&routes, trailing_slash)?;
+        for tenant in tenants.iter() {
+            check_routes(&tenant.routes, trailing_slash)?;
+        }
+
+        let global_middleware = self.middleware.read().await.clone();
+        let (index, host_routes) = index_routes(&routes, &global_middleware);
+        let state = Arc::clone(&*self.state.read().await);
+        let tenants = tenants
+            .iter()
+            .map(|tenant| {
+                let mut middleware = global_middleware.clone();
+                middleware.extend(tenant.middleware.iter().cloned());
+                let (index, host_routes) = index_routes(&tenant.routes, &middleware);
+                // Its own values win over the server's of the same type
+                let mut tenant_state = (*state).clone();
+                tenant_state.extend(tenant.state.iter().map(|(key, value)| (*key, Arc::clone(value))));
+                let table = DispatchTable {
+                    routes: Arc::new(index),
+                    host_routes: Arc::new(host_routes),
+                    global_middleware: Arc::new(middleware),
+                    not_found: tenant.not_found.clone(),
+                    trailing_slash,
+                    tenants: Arc::new(Vec::new()),
+                    tenant_state: Some(Arc::new(tenant_state)),
+                    error_renderer: tenant.error_renderer.clone(),
+                    stale: false,
+                };
+                (tenant.selector.clone(), table)
+            })
+            .collect();
+
+        *self.dispatch.write().await = DispatchTable {
+            routes: Arc::new(index),
+            host_routes: Arc::new(host_routes),
+            global_middleware: Arc::new(global_middleware),
+            not_found: self.not_found.read().await.clone(),
+            trailing_slash,
+            tenants: Arc::new(tenants),
+            tenant_state: None,
+            error_renderer: None,
+            stale: false,
+        };
+        *self.finalized.write().await = true;
+
+        Ok(())
+    }
+
+    // Finalized table, rebuilt first if registrations happened since
+    async fn dispatch_table(&self) -> DispatchTable {
+        let stale = self.dispatch.read().await.stale;
+        if stale && let Err(e) = self.finalize().await {
+            // Keep serving the last good table
+            eprintln!("Route table not updated: {e}");
+            self.dispatch.write().await.stale = false;
+        }
+        self.dispatch.read().await.clone()
+    }
+
+    async fn add_route(&self, route: Route) {
+        let mut routes = self.routes.write().await;
+        // Once finalized a bad route would fail every rebuild after it, keep it out instead
+        if *self.finalized.read().await {
+            routes.push(route);
+            if let Err(e) = check_routes(&routes, *self.trailing_slash.read().await) {
+                routes.pop();
+                eprintln!("Route not registered: {e}");
+                return;
+            }
+        } else {
+            routes.push(route);
+        }
+        drop(routes);
+        self.dispatch.write().await.stale = true;
+    }
+
+    /**
+     * Start our server at specific port
+     */
+    pub async fn listen(self: Arc<Self>, addr: (&str, u16)) -> tokio::io::Result<()> {
+        self.listen_on(addr).await
+    }
+
+    /**
+     * Start our server on any address, e.g. "127.0.0.1:8080", "[::1]:8080" or port 0 for
+     * an OS-assigned port (see local_addr)
+     */
+    pub async fn listen_on(self: Arc<Self>, addr: impl ToSocketAddrs) -> tokio::io::Result<()> {
+        self.listen_with_shutdown(addr, std::future::pending()).await
+    }
+
+    // Address the server is bound to, once listening
+    pub async fn local_addr(&self) -> Option<SocketAddr> {
+        *self.local_addr.read().await
+    }
+
+    /**
+     * Start our server and run until `shutdown` resolves, then stop accepting and wait
+     * up to the grace period for in-flight requests before returning
+     */
+    pub async fn listen_with_shutdown<S>(
+        self: Arc<Self>,
+        addr: impl ToSocketAddrs,
+        shutdown: S
+    ) -> tokio::io::Result<()>
+        where S: Future<Output = ()>
+    {
+        self.serve(addr, Acceptor::Plain, shutdown).await
+    }
+
+    /**
+     * Start our server speaking HTTPS, with a PEM certificate chain and private key
+     */
+    #[cfg(feature = "tls")]
+    pub async fn listen_tls(
+        self: Arc<Self>,
+        addr: impl ToSocketAddrs,
+        cert_pem_path: impl AsRef<Path>,
+        key_pem_path: impl AsRef<Path>
+    ) -> tokio::io::Result<()> {
+        self.listen_tls_with_shutdown(addr, cert_pem_path, key_pem_path, std::future::pending()).await
+    }
+
+    /**
+     * listen_tls that stops like listen_with_shutdown
+     */
+    #[cfg(feature = "tls")]
+    pub async fn listen_tls_with_shutdown<S>(
+        self: Arc<Self>,
+        addr: impl ToSocketAddrs,
+        cert_pem_path: impl AsRef<Path>,
+        key_pem_path: impl AsRef<Path>,
+        shutdown: S
+    ) -> tokio::io::Result<()>
+        where S: Future<Output = ()>
+    {
+        // Bad certificates fail here, before anything is bound
+        let client_auth = self.client_auth.read().await.clone();
+        let acceptor = Acceptor::tls(cert_pem_path.as_ref(), key_pem_path.as_ref(), &client_auth)?;
+        self.serve(addr, acceptor, shutdown).await
+    }
+
+    async fn serve<S>(
+        self: Arc<Self>,
+        addr: impl ToSocketAddrs,
+        acceptor: Acceptor,
+        shutdown: S
+    ) -> tokio::io::Result<()>
+        where S: Future<Output = ()>
+    {
+        // Finalize implicitly if the user didn't, a bad table never gets as far as the port
+        if self.dispatch.read().await.stale {
+            self.finalize().await.map_err(|e| {
+                tokio::io::Error::new(tokio::io::ErrorKind::InvalidInput, e)
+            })?;
+        }
+
+        let listener = bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        *self.local_addr.write().await = Some(local_addr);
+
+        if !*self.quiet.read().await {
+            println!("\n---------------------\nServer running on {local_addr}");
+        }
+
+        // Flipped to true once shutdown starts, idle connections close on it
+        let (stopping_tx, stopping) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+
+        // Listening incoming request
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, peer_addr)) => {
+                        let this = self.clone();
+                        connections.spawn(
+                            this.serve_connection(stream, peer_addr, acceptor.clone(), stopping.clone())
+                        );
+                    }
+                    Err(e) => eprintln!("Listener accept failed: \n{e}"),
+                },
+                // Reap finished connections so the set doesn't grow forever
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            }
+        }
+
+        drop(listener);
+        *self.local_addr.write().await = None;
+        let _ = stopping_tx.send(true);
+        if !*self.quiet.read().await {
+            println!("Shutting down, waiting for {} connection(s)", connections.len());
+        }
+
+        let grace = *self.shutdown_grace_period.read().await;
+        let drained = tokio::time::timeout(grace, async {
+            while connections.join_next().await.is_some() {}
+        }).await;
+        if drained.is_err() {
+            eprintln!("Grace period over, aborting {} connection(s)", connections.len());
+            connections.shutdown().await;
+        }
+        Ok(())
+    }
+
+    // Serve requests off one connection until either side wants it closed
+    async fn serve_connection(
+        self: Arc<Self>,
+        stream: TcpStream,
+        peer_addr: SocketAddr,
+        acceptor: Acceptor,
+        stopping: watch::Receiver<bool>
+    ) {
+        // Fixed for the lifetime of the connection
+        let mut conn_info = ConnectionInfo {
+            local_addr: stream.local_addr().ok(),
+            peer_addr: Some(peer_addr),
+            ..ConnectionInfo::default()
+        };
+        // A client that never finishes the handshake mustn't hold the task forever
+        let accepted = tokio::time::timeout(
+            HANDSHAKE_TIMEOUT,
+            acceptor.accept(stream, &mut conn_info)
+        ).await;
+        let stream = match accepted {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                eprintln!("Handshake with {peer_addr} failed: {e}");
+                return;
+            }
+            Err(_) => {
+                return;
+            }
+        };
+        self.serve_transport(stream, Arc::new(conn_info), stopping).await;
+    }
+
+    // Serve requests off an accepted (or in-memory) connection until it closes
+    pub(crate) async fn serve_transport(
+        self: &Arc<Self>,
+        stream: Transport,
+        conn_info: Arc<ConnectionInfo>,
+        mut stopping: watch::Receiver<bool>
+    ) {
+        let stream = Arc::new(RwLock::new(stream));
+        // Kept across requests so pipelined bytes aren't lost between them
+        let mut reader = BufReader::new(SharedStream::new(Arc::clone(&stream)));
+        let mut first = true;
+
+        while self.serve_request(&mut reader, &stream, &conn_info, first, &mut stopping).await {
+            first = false;
+        }
+
+        // Handlers may still hold the response, so close explicitly rather than on drop
+        let _ = stream.write().await.shutdown().await;
+    }
+
+    // Answer a request that won't reach routing and close the connection, the body comes
+    // from the ErrorRenderer. Logged like any other request, with `reason` as its error.
+    async fn reject(&self, stream: &Arc<RwLock<Transport>>, code: u16, req: &Request, reason: &str) {
+        let (content_type, body) = self.current_error_renderer().await.render(code, req);
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            code,
+            get_status_text(code),
+            content_type,
+            body.len()
+        );
+
+        {
+            let mut stream = stream.write().await;
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+            let _ = stream.shutdown().await;
+
+            // Drain what the client already sent, closing with unread data would reset the
+            // connection before it reads our answer
+            let mut sink = [0u8; 4096];
+            let _ = tokio::time::timeout(Duration::from_secs(1), async {
+                while let Ok(n) = stream.read(&mut sink).await {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            }).await;
+        }
+
+        // A too long target is only logged by its start
+        let path = if code == 414 {
+            format!("{}...", req.path.chars().take(64).collect::<String>())
+        } else {
+            req.path.clone()
+        };
+        self.log_request(RequestLog {
+            request_id: req.id().to_string(),
+            method: req.method.clone(),
+            path,
+            route: None,
+            status: code,
+            duration: Duration::ZERO,
+            peer_addr: req.conn_info().peer_addr,
+            static_file: None,
+            panic: None,
+            error: Some(reason.to_string()),
+        }).await;
+    }
+
+    // Read and answer one request, returns whether the connection stays open
+    async fn serve_request(
+        self: &Arc<Self>,
+        reader: &mut BufReader<SharedStream>,
+        stream: &Arc<RwLock<Transport>>,
+        conn_info: &Arc<ConnectionInfo>,
+        first: bool,
+        stopping: &mut watch::Receiver<bool>
+    ) -> bool {
+        // Snapshot of our Routes
+        let dispatch = self.dispatch_table().await;
+        let idle_timeout = *self.keep_alive_timeout.read().await;
+        let header_timeout = *self.read_header_timeout.read().await;
+        // A kept-alive connection may idle first, its clock starts with the request line
+        let mut header_deadline = first.then(|| Instant::now() + header_timeout);
+
+        // Request data Header and Body
+        let mut lines: Vec<String> = Vec::new();
+        // Buffer stream store as Chunk of string
+        let mut buffer = String::new();
+
+        // Longest request line we accept: method, target and version
+        let max_uri_length = *self.max_uri_length.read().await;
+        let request_line_cap = (max_uri_length as u64) + 64;
+        let max_header_line_length = *self.max_header_line_length.read().await;
+        let max_header_count = *self.max_header_count.read().await;
+        let max_header_size = *self.max_header_size.read().await;
+        // Room for the line ending on top of the longest header line
+        let header_line_cap = (max_header_line_length as u64) + 2;
+        let mut header_bytes = 0;
+
+        loop {
+            buffer.clear();
+            // Cap the request line so an oversized target is never buffered whole
+            let read = if lines.is_empty() {
+                let mut capped = (&mut *reader).take(request_line_cap);
+                let line = capped.read_line(&mut buffer);
+                // Idle keep-alive connection, give up once it goes quiet
+                let limit = header_deadline.unwrap_or_else(|| Instant::now() + idle_timeout);
+                let read = tokio::select! {
+                    read = tokio::time::timeout_at(limit.into(), line) => read,
+                    // No request in progress, nothing to drain
+                    _ = stopping.wait_for(|stopping| *stopping) => {
+                        return false;
+                    }
+                };
+                if read.is_ok() && header_deadline.is_none() {
+                    header_deadline = Some(Instant::now() + header_timeout);
+                }
+                read
+            } else {
+                let deadline = header_deadline.unwrap_or_else(Instant::now);
+                // Same for header lines, a client can't make us buffer an endless one
+                let mut capped = (&mut *reader).take(header_line_cap);
+                tokio::time::timeout_at(deadline.into(), capped.read_line(&mut buffer)).await
+            };
+            let Ok(read) = read else {
+                reader.get_mut().stop_waiting();
+                // A client that never started a request is just dropped
+                if lines.is_empty() && buffer.is_empty() {
+                    return false;
+                }
+                let req = refused_request(&lines, conn_info);
+                self.reject(stream, 408, &req, "timed out reading request headers").await;
+                return false;
+            };
+            match read {
+                Ok(0) => {
+                    break;
+                }
+                Ok(_) => {
+                    if lines.is_empty() && uri_too_long(&buffer, max_uri_length) {
+                        let req = refused_request(std::slice::from_ref(&buffer), conn_info);
+                        self.reject(stream, 414, &req, "request target too long").await;
+                        return false;
+                    }
+                    let line = buffer.trim_end().to_string();
+                    if line.is_empty() {
+                        // Stray CRLFs before a request line are tolerated
+                        if lines.is_empty() {
+                            continue;
+                        }
+                        break;
+                    }
+                    if !lines.is_empty() {
+                        header_bytes += buffer.len();
+                        let cut_off = !buffer.ends_with('\n') && (buffer.len() as u64) >= header_line_cap;
+                        if cut_off || lines.len() > max_header_count || header_bytes > max_header_size {
+                            let req = refused_request(&lines, conn_info);
+                            self.reject(stream, 431, &req, "request headers too large").await;
+                            return false;
+                        }
+                    }
+                    lines.push(line);
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+                    continue;
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {
+                    continue;
+                }
+                // Not UTF-8, e.g. a TLS handshake on the plaintext port
+                Err(ref e) if e.kind() == ErrorKind::InvalidData => {
+                    let req = refused_request(&lines, conn_info);
+                    self.reject(stream, 400, &req, "binary data instead of a request").await;
+                    return false;
+                }
+                Err(e) => {
+                    eprintln!("Failed to read line: {e}");
+                    return false;
+                }
+            }
+        }
+        // Client closed the connection between requests
+        if lines.is_empty() {
+            return false;
+        }
+        // Current time for time takes to fullfill the request
+        let now = Instant::now();
+        let cutoff = self.request_timeout.read().await.map(|timeout| now + timeout);
+        // Length of request content, ambiguous framing is refused outright
+        let framing = match body_framing(lines.get(1..).unwrap_or_default()) {
+            Ok(framing) => framing,
+            Err(e) => {
+                let req = refused_request(&lines, conn_info);
+                self.reject(stream, e.status(), &req, &e.to_string()).await;
+                return false;
+            }
+        };
+        // Client holding the body back until we agree to take it
+        let expects_continue = match expect_header(&lines) {
+            None => false,
+            Some(expect) if expect.eq_ignore_ascii_case("100-continue") => {
+                // HTTP/1.0 clients don't know interim responses
+                lines[0].split_whitespace().nth(2) == Some("HTTP/1.1") && framing != BodyFraming::Empty
+            }
+            Some(expect) => {
+                let req = refused_request(&lines, conn_info);
+                self.reject(stream, 417, &req, &format!("unsupported Expect: {expect}")).await;
+                return false;
+            }
+        };
+        // Raw body bytes, kept as they came off the wire
+        let mut body = None;
+        let max_body_size = *self.max_body_size.read().await;
+        let body_deadline = tokio::time::Instant::now() + *self.read_body_timeout.read().await;
+        let read = match framing {
+            BodyFraming::Empty => None,
+            BodyFraming::Length(len) if len > max_body_size => {
+                // Refused before allocating anything for it
+                let req = refused_request(&lines, conn_info);
+                let reason = format!("body of {len} bytes, limit is {max_body_size}");
+                self.reject(stream, 413, &req, &reason).await;
+                return false;
+            }
+            BodyFraming::Length(len) => {
+                if expects_continue {
+                    write_continue(stream).await;
+                }
+                // Make buffer to store full content
+                let mut buf = vec![0u8; len];
+                // Store data into buf
+                match tokio::time::timeout_at(body_deadline, reader.read_exact(&mut buf)).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => {
+                        eprintln!("Failed to read body: {e}");
+                        return false;
+                    }
+                    Err(_) => {
+                        reader.get_mut().stop_waiting();
+                        let req = refused_request(&lines, conn_info);
+                        self.reject(stream, 408, &req, "timed out reading request body").await;
+                        return false;
+                    }
+                }
+                Some(buf)
+            }
+            BodyFraming::Chunked => {
+                if expects_continue {
+                    write_continue(stream).await;
+                }
+                let read = tokio::time::timeout_at(body_deadline, read_chunked(reader, max_body_size));
+                match read.await {
+                    Ok(Ok(buf)) => Some(buf),
+                    Ok(Err(e)) => {
+                        let req = refused_request(&lines, conn_info);
+                        self.reject(stream, e.status(), &req, &e.to_string()).await;
+                        return false;
+                    }
+                    Err(_) => {
+                        reader.get_mut().stop_waiting();
+                        let req = refused_request(&lines, conn_info);
+                        self.reject(stream, 408, &req, "timed out reading request body").await;
+                        return false;
+                    }
+                }
+            }
+        };
+        // Case have body
+        #[allow(unused_mut)]
+        if let Some(mut buf) = read {
+            // Undo Content-Encoding before handlers see the body
+            #[cfg(feature = "compression")]
+            if let Some(limit) = *self.decompress_limit.read().await {
+                match decompress_body(&mut lines, &buf, limit) {
+                    Ok(Some(decoded)) => {
+                        buf = decoded;
+                    }
+                    Ok(None) => {}
+                    Err(code) => {
+                        let req = refused_request(&lines, conn_info);
+                        self.reject(stream, code, &req, "Content-Encoding couldn't be decoded").await;
+                        return false;
+                    }
+                }
+            }
+
+            if !buf.is_empty() {
+                body = Some(buf);
+            }
+        }
+
+        // Parse metadata into Request struct, garbage gets a 400 instead of a request
+        let mut req = match Request::new(&lines) {
+            Ok(req) => req,
+            Err(e) => {
+                let req = refused_request(&lines, conn_info);
+                self.reject(stream, 400, &req, &e.to_string()).await;
+                return false;
+            }
+        };
+        req.body_bytes = body;
+        req.set_conn_info(Arc::clone(conn_info));
+        // Routes constrained to the request's host are tried before the rest
+        let host = req.headers.get("host").map(|host| normalize_host(host));
+        let host = host.as_deref();
+        // Matching runs on a normalized path, `req.path` stays as sent
+        let collapsed = collapse_slashes(&req.path);
+        let dispatch = dispatch.for_request(host, &collapsed);
+        match &dispatch.tenant_state {
+            Some(state) => req.set_state(Arc::clone(state)),
+            None => req.set_state(Arc::clone(&*self.state.read().await)),
+        }
+        // Parse stream into Response struct
+        let mut res = Response::from_shared(Arc::clone(stream));
+        let error_renderer = match &dispatch.error_renderer {
+            Some(renderer) if !*self.dev_errors.read().await => Arc::clone(renderer),
+            _ => self.current_error_renderer().await,
+        };
+        res.set_error_renderer(error_renderer);
+        res.set_error_request(Arc::new(req.without_body()));
+        res.set_cancellation(req.cancellation());
+        if *self.half_close.read().await {
+            res.set_half_close();
+        }
+        // HEAD gets the headers a GET would, whoever answers it
+        if req.method == "HEAD" {
+            res.set_head_only();
+        }
+        // Requests in flight during shutdown are answered, then the connection closes
+        res.set_keep_alive(
+            wants_keep_alive(&lines[0], req.headers.get("connection")),
+            stopping.clone()
+        );
+        let res_handle = Arc::new(RwLock::new(res));
+        let mut res_opt = Some(Arc::clone(&res_handle));
+        // Check is Route have or not
+        let mut matched = false;
+        let mut matched_route = None;
+        // Methods of routes whose path matched but method didn't, for 405 and OPTIONS
+        let mut allowed: Vec<&str> = Vec::new();
+        // Message of a panic in the chain that answered
+        let mut panicked = None;
+        let (segments, trailing_slash) = match dispatch.trailing_slash {
+            TrailingSlash::Strict => {
+                let (segments, trailing_slash) = split_path_strict(&req.path);
+                (segments, Some(trailing_slash))
+            }
+            TrailingSlash::Trim | TrailingSlash::Redirect => (split_path(&collapsed), None),
+        };
+        // First registered route for this method (or any method) matching the path. HEAD
+        // falls back to the GET route when no route takes HEAD itself.
+        let mut found = dispatch.find(host, &req.method, &segments, trailing_slash);
+        if found.is_none() && req.method == "HEAD" {
+            found = dispatch.find(host, "GET", &segments, trailing_slash);
+        }
+        let canonical = canonical_path(&collapsed);
+        if dispatch.trailing_slash == TrailingSlash::Redirect && found.is_some() && canonical != req.path {
+            let location = match lines[0].split_whitespace().nth(1).and_then(|target| target.split_once('?')) {
+                Some((_, query)) => format!("{canonical}?{query}"),
+                None => canonical.to_string(),
+            };
+            // 308 keeps the method and body, browsers turn a 301 POST into a GET
+            let status = if matches!(req.method.as_str(), "GET" | "HEAD") { 301 } else { 308 };
+            if let Some(res) = res_opt.take() {
+                res.read().await.redirect_with_status(status, &location).await;
+            }
+        } else if let Some((route, captures)) = found {
+            // CLone req inside have params
+            let mut req_with_params = req.clone();
+            req_with_params.path_params = decode_path_params(captures);
+            req_with_params.extensions.insert(MatchedRoute {
+                pattern: route.path.clone(),
+                deprecation: route.deprecation.clone(),
+            });
+            // Server and route timeouts both cap the chain, the tighter one wins
+            let cutoff = cutoff.into_iter().chain(route.timeout.map(|timeout| now + timeout)).min();
+            if let Some(cutoff) = cutoff {
+                req_with_params.tighten_deadline(cutoff);
+            }
+            // Nor can it outlive the time the connection had to deliver the request
+            req_with_params.tighten_deadline(body_deadline.into_std());
+            let req_with_params = Arc::new(RwLock::new(req_with_params));
+
+            // Combined Global Middleware and Routes Middleware
+            let combined_middleware: Vec<_> = route.middleware.clone();
+
+            if let Some(res_actual) = res_opt.take() {
+                // Sent with whatever answers, errors included
+                if let Some(deprecation) = &route.deprecation {
+                    let res = res_actual.read().await;
+                    for (name, value) in deprecation.headers() {
+                        let _ = res.append_header(name, &value).await;
+                    }
+                }
+                // Move ownership
+                let req_for_handler = Arc::clone(&req_with_params);
+                let res_for_handler = Arc::clone(&res_actual);
+                // Flag the request as cancelled if the client goes away mid-handler
+                let cancellation = req_for_handler.read().await.cancellation();
+                let watcher = watch_disconnect(
+                    Arc::clone(&res_for_handler),
+                    cancellation.clone()
+                );
+                // Call run_handler
+                let chain = self.run_handlers(
+                    Arc::clone(&req_for_handler),
+                    Arc::clone(&res_for_handler),
+                    &combined_middleware,
+                    route.handler.clone()
+                );
+                let chain = async {
+                    panicked = if route.cancel_on_disconnect {
+                        // Drop the handler future as soon as the client is gone
+                        tokio::select! {
+                            panicked = chain => panicked,
+                            _ = cancellation.wait() => None,
+                        }
+                    } else {
+                        chain.await
+                    };
+                };
+                let finished = match cutoff {
+                    Some(cutoff) => tokio::time::timeout_at(cutoff.into(), chain).await.is_ok(),
+                    None => {
+                        chain.await;
+                        true
+                    }
+                };
+                watcher.abort();
+                // The dropped chain released the response, a no-op if it was sent
+                if !finished {
+                    res_actual.read().await.send_error(504, &req).await;
+                }
+
+                matched = true;
+                matched_route = Some(route.path.clone());
+            }
+        } else {
+            allowed = dispatch.allowed_methods(host, &req.method, &segments, trailing_slash);
+        }
+        // Static file served in place of a route
+        let mut static_served = None;
+
+        // Case route not matched
+        if !matched && let Some(res) = res_opt {
+            let auto_options = *self.auto_options.read().await;
+            if req.method == "OPTIONS" && auto_options && req.path == "*" {
+                // Server-wide: everything some route on this host takes
+                let allow = allow_list(dispatch.methods(host), true);
+                panicked = self.answer_options(&req, res, dispatch, allow).await;
+            } else if req.method == "OPTIONS" && auto_options && !allowed.is_empty() {
+                // OPTIONS without its own route lists what the path takes
+                let allow = allow_list(allowed, true);
+                panicked = self.answer_options(&req, res, dispatch, allow).await;
+            } else if !allowed.is_empty() {
+                // Path exists under other methods
+                let allow = allow_list(allowed, auto_options);
+                res
+                    .read().await
+                    .send_error_with_headers(405, &req, &[("Allow", &allow)]).await;
+            } else {
+                static_served = self.serve_static_or_not_found(&req, res, dispatch, &mut panicked).await;
+            }
+        }
+
+        let res = res_handle.read().await;
+        // A stream the handler didn't end is ended here so the body is complete. After a
+        // panic it's left cut short, the connection closes.
+        if panicked.is_none() && res.is_stopped().await && !res.is_finished().await {
+            let _ = res.end().await;
+        }
+        let status = res.sent_status().await.unwrap_or(res.status_code());
+        self.log_request(RequestLog {
+            request_id: req.id().to_string(),
+            method: req.method.clone(),
+            path: req.path.clone(),
+            route: matched_route,
+            status,
+            duration: now.elapsed(),
+            peer_addr: conn_info.peer_addr,
+            static_file: static_served,
+            panic: panicked,
+            error: None,
+        }).await;
+
+        res.keeps_alive().await
+    }
+
+    // 204 with the path's methods in Allow. Runs behind global middleware so CORS can
+    // answer a preflight or add its headers.
+    async fn answer_options(
+        &self,
+        req: &Request,
+        res: Arc<RwLock<Response>>,
+        dispatch: &DispatchTable,
+        allow: String
+    ) -> Option<String> {
+        let handler: Arc<Handler> = Arc::new(move |_req, res| {
+            let allow = allow.clone();
+            Box::pin(async move {
+                let res = res.read().await;
+                let _ = res.set_header("Allow", &allow).await;
+                res.send_status(204, "").await;
+            })
+        });
+        self.run_handlers(
+            Arc::new(RwLock::new(req.clone())),
+            res,
+            &dispatch.global_middleware,
+            handler
+        ).await
+    }
+
+    // Unmatched request: static file if one resolves, else the not_found handler or a 404.
+    // Returns the file that was served, a not_found panic lands in `panicked`.
+    async fn serve_static_or_not_found(
+        &self,
+        req: &Request,
+        res: Arc<RwLock<Response>>,
+        dispatch: &DispatchTable,
+        panicked: &mut Option<String>
+    ) -> Option<PathBuf> {
+        // Tenants only answer from their own routes
+        let static_root = match dispatch.tenant_state {
+            Some(_) => None,
+            None => self.static_root(req).await,
+        };
+        if let Some(static_dir) = static_root {
+            match resolve_static(&static_dir, &req.path).await {
+                Ok(Some(file_path)) => {
+                    if let Ok(file) = File::open(&file_path).await {
+                        let res = res.read().await;
+                        res.status(200).await;
+                        let cache_control = self.static_cache_control.read().await.clone();
+                        let extra = cache_control
+                            .as_deref()
+                            .map(|value| vec![("Cache-Control", value)])
+                            .unwrap_or_default();
+                        // Conditional requests only make sense for reads
+                        let conditional = matches!(req.method.as_str(), "GET" | "HEAD")
+                            .then_some(&req.headers);
+                        if res.send_open_file(file, &file_path, &extra, conditional).await {
+                            return Some(file_path);
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(code) => {
+                    res.read().await.send_error(code, req).await;
+                    return None;
+                }
+            }
+        }
+
+        if let Some(not_found) = dispatch.not_found.clone() {
+            // Custom 404 still goes through global middleware (CORS etc.)
+            res.read().await.status(404).await;
+            *panicked = self.run_handlers(
+                Arc::new(RwLock::new(req.clone())),
+                res,
+                &dispatch.global_middleware,
+                not_found
+            ).await;
+        } else {
+            res.read().await.send_error(404, req).await;
+        }
+        None
+    }
+
+    async fn log_request(&self, log: RequestLog) {
+        if let Some(logger) = self.request_logger.read().await.as_ref() {
+            logger(log);
+        }
+    }
+
+    // ========== Any Method ============
+
+    // Route for an arbitrary method string, e.g. from a config table
+    pub async fn route<F, Fut>(&self, method: &str, path: &str, handler: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        let empty_middleware: Vec<Arc<Middleware>> = vec![];
+
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            let fut = handler(req, res);
+            Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
+
+        self.route_with_middleware_run(
+            &method.to_ascii_uppercase(),
+            path,
+            empty_middleware,
+            wrapped_handler
+        ).await;
+    }
+
+    // Route for an arbitrary method string with middleware
+    pub async fn route_with_middleware<Mfut, F, Ffut>(
+        &self,
+        method: &str,
+        path: &str,
+        middleware: Vec<MiddlewareFn<Mfut>>,
+        handler: F
+    )
+        where
+            Mfut: Future<Output = ()> + Send + 'static,
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+            Ffut: Future<Output = ()> + Send + 'static
+    {
+        let wrapped_middleware: Vec<Arc<Middleware>> = middleware
+            .into_iter()
+            .map(|mw_fn| {
+                let wrapped = move |
+                    req: Arc<RwLock<Request>>,
+                    res: Arc<RwLock<Response>>,
+                    next: Next
+                | {
+                    Box::pin(mw_fn(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+                };
+                Arc::new(wrapped) as Arc<Middleware>
+            })
+            .collect();
+
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
+
+        self.route_with_middleware_run(
+            &method.to_ascii_uppercase(),
+            path,
+            wrapped_middleware,
+            wrapped_handler
+        ).await;
+    }
+
+    // Route matching every method
+    pub async fn all<F, Fut>(&self, path: &str, handler: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        self.route(ANY_METHOD, path, handler).await;
+    }
+
+    // Route matching every method with middleware
+    pub async fn all_with_middleware<Mfut, F, Ffut>(
+        &self,
+        path: &str,
+        middleware: Vec<MiddlewareFn<Mfut>>,
+        handler: F
+    )
+        where
+            Mfut: Future<Output = ()> + Send + 'static,
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+            Ffut: Future<Output = ()> + Send + 'static
+    {
+        self.route_with_middleware(ANY_METHOD, path, middleware, handler).await;
+    }
+
+    // Route registration helper for any method string
+    async fn route_with_middleware_run(
+        &self,
+        method: &str,
+        path: &str,
+        middleware: Vec<Arc<Middleware>>,
+        handler: Arc<Handler>
+    ) {
+        let route = Route {
+            method: method.to_string(),
+            path: path.to_string(),
+            middleware,
+            handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+        };
+
+        self.add_route(route).await;
+    }
+
+    // ========== Get Method ============
+
+    // Get routes without middleware
+    pub async fn get<F, Fut>(&self, path: &str, handler: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        // Empty middleware vec
+        let empty_middleware: Vec<Arc<Middleware>> = vec![];
+
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            let fut = handler(req, res);
+            Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
+
+        self.get_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+    }
+
+    // Get routes with middleware
+    pub async fn get_with_middleware<Mfut, F, Ffut>(
+        &self,
+        path: &str,
+        middleware: Vec<MiddlewareFn<Mfut>>,
+        handler: F
+    )
+        where
+            Mfut: Future<Output = ()> + Send + 'static,
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+            Ffut: Future<Output = ()> + Send + 'static
+    {
+        let wrapped_middleware: Vec<Arc<Middleware>> = middleware
+            .into_iter()
+            .map(|mw_fn| {
+                let wrapped = move |
+                    req: Arc<RwLock<Request>>,
+                    res: Arc<RwLock<Response>>,
+                    next: Next
+                | {
+                    Box::pin(mw_fn(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+                };
+                Arc::new(wrapped) as Arc<Middleware>
+            })
+            .collect();
+
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
+
+        self.get_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
+    }
+
+    // Responsible for runing both Get without middleware or with middleware
+    async fn get_with_middleware_run(
+        &self,
+        path: &str,
+        middleware: Vec<Arc<Middleware>>,
+        handler: Arc<Handler>
+    ) {
+        let route = Route {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            middleware,
+            handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+        };
+
+        self.add_route(route).await;
+    }
+
+    // // ========== Post Method ============
+    // POST routes without middleware
+    pub async fn post<F, Fut>(&self, path: &str, handler: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        let empty_middleware: Vec<Arc<Middleware>> = vec![];
+
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            let fut = handler(req, res);
+            Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
+
+        self.post_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+    }
+
+    // POST with middleware
+    pub async fn post_with_middleware<Mfut, F, Ffut>(
+        &self,
+        path: &str,
+        middleware: Vec<MiddlewareFn<Mfut>>,
+        handler: F
+    )
+        where
+            Mfut: Future<Output = ()> + Send + 'static,
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+            Ffut: Future<Output = ()> + Send + 'static
+    {
+        let wrapped_middleware: Vec<Arc<Middleware>> = middleware
+            .into_iter()
+            .map(|mw_fn| {
+                let wrapped = move |
+                    req: Arc<RwLock<Request>>,
+                    res: Arc<RwLock<Response>>,
+                    next: Next
+                | {
+                    Box::pin(mw_fn(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+                };
+                Arc::new(wrapped) as Arc<Middleware>
+            })
+            .collect();
+
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
+
+        self.post_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
     }
 
-    // Set Global Middleware
-    pub async fn use_middleware<F, Fut>(&self, middleware: F)
-        where
-            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Fut + Send + Sync + 'static,
-            Fut: Future<Output = ()> + Send + 'static
-    {
-        let wrapped = move |req, res, next| {
-            Box::pin(middleware(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+    // POST route registration helper
+    async fn post_with_middleware_run(
+        &self,
+        path: &str,
+        middleware: Vec<Arc<Middleware>>,
+        handler: Arc<Handler>
+    ) {
+        let route = Route {
+            method: "POST".to_string(),
+            path: path.to_string(),
+            middleware,
+            handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
         };
 
-        let mut middlewares = self.middleware.write().await;
-        middlewares.push(Arc::new(wrapped));
+        self.add_route(route).await;
     }
 
-    /**
-     * Start our server at specific port
-     */
-    pub async fn listen(self: Arc<Self>, addr: (&str, u16)) -> tokio::io::Result<()> {
-        let listener = TcpListener::bind((addr.0, addr.1)).await?;
-
-        println!("\n---------------------\nServer running on port {}", addr.1);
+    // // ========== Put Method ============
+    // PUT routes without middleware
+    pub async fn put<F, Fut>(&self, path: &str, handler: F)
+        where
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static
+    {
+        let empty_middleware: Vec<Arc<Middleware>> = vec![];
 
-        let global_middleware = self.middleware.read().await.clone();
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            let fut = handler(req, res);
+            Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
 
-        for route in self.routes.write().await.iter_mut() {
-            let mut new_middleware = global_middleware.clone();
+        self.put_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+    }
 
-            let route_specific = std::mem::take(&mut route.middleware);
+    // PUT with middleware
+    pub async fn put_with_middleware<Mfut, F, Ffut>(
+        &self,
+        path: &str,
+        middleware: Vec<MiddlewareFn<Mfut>>,
+        handler: F
+    )
+        where
+            Mfut: Future<Output = ()> + Send + 'static,
+            F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Ffut + Send + Sync + 'static,
+            Ffut: Future<Output = ()> + Send + 'static
+    {
+        let wrapped_middleware: Vec<Arc<Middleware>> = middleware
+            .into_iter()
+            .map(|mw_fn| {
+                let wrapped = move |
+                    req: Arc<RwLock<Request>>,
+                    res: Arc<RwLock<Response>>,
+                    next: Next
+                | {
+                    Box::pin(mw_fn(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+                };
+                Arc::new(wrapped) as Arc<Middleware>
+            })
+            .collect();
 
-            new_middleware.extend(route_specific);
-            route.middleware = new_middleware;
-        }
+        let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
+            Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
+        });
 
-        drop(global_middleware);
+        self.put_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
+    }
 
-        // Listening incoming request
-        loop {
-            match listener.accept().await {
-                Ok((s, _add)) => {
-                    // Filter out raw stream from inconging request
-                    let stream = s;
-                    // Clone of our Routes
-                    let routers_clone = {
-                        let guard = self.routes.read().await;
-                        guard.clone()
-                    };
-                    // static file not used
-                    let static_file = self.static_path.clone();
-
-                    let this = self.clone();
-                    // Assign a Worker though warkerpool
-                    tokio::spawn(async move {
-                        // Current time for time takes to fullfill the request
-                        let now = Instant::now();
-                        // Shadowing make mutable
-                        let mut stream = stream;
-                        // TcpStream to buffer stream
-                        let mut reader = BufReader::new(&mut stream);
-                        // Request data Header and Body
-                        let mut lines = Vec::new();
-                        // Buffer stream store as Chunk of string
-                        let mut buffer = String::new();
-
-                        loop {
-                            buffer.clear();
-                            match reader.read_line(&mut buffer).await {
-                                Ok(0) => {
-                                    break;
-                                }
-                                Ok(_) => {
-                                    let line = buffer.trim_end().to_string();
-                                    if line.is_empty() {
-                                        break;
-                                    }
-                                    lines.push(line);
-                                }
-                                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
-                                    continue;
-                                }
-                                Err(ref e) if e.kind() == ErrorKind::Interrupted => {
-                                    continue;
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to read line: {e}");
-                                    return;
-                                }
-                            }
-                        }
-                        // Length of request content
-                        let content_length = lines
-                            .iter()
-                            .find(|line| line.to_ascii_lowercase().starts_with("content-length:"))
-                            .and_then(|line| line.split(": ").nth(1))
-                            .and_then(|len| len.parse::<usize>().ok());
-                        // Store body as Vec line
-                        let mut body_lines = Vec::new();
-                        // Case have length
-                        if let Some(len) = content_length {
-                            // Make buffer to store full content
-                            let mut buf = vec![0u8; len];
-                            // Store data into buf
-                            match reader.read_exact(&mut buf).await {
-                                Ok(_) => {
-                                    let body = String::from_utf8_lossy(&buf).to_string();
-                                    body_lines.extend(body.lines().map(|s| s.to_string()));
-                                }
-                                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
-                                    return;
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to read body: {e}");
-                                    return;
-                                }
-                            }
-
-                            // Parse into UTF_8
-                            let body = String::from_utf8_lossy(&buf).to_string();
-                            // Concat it in body_lines
-                            body_lines.extend(body.lines().map(|s| s.to_string()));
-                        }
+    // PUT route registration helper
+    async fn put_with_middleware_run(
+        &self,
+        path: &str,
+        middleware: Vec<Arc<Middleware>>,
+        handler: Arc<Handler>
+    ) {
+        let route = Route {
+            method: "PUT".to_string(),
+            path: path.to_string(),
+            middleware,
+            handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+        };
 
-                        lines.push(String::new()); // Empty string before body
-                        lines.extend(body_lines);
-
-                        // Parse metadata into Request struct
-                        let req = Request::new(&lines);
-                        // Parse stream into Response struct
-                        let mut res_opt = Some(Arc::new(RwLock::new(Response::new(stream))));
-                        // Check is Route have or not
-                        let mut matched = false;
-                        // Iterate in Routes
-                        for route in routers_clone.into_iter() {
-                            // Case method same
-                            if route.method == req.method {
-                                // Parse params
-                                if let Some(params) = parse_path_params(&route.path, &req.path) {
-                                    // CLone req inside have params
-                                    let mut req_with_params = req.clone();
-                                    req_with_params.path_params = params;
-                                    let req_with_params = Arc::new(RwLock::new(req_with_params));
-
-                                    // Combined Global Middleware and Routes Middleware
-                                    let combined_middleware: Vec<_> = route.middleware.clone();
-
-                                    if let Some(res_actual) = res_opt.take() {
-                                        // Move ownership
-                                        let req_for_handler = Arc::clone(&req_with_params);
-                                        let res_for_handler = Arc::clone(&res_actual);
-                                        // Call run_handler
-                                        this.run_handlers(
-                                            Arc::clone(&req_for_handler),
-                                            Arc::clone(&res_for_handler),
-                                            &combined_middleware,
-                                            route.handler.clone()
-                                        ).await;
-
-                                        matched = true;
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        // Duration to fullfill the request
-                        let duration = now.elapsed();
-
-                        // Case route not matched
-                        if !matched {
-                            if let Some(res) = res_opt {
-                                if let Some(static_dir) = &static_file.read().await.as_ref() {
-                                    let mut file_path = PathBuf::from(static_dir);
-                                    let mut req_path = req.path.trim_start_matches('/').to_string();
-
-                                    if req_path.is_empty() {
-                                        req_path = "index.html".into();
-                                    }
-
-                                    file_path.push(req_path);
-
-                                    if let Ok(mut file) = File::open(&file_path).await {
-                                        let mut contents = Vec::new();
-                                        if file.read_to_end(&mut contents).await.is_ok() {
-                                            let mut res = res.write().await;
-                                            res.status(200).await;
-                                            res.send_bytes(
-                                                &contents,
-                                                mime_guess
-                                                    ::from_path(&file_path)
-                                                    .first_or_text_plain()
-                                                    .as_ref()
-                                            ).await;
-                                            println!(
-                                                "\x1b[34mSTATIC {}: {:?}\x1b[0m",
-                                                file_path.display(),
-                                                duration
-                                            );
-                                            return;
-                                        }
-                                    }
-                                }
-
-                                let mut res = res.write().await;
-                                res.status(404).await;
-                                res.send("404 Not Found").await;
-                            }
-                            println!("\x1b[31m{} {}: {:?}\x1b[0m ", req.method, req.path, duration);
-                        } else {
-                            println!("\x1b[32m{} {}: {:?}\x1b[0m ", req.method, req.path, duration);
-                        }
-                    });
-                }
-                Err(e) => eprintln!("Listener accept failed: \n{e}"),
-            }
-        }
+        self.add_route(route).await;
     }
 
-    // ========== Get Method ============
+    // // ========== Delete Method ============
 
-    // Get routes without middleware
-    pub async fn get<F, Fut>(&self, path: &str, handler: F)
+    // DELETE routes without middleware
+    pub async fn delete<F, Fut>(&self, path: &str, handler: F)
         where
             F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
             Fut: Future<Output = ()> + Send + 'static
     {
-        // Empty middleware vec
         let empty_middleware: Vec<Arc<Middleware>> = vec![];
 
         let wrapped_handler: Arc<Handler> = Arc::new(move |req, res| {
@@ -348,14 +1976,14 @@ impl Glote {
             Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.get_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+        self.delete_with_middleware_run(path, empty_middleware, wrapped_handler).await;
     }
 
-    // Get routes with middleware
-    pub async fn get_with_middleware<Mfut, F, Ffut>(
+    // DELETE with middleware
+    pub async fn delete_with_middleware<Mfut, F, Ffut>(
         &self,
         path: &str,
-        middleware: Vec<fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Mfut>,
+        middleware: Vec<MiddlewareFn<Mfut>>,
         handler: F
     )
         where
@@ -381,29 +2009,33 @@ impl Glote {
             Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.get_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
+        self.delete_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
     }
 
-    // Responsible for runing both Get without middleware or with middleware
-    async fn get_with_middleware_run(
+    // DELETE route registration helper
+    async fn delete_with_middleware_run(
         &self,
         path: &str,
         middleware: Vec<Arc<Middleware>>,
         handler: Arc<Handler>
     ) {
         let route = Route {
-            method: "GET".to_string(),
+            method: "DELETE".to_string(),
             path: path.to_string(),
             middleware,
             handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
         };
 
-        self.routes.write().await.push(route);
+        self.add_route(route).await;
     }
 
-    // // ========== Post Method ============
-    // POST routes without middleware
-    pub async fn post<F, Fut>(&self, path: &str, handler: F)
+    // // ========== Patch Method ============
+    // PATCH routes without middleware
+    pub async fn patch<F, Fut>(&self, path: &str, handler: F)
         where
             F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
             Fut: Future<Output = ()> + Send + 'static
@@ -415,14 +2047,14 @@ impl Glote {
             Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.post_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+        self.patch_with_middleware_run(path, empty_middleware, wrapped_handler).await;
     }
 
-    // POST with middleware
-    pub async fn post_with_middleware<Mfut, F, Ffut>(
+    // PATCH with middleware
+    pub async fn patch_with_middleware<Mfut, F, Ffut>(
         &self,
         path: &str,
-        middleware: Vec<fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Mfut>,
+        middleware: Vec<MiddlewareFn<Mfut>>,
         handler: F
     )
         where
@@ -448,29 +2080,33 @@ impl Glote {
             Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.post_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
+        self.patch_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
     }
 
-    // POST route registration helper
-    async fn post_with_middleware_run(
+    // PATCH route registration helper
+    async fn patch_with_middleware_run(
         &self,
         path: &str,
         middleware: Vec<Arc<Middleware>>,
         handler: Arc<Handler>
     ) {
         let route = Route {
-            method: "POST".to_string(),
+            method: "PATCH".to_string(),
             path: path.to_string(),
             middleware,
             handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
         };
 
-        self.routes.write().await.push(route);
+        self.add_route(route).await;
     }
 
-    // // ========== Put Method ============
-    // PUT routes without middleware
-    pub async fn put<F, Fut>(&self, path: &str, handler: F)
+    // // ========== Head Method ============
+    // HEAD routes without middleware
+    pub async fn head<F, Fut>(&self, path: &str, handler: F)
         where
             F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
             Fut: Future<Output = ()> + Send + 'static
@@ -482,14 +2118,14 @@ impl Glote {
             Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.put_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+        self.head_with_middleware_run(path, empty_middleware, wrapped_handler).await;
     }
 
-    // PUT with middleware
-    pub async fn put_with_middleware<Mfut, F, Ffut>(
+    // HEAD with middleware
+    pub async fn head_with_middleware<Mfut, F, Ffut>(
         &self,
         path: &str,
-        middleware: Vec<fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Mfut>,
+        middleware: Vec<MiddlewareFn<Mfut>>,
         handler: F
     )
         where
@@ -515,30 +2151,33 @@ impl Glote {
             Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.put_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
+        self.head_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
     }
 
-    // PUT route registration helper
-    async fn put_with_middleware_run(
+    // HEAD route registration helper
+    async fn head_with_middleware_run(
         &self,
         path: &str,
         middleware: Vec<Arc<Middleware>>,
         handler: Arc<Handler>
     ) {
         let route = Route {
-            method: "PUT".to_string(),
+            method: "HEAD".to_string(),
             path: path.to_string(),
             middleware,
             handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
         };
 
-        self.routes.write().await.push(route);
+        self.add_route(route).await;
     }
 
-    // // ========== Delete Method ============
-
-    // DELETE routes without middleware
-    pub async fn delete<F, Fut>(&self, path: &str, handler: F)
+    // // ========== Options Method ============
+    // OPTIONS routes without middleware
+    pub async fn options<F, Fut>(&self, path: &str, handler: F)
         where
             F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
             Fut: Future<Output = ()> + Send + 'static
@@ -550,14 +2189,14 @@ impl Glote {
             Box::pin(fut) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.delete_with_middleware_run(path, empty_middleware, wrapped_handler).await;
+        self.options_with_middleware_run(path, empty_middleware, wrapped_handler).await;
     }
 
-    // DELETE with middleware
-    pub async fn delete_with_middleware<Mfut, F, Ffut>(
+    // OPTIONS with middleware
+    pub async fn options_with_middleware<Mfut, F, Ffut>(
         &self,
         path: &str,
-        middleware: Vec<fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Mfut>,
+        middleware: Vec<MiddlewareFn<Mfut>>,
         handler: F
     )
         where
@@ -583,23 +2222,369 @@ impl Glote {
             Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>
         });
 
-        self.delete_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
+        self.options_with_middleware_run(path, wrapped_middleware, wrapped_handler).await;
     }
 
-    // DELETE route registration helper
-    async fn delete_with_middleware_run(
+    // OPTIONS route registration helper
+    async fn options_with_middleware_run(
         &self,
         path: &str,
         middleware: Vec<Arc<Middleware>>,
         handler: Arc<Handler>
     ) {
         let route = Route {
-            method: "DELETE".to_string(),
+            method: "OPTIONS".to_string(),
             path: path.to_string(),
             middleware,
             handler,
+            host: None,
+            deprecation: None,
+            cancel_on_disconnect: false,
+            timeout: None,
+        };
+
+        self.add_route(route).await;
+    }
+}
+
+// Bind the first address that works, with SO_REUSEADDR so restarts don't hit AddrInUse
+async fn bind(addr: impl ToSocketAddrs) -> tokio::io::Result<TcpListener> {
+    let mut last_err = None;
+    for addr in lookup_host(addr).await? {
+        let socket = if addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+        // On Windows this would let another process steal the port
+        #[cfg(unix)]
+        socket.set_reuseaddr(true)?;
+        match socket.bind(addr).and_then(|_| socket.listen(1024)) {
+            Ok(listener) => {
+                return Ok(listener);
+            }
+            Err(e) => {
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(
+        last_err.unwrap_or_else(|| {
+            tokio::io::Error::new(tokio::io::ErrorKind::InvalidInput, "address resolved to nothing")
+        })
+    )
+}
+
+// Value of the Expect header, if the client sent one
+fn expect_header(lines: &[String]) -> Option<&str> {
+    lines[1..].iter().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("expect").then(|| value.trim())
+    })
+}
+
+// Interim response telling an `Expect: 100-continue` client to send its body
+async fn write_continue(stream: &Arc<RwLock<Transport>>) {
+    let mut stream = stream.write().await;
+    let _ = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await;
+    let _ = stream.flush().await;
+}
+
+// What the ErrorRenderer gets for a request refused before it was read in full: the head
+// as far as it parses, else the method and path from the request line
+fn refused_request(lines: &[String], conn_info: &Arc<ConnectionInfo>) -> Request {
+    let mut req = Request::new(lines).unwrap_or_else(|_| Request::refused(lines.first().map(String::as_str)));
+    req.set_conn_info(Arc::clone(conn_info));
+    req
+}
+
+// Canonicalize once so each request only has to resolve its own path. A root that
+// doesn't resolve to a directory is never served, a relative fallback would follow the
+// working directory.
+async fn canonical_static_root(path: &str) -> Option<PathBuf> {
+    let root = match tokio::fs::canonicalize(path).await {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("Static path {path} can't be resolved, static files are off: {e}");
+            return None;
+        }
+    };
+    if !tokio::fs::metadata(&root).await.is_ok_and(|meta| meta.is_dir()) {
+        eprintln!("Static path {path} is not a directory, static files are off");
+        return None;
+    }
+    Some(root)
+}
+
+// Read side of a connection whose stream the Response shares for writing
+pub(crate) struct SharedStream {
+    stream: Arc<RwLock<Transport>>,
+    // Queued for the lock while a writer holds it, woken when it's released
+    waiting: Option<Pin<Box<dyn Future<Output = OwnedRwLockWriteGuard<Transport>> + Send>>>,
+}
+
+impl SharedStream {
+    fn new(stream: Arc<RwLock<Transport>>) -> Self {
+        Self { stream, waiting: None }
+    }
+
+    // Leave the queue after a read was given up on, a lock handed to a read nobody polls
+    // again would never be released
+    fn stop_waiting(&mut self) {
+        self.waiting = None;
+    }
+}
+
+impl AsyncRead for SharedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>
+    ) -> Poll<tokio::io::Result<()>> {
+        let this = &mut *self;
+        let waiting = this.waiting.get_or_insert_with(|| Box::pin(Arc::clone(&this.stream).write_owned()));
+        let Poll::Ready(mut stream) = waiting.as_mut().poll(cx) else {
+            return Poll::Pending;
+        };
+        // The guard only lives for this poll, so writers aren't held up by an idle read
+        this.waiting = None;
+        Pin::new(&mut *stream).poll_read(cx, buf)
+    }
+}
+
+// HTTP/1.1 stays open unless the client says close, HTTP/1.0 only if it asks for keep-alive
+fn wants_keep_alive(request_line: &str, connection: Option<&String>) -> bool {
+    let has = |token: &str| {
+        connection.is_some_and(|value| {
+            value.split(',').any(|t| t.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    match request_line.split_whitespace().nth(2) {
+        Some("HTTP/1.1") => !has("close"),
+        Some("HTTP/1.0") => has("keep-alive") && !has("close"),
+        _ => false,
+    }
+}
+
+// Handler chain with panics turned into `Err(message)`. The chain is dropped right away
+// so locks it held are released before the 500 goes out.
+struct CatchPanic(Option<Pin<Box<dyn Future<Output = ()> + Send>>>);
+
+impl Future for CatchPanic {
+    type Output = Result<(), HandlerPanic>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Some(chain) = self.0.as_mut() else {
+            return Poll::Ready(Ok(()));
         };
+        install_backtrace_hook();
+        let outer = IN_CHAIN.replace(true);
+        let polled = catch_unwind(AssertUnwindSafe(|| chain.as_mut().poll(cx)));
+        IN_CHAIN.set(outer);
+        match polled {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => {
+                self.0 = None;
+                Poll::Ready(Err(HandlerPanic {
+                    message: panic_message(payload.as_ref()),
+                    backtrace: CAUGHT_BACKTRACE.take(),
+                }))
+            }
+        }
+    }
+}
+
+thread_local! {
+    // Set while CatchPanic polls a chain, its panics are about to be caught
+    static IN_CHAIN: Cell<bool> = const { Cell::new(false) };
+    // Backtrace of the panic CatchPanic is catching on this thread
+    static CAUGHT_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static BACKTRACE_HOOK: Once = Once::new();
+
+// Keep a backtrace of panics in handler chains for `HandlerPanic`, then run the hook that
+// was there before (by default the usual message on stderr)
+fn install_backtrace_hook() {
+    BACKTRACE_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if IN_CHAIN.get() {
+                CAUGHT_BACKTRACE.set(Some(Backtrace::force_capture().to_string()));
+            }
+            previous(info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+// Poll the socket while the chain runs and fire the request's cancellation on EOF or a reset
+fn watch_disconnect(res: Arc<RwLock<Response>>, cancellation: Cancellation) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(DISCONNECT_POLL_INTERVAL).await;
+            // The handler may be holding the response, try again next tick
+            let Ok(res) = res.try_read() else {
+                continue;
+            };
+            if res.is_finished().await {
+                return;
+            }
+            if res.peer_closed().await {
+                cancellation.cancel();
+                return;
+            }
+        }
+    })
+}
+
+// Patterns are absolute paths with uniquely named, non-empty params
+fn validate_pattern(pattern: &str) -> Result<(), Error> {
+    let invalid = |reason: String| Error::InvalidPattern { pattern: pattern.to_string(), reason };
+
+    if !pattern.starts_with('/') {
+        return Err(invalid("must start with '/'".into()));
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    let segments = split_path(pattern);
+    for (idx, segment) in segments.iter().enumerate() {
+        if let Some(name) = segment.strip_prefix('*') {
+            if idx + 1 != segments.len() {
+                return Err(invalid(format!("wildcard '*{name}' must be the last segment")));
+            }
+            if name.is_empty() {
+                return Err(invalid("wildcard without a name".into()));
+            }
+            if names.contains(&name) {
+                return Err(invalid(format!("parameter '*{name}' appears more than once")));
+            }
+        } else if let Some(name) = segment.strip_prefix(':') {
+            if name.is_empty() {
+                return Err(invalid("parameter without a name".into()));
+            }
+            if names.contains(&name) {
+                return Err(invalid(format!("parameter ':{name}' appears more than once")));
+            }
+            names.push(name);
+        }
+    }
+
+    Ok(())
+}
+
+// Collapsed path without its trailing slash, where `TrailingSlash::Redirect` sends clients
+fn canonical_path(collapsed: &str) -> &str {
+    match collapsed.strip_suffix('/') {
+        Some(trimmed) if !trimmed.is_empty() => trimmed,
+        _ => collapsed,
+    }
+}
+
+// Shape of a pattern for duplicate detection, "/users/:id/" and "/users/:uid" collide
+// (unless every slash counts)
+fn pattern_key(pattern: &str, trailing_slash: TrailingSlash) -> Vec<&str> {
+    let mut key: Vec<&str> = pattern
+        .trim_matches('/')
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with(':') {
+                ":"
+            } else if segment.starts_with('*') {
+                "*"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    if trailing_slash == TrailingSlash::Strict && pattern.len() > 1 && pattern.ends_with('/') {
+        key.push("/");
+    }
+    key
+}
+
+// Request line whose target is over the limit, or that hit the read cap unterminated
+fn uri_too_long(request_line: &str, max_uri_length: usize) -> bool {
+    if !request_line.ends_with('\n') && request_line.len() > max_uri_length {
+        return true;
+    }
+    request_line
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|target| target.len() > max_uri_length)
+}
+
+// Decode the body per Content-Encoding and fix up the header lines to match
+#[cfg(feature = "compression")]
+fn decompress_body(lines: &mut Vec<String>, body: &[u8], limit: usize) -> Result<Option<Vec<u8>>, u16> {
+    let encoding = lines
+        .iter()
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case("content-encoding").then(|| value.trim().to_ascii_lowercase())
+        });
+
+    let Some(encoding) = encoding else {
+        return Ok(None);
+    };
+
+    let decoded = compression::decode(&encoding, body, limit).map_err(|e| e.status())?;
+
+    // Downstream code sees a plain body of the decoded length
+    lines.retain(|line| !line.to_ascii_lowercase().starts_with("content-encoding:"));
+    for line in lines.iter_mut() {
+        if line.to_ascii_lowercase().starts_with("content-length:") {
+            *line = format!("Content-Length: {}", decoded.len());
+        }
+    }
+
+    Ok(Some(decoded))
+}
 
-        self.routes.write().await.push(route);
+// Map a request path onto a file under the static root. Ok(None) when there is no
+// such file, Err(status) when the path is malformed or escapes the root.
+async fn resolve_static(root: &Path, path: &str) -> Result<Option<PathBuf>, u16> {
+    let decoded = percent_decode(path).ok_or(400u16)?;
+    if decoded.contains('\0') {
+        return Err(400);
     }
+
+    let mut relative = decoded.trim_start_matches('/');
+    if relative.is_empty() {
+        relative = "index.html";
+    }
+
+    // Resolves `..` and symlinks, so the prefix check below sees the real location
+    let Ok(file_path) = tokio::fs::canonicalize(root.join(relative)).await else {
+        return Ok(None);
+    };
+    if !file_path.starts_with(root) {
+        return Err(403);
+    }
+    Ok(Some(file_path))
+}
+
+// Box a handler closure into the shared Handler type
+pub(crate) fn wrap_handler<F, Fut>(handler: F) -> Arc<Handler>
+    where
+        F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static
+{
+    Arc::new(move |req, res| Box::pin(handler(req, res)) as Pin<Box<dyn Future<Output = ()> + Send>>)
+}
+
+// Box a middleware closure into the shared Middleware type
+pub(crate) fn wrap_middleware<F, Fut>(middleware: F) -> Arc<Middleware>
+    where
+        F: Fn(Arc<RwLock<Request>>, Arc<RwLock<Response>>, Next) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static
+{
+    Arc::new(move |req, res, next| {
+        Box::pin(middleware(req, res, next)) as Pin<Box<dyn Future<Output = ()> + Send>>
+    })
 }
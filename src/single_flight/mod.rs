@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use crate::response::{ Captured, Response };
+use crate::{ Next, Req, Request, Res };
+
+#[allow(async_fn_in_trait)]
+pub trait SingleFlightExt {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next);
+}
+
+impl SingleFlightExt for Arc<SingleFlight> {
+    async fn run_middleware(&self, req: Req, res: Res, next: Next) {
+        self.coalesce_middleware(req, res, next).await;
+    }
+}
+
+type KeyFn = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+// Identical GETs arriving while one is running wait for it and get a copy of its response
+// instead of running the chain again, e.g. `SingleFlight::new().build()`
+pub struct SingleFlight {
+    max_size: usize,
+    timeout: Duration,
+    key: Option<Box<KeyFn>>,
+    flights: Mutex<HashMap<String, watch::Receiver<Flight>>>,
+}
+
+#[derive(Clone)]
+enum Flight {
+    Running,
+    // None when the leader's response can't be shared, followers run the chain themselves
+    Done(Option<Arc<Captured>>),
+}
+
+enum Role {
+    Leader(watch::Sender<Flight>),
+    Follower(watch::Receiver<Flight>),
+}
+
+impl Default for SingleFlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SingleFlight {
+    pub fn new() -> Self {
+        Self {
+            max_size: 1024 * 1024,
+            timeout: Duration::from_secs(10),
+            key: None,
+            flights: Mutex::default(),
+        }
+    }
+
+    // Largest response shared with followers, 1 MB by default. A bigger one still reaches
+    // the leader, followers then run the chain themselves.
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    // How long a follower waits for the leader before running the chain itself, 10s by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Group requests by something other than method, path and query, e.g. to split them by
+    // an Accept-Language header. Requests where `key` returns None are never coalesced.
+    pub fn key_by<F>(mut self, key: F) -> Self where F: Fn(&Request) -> Option<String> + Send + Sync + 'static {
+        self.key = Some(Box::new(key));
+        self
+    }
+
+    // Shared handle for use with `SingleFlightExt::run_middleware`
+    pub fn build(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub async fn coalesce_middleware(&self, req: Req, res: Res, next: Next) {
+        let key = {
+            let req = req.read().await;
+            // Only safe methods can share one execution
+            if req.method != "GET" && req.method != "HEAD" {
+                None
+            } else {
+                match &self.key {
+                    Some(key) => key(&req).map(|key| format!("{} {key}", req.method)),
+                    None => Some(req.identity()),
+                }
+            }
+        };
+        let Some(key) = key else {
+            next().await;
+            return;
+        };
+
+        match self.join(&key) {
+            Role::Leader(done) => {
+                let captured = Response::capture(&res, next, self.max_size).await
+                    .filter(|captured| captured.is_shareable())
+                    .map(Arc::new);
+                self.land(&key, &done);
+                let _ = done.send(Flight::Done(captured));
+            }
+            Role::Follower(mut flight) => {
+                let landed = timeout(self.timeout, flight.wait_for(|flight| matches!(flight, Flight::Done(_)))).await;
+                // Timed out, or the leader went away without an answer
+                let captured = match landed.ok().and_then(Result::ok).as_deref() {
+                    Some(Flight::Done(captured)) => captured.clone(),
+                    _ => None,
+                };
+                match captured {
+                    Some(captured) => res.read().await.replay(&captured).await,
+                    None => next().await,
+                }
+            }
+        }
+    }
+
+    // Wait on the flight already running for `key`, or start one
+    fn join(&self, key: &str) -> Role {
+        let mut flights = self.flights.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // A leader that panicked or was cancelled dropped its sender without landing
+        if let Some(flight) = flights.get(key)
+            && flight.has_changed().is_ok()
+        {
+            return Role::Follower(flight.clone());
+        }
+        let (done, flight) = watch::channel(Flight::Running);
+        flights.insert(key.to_string(), flight);
+        Role::Leader(done)
+    }
+
+    // Requests from now on start a new flight
+    fn land(&self, key: &str, done: &watch::Sender<Flight>) {
+        let mut flights = self.flights.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if flights.get(key).is_some_and(|flight| flight.same_channel(&done.subscribe())) {
+            flights.remove(key);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::error::StreamError;
+use crate::response::Response;
+
+// Writes Server-Sent Events on a response started by `Response::sse`. The stream
+// stays open until the handler returns or the client goes away.
+#[derive(Debug, Clone)]
+pub struct SseSender {
+    res: Response,
+}
+
+impl SseSender {
+    pub(crate) fn new(res: Response) -> Self {
+        Self { res }
+    }
+
+    // One event block. Multi-line data becomes several `data:` lines, line breaks in
+    // the event name or id are dropped since they would end the field early.
+    // Fails once the client has disconnected.
+    pub async fn send_event(
+        &self,
+        event: Option<&str>,
+        data: &str,
+        id: Option<&str>
+    ) -> Result<(), StreamError> {
+        self.res.write_chunk(format_event(event, data, id).as_bytes()).await
+    }
+
+    // Whether the client closed the connection or a write to it failed
+    pub async fn is_closed(&self) -> bool {
+        self.res.peer_closed().await
+    }
+}
+
+fn format_event(event: Option<&str>, data: &str, id: Option<&str>) -> String {
+    let single_line = |value: &str| value.replace(['\r', '\n'], "");
+    let mut block = String::new();
+    if let Some(event) = event {
+        block.push_str(&format!("event: {}\n", single_line(event)));
+    }
+    if let Some(id) = id {
+        block.push_str(&format!("id: {}\n", single_line(id)));
+    }
+    for line in data.split('\n') {
+        block.push_str(&format!("data: {}\n", line.strip_suffix('\r').unwrap_or(line)));
+    }
+    block.push('\n');
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_event;
+
+    #[test]
+    fn multi_line_data_is_split() {
+        assert_eq!(
+            format_event(Some("tick"), "a\r\nb\n", Some("7")),
+            "event: tick\nid: 7\ndata: a\ndata: b\ndata: \n\n"
+        );
+        assert_eq!(format_event(Some("x\ny"), "", None), "event: xy\ndata: \n\n");
+    }
+}
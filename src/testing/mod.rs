@@ -0,0 +1,192 @@
+use std::sync::Arc;
+use tokio::io::{ duplex, AsyncReadExt, AsyncWriteExt };
+use tokio::sync::watch;
+
+use serde::de::DeserializeOwned;
+
+use crate::request::ConnectionInfo;
+use crate::server::Glote;
+use crate::transport::Transport;
+
+// Room for a request or response in flight, the other side drains it as it goes
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+// Sends requests through the same parsing, routing and middleware as `listen`, over an
+// in-memory pipe instead of a socket, e.g.
+// `let res = TestClient::new(server.clone()).get("/hello").await;`
+#[derive(Clone)]
+pub struct TestClient {
+    server: Arc<Glote>,
+}
+
+// A response as the client received it
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: u16,
+    // In the order sent, names as the server wrote them
+    pub headers: Vec<(String, String)>,
+    // Chunked bodies come de-chunked
+    pub body: Vec<u8>,
+}
+
+impl TestClient {
+    pub fn new(server: Arc<Glote>) -> Self {
+        Self { server }
+    }
+
+    pub async fn get(&self, path: &str) -> TestResponse {
+        self.request("GET", path, &[], b"").await
+    }
+
+    pub async fn post(&self, path: &str, body: &[u8]) -> TestResponse {
+        self.request("POST", path, &[], body).await
+    }
+
+    // Host defaults to localhost, Content-Length is added for a non-empty body. Either is
+    // left alone when given in `headers`.
+    pub async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &[u8]
+    ) -> TestResponse {
+        let given = |header: &str| headers.iter().any(|(name, _)| name.eq_ignore_ascii_case(header));
+        let mut raw = format!("{method} {path} HTTP/1.1\r\n");
+        if !given("host") {
+            raw.push_str("Host: localhost\r\n");
+        }
+        for (name, value) in headers {
+            raw.push_str(&format!("{name}: {value}\r\n"));
+        }
+        if !body.is_empty() && !given("content-length") {
+            raw.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        raw.push_str("\r\n");
+        let mut raw = raw.into_bytes();
+        raw.extend_from_slice(body);
+
+        let received = self.exchange(&raw).await;
+        parse_response(&received, method == "HEAD")
+    }
+
+    // Write the raw request, close our side and collect everything the server sends back
+    async fn exchange(&self, raw: &[u8]) -> Vec<u8> {
+        let (mut client, server_side) = duplex(PIPE_CAPACITY);
+        // Kept alive until the exchange is over, dropping it would read as a shutdown
+        let (_stopping_tx, stopping) = watch::channel(false);
+        let serve = self.server.serve_transport(
+            Transport::Memory(server_side),
+            Arc::new(ConnectionInfo::default()),
+            stopping
+        );
+        let talk = async {
+            let _ = client.write_all(raw).await;
+            // EOF ends the connection once the response is out
+            let _ = client.shutdown().await;
+            let mut received = Vec::new();
+            let _ = client.read_to_end(&mut received).await;
+            received
+        };
+        let ((), received) = tokio::join!(serve, talk);
+        received
+    }
+}
+
+impl TestResponse {
+    // Case-insensitive, first value
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+// First final response in `raw`. Interim 1xx responses are skipped, a response that
+// never arrived comes back with status 0.
+pub(crate) fn parse_response(raw: &[u8], head_request: bool) -> TestResponse {
+    let mut rest = raw;
+    loop {
+        let Some(head_end) = rest.windows(4).position(|w| w == b"\r\n\r\n") else {
+            return TestResponse { status: 0, headers: Vec::new(), body: rest.to_vec() };
+        };
+        let head = String::from_utf8_lossy(&rest[..head_end]).into_owned();
+        let body = &rest[head_end + 4..];
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse::<u16>().ok())
+            .unwrap_or_default();
+        if (100..200).contains(&status) {
+            rest = body;
+            continue;
+        }
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+            .collect();
+
+        let mut response = TestResponse { status, headers, body: Vec::new() };
+        if head_request || status == 204 || status == 304 {
+            return response;
+        }
+        response.body = if response.header("transfer-encoding").is_some_and(|te| te.eq_ignore_ascii_case("chunked")) {
+            dechunk(body)
+        } else if let Some(length) = response.header("content-length").and_then(|l| l.parse::<usize>().ok()) {
+            body[..length.min(body.len())].to_vec()
+        } else {
+            body.to_vec()
+        };
+        return response;
+    }
+}
+
+// Body of a chunked message, trailers dropped. Stops at the first malformed chunk.
+fn dechunk(mut raw: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    while let Some(line_end) = raw.windows(2).position(|w| w == b"\r\n") {
+        let size_line = String::from_utf8_lossy(&raw[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let Ok(size) = usize::from_str_radix(size_hex, 16) else {
+            break;
+        };
+        raw = &raw[line_end + 2..];
+        if size == 0 || raw.len() < size {
+            break;
+        }
+        body.extend_from_slice(&raw[..size]);
+        raw = raw.get(size + 2..).unwrap_or_default();
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_response;
+
+    #[test]
+    fn parses_interim_chunked_and_head_responses() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;x=y\r\nde\r\n0\r\n\r\n";
+        let response = parse_response(raw, false);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"abcde");
+
+        let raw = b"HTTP/1.1 201 Created\r\ncontent-length: 11\r\nX-A: 1\r\n\r\n";
+        let response = parse_response(raw, true);
+        assert_eq!(response.status, 201);
+        assert_eq!(response.header("Content-Length"), Some("11"));
+        assert!(response.body.is_empty());
+
+        assert_eq!(parse_response(b"", false).status, 0);
+    }
+}
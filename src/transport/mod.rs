@@ -0,0 +1,360 @@
+use std::{ io, pin::Pin, task::{ Context, Poll } };
+use tokio::io::{ AsyncRead, AsyncWrite, DuplexStream, ReadBuf };
+use tokio::net::TcpStream;
+#[cfg(feature = "tls")]
+use std::{ path::{ Path, PathBuf }, sync::Arc };
+#[cfg(feature = "tls")]
+use tokio_rustls::{
+    rustls::{
+        self,
+        pki_types::{ CertificateDer, PrivateKeyDer, pem::PemObject },
+        server::WebPkiClientVerifier,
+    },
+    TlsAcceptor,
+};
+
+use crate::request::ConnectionInfo;
+#[cfg(feature = "tls")]
+use crate::request::TlsInfo;
+
+// The byte stream a connection is served over
+pub(crate) enum Transport {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::server::TlsStream<TcpStream>>),
+    // In-process pipe used by `TestClient`
+    Memory(DuplexStream),
+}
+
+impl Transport {
+    // Underlying socket, for peeking at disconnects. None for in-memory connections.
+    pub(crate) fn tcp(&self) -> Option<&TcpStream> {
+        match self {
+            Transport::Plain(stream) => Some(stream),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => Some(stream.get_ref().0),
+            Transport::Memory(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Plain(stream) => f.debug_tuple("Plain").field(stream).finish(),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => f.debug_tuple("Tls").field(stream.get_ref().0).finish(),
+            Transport::Memory(_) => f.write_str("Memory"),
+        }
+    }
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Memory(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Memory(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Memory(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::Memory(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+// Whether HTTPS clients have to present a certificate. The path is a PEM file with the
+// CA certificates client certificates must chain to.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone, Default)]
+pub enum ClientAuth {
+    #[default]
+    None,
+    // Clients without a certificate get in, ones with an untrusted certificate don't
+    Optional(PathBuf),
+    // The handshake fails without a trusted certificate
+    Required(PathBuf),
+}
+
+// Turns accepted sockets into Transports, running the TLS handshake if configured
+#[derive(Clone)]
+pub(crate) enum Acceptor {
+    Plain,
+    #[cfg(feature = "tls")]
+    Tls(TlsAcceptor),
+}
+
+impl Acceptor {
+    // Fills in the TLS details of `conn_info`
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+    pub(crate) async fn accept(
+        &self,
+        stream: TcpStream,
+        conn_info: &mut ConnectionInfo
+    ) -> io::Result<Transport> {
+        match self {
+            Acceptor::Plain => Ok(Transport::Plain(stream)),
+            #[cfg(feature = "tls")]
+            Acceptor::Tls(acceptor) => {
+                let stream = acceptor.accept(stream).await?;
+                let (_, connection) = stream.get_ref();
+                conn_info.alpn_protocol = connection
+                    .alpn_protocol()
+                    .map(|alpn| String::from_utf8_lossy(alpn).into_owned());
+                conn_info.tls = Some(TlsInfo {
+                    version: connection
+                        .protocol_version()
+                        .and_then(|version| version.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    cipher: connection
+                        .negotiated_cipher_suite()
+                        .and_then(|suite| suite.suite().as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    peer_certificate: connection
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .map(|cert| cert.to_vec()),
+                    peer_subject: connection
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .and_then(|cert| certificate_subject(cert)),
+                });
+                Ok(Transport::Tls(Box::new(stream)))
+            }
+        }
+    }
+
+    // Load a PEM certificate chain and private key, failing with the offending path
+    #[cfg(feature = "tls")]
+    pub(crate) fn tls(
+        cert_pem_path: &Path,
+        key_pem_path: &Path,
+        client_auth: &ClientAuth
+    ) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        let certs = CertificateDer::pem_file_iter(cert_pem_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                invalid(format!("can't read certificate {}: {e}", cert_pem_path.display()))
+            })?;
+        if certs.is_empty() {
+            return Err(invalid(format!("no certificate in {}", cert_pem_path.display())));
+        }
+        let key = PrivateKeyDer::from_pem_file(key_pem_path).map_err(|e| {
+            invalid(format!("can't read private key {}: {e}", key_pem_path.display()))
+        })?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| invalid(format!("TLS setup failed: {e}")))?;
+        let builder = match client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(ca_pem_path) | ClientAuth::Required(ca_pem_path) => {
+                let mut roots = rustls::RootCertStore::empty();
+                let ca_certs = CertificateDer::pem_file_iter(ca_pem_path)
+                    .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                    .map_err(|e| {
+                        invalid(format!("can't read client CA {}: {e}", ca_pem_path.display()))
+                    })?;
+                for cert in ca_certs {
+                    roots.add(cert).map_err(|e| {
+                        invalid(format!("bad client CA in {}: {e}", ca_pem_path.display()))
+                    })?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = match client_auth {
+                    ClientAuth::Optional(_) => verifier.allow_unauthenticated().build(),
+                    _ => verifier.build(),
+                };
+                let verifier = verifier.map_err(|e| {
+                    invalid(format!("no usable client CA in {}: {e}", ca_pem_path.display()))
+                })?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| invalid(format!("certificate and key don't fit together: {e}")))?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Acceptor::Tls(TlsAcceptor::from(Arc::new(config))))
+    }
+}
+
+// Subject of a DER certificate as `CN=ada,O=Acme` (RFC 4514 order, most specific first).
+// Attributes that aren't strings are left out, None if the certificate doesn't parse.
+#[cfg(feature = "tls")]
+fn certificate_subject(der: &[u8]) -> Option<String> {
+    let (_, certificate, _) = der_element(der)?;
+    let (_, mut tbs, _) = der_element(certificate)?;
+    // Optional [0] version, then serial number, signature algorithm, issuer, validity
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_element(tbs)?.2;
+    }
+    for _ in 0..4 {
+        tbs = der_element(tbs)?.2;
+    }
+    let (_, mut subject, _) = der_element(tbs)?;
+
+    let mut names = Vec::new();
+    while !subject.is_empty() {
+        // Each relative distinguished name is a SET of type/value SEQUENCEs
+        let (_, mut set, rest) = der_element(subject)?;
+        subject = rest;
+        let mut parts = Vec::new();
+        while !set.is_empty() {
+            let (_, attribute, rest) = der_element(set)?;
+            set = rest;
+            let (_, oid, value) = der_element(attribute)?;
+            let (tag, value, _) = der_element(value)?;
+            // UTF8String, PrintableString, T61String, IA5String
+            if !matches!(tag, 0x0c | 0x13 | 0x14 | 0x16) {
+                continue;
+            }
+            let name = match oid {
+                [0x55, 0x04, 0x03] => "CN",
+                [0x55, 0x04, 0x06] => "C",
+                [0x55, 0x04, 0x07] => "L",
+                [0x55, 0x04, 0x08] => "ST",
+                [0x55, 0x04, 0x0a] => "O",
+                [0x55, 0x04, 0x0b] => "OU",
+                _ => {
+                    continue;
+                }
+            };
+            parts.push(format!("{name}={}", escape_dn_value(&String::from_utf8_lossy(value))));
+        }
+        // Multi-valued names stay together, joined with '+'
+        if !parts.is_empty() {
+            names.push(parts.join("+"));
+        }
+    }
+    names.reverse();
+    Some(names.join(","))
+}
+
+// RFC 4514 escaping, so a value with ',' or '+' can't pass for another attribute
+#[cfg(feature = "tls")]
+fn escape_dn_value(value: &str) -> String {
+    let last = value.chars().count().saturating_sub(1);
+    let mut escaped = String::with_capacity(value.len());
+    for (idx, c) in value.chars().enumerate() {
+        match c {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\0' => escaped.push_str("\\00"),
+            '#' if idx == 0 => escaped.push_str("\\#"),
+            ' ' if idx == 0 || idx == last => escaped.push_str("\\ "),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Tag, contents and what follows of the DER element at the start of `input`
+#[cfg(feature = "tls")]
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *input.first()?;
+    let first = *input.get(1)?;
+    let (length, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + count)?;
+        (bytes.iter().fold(0usize, |length, &byte| (length << 8) | (byte as usize)), 2 + count)
+    };
+    let contents = input.get(header..header.checked_add(length)?)?;
+    Some((tag, contents, &input[header + length..]))
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use tokio_rustls::rustls::pki_types::{ CertificateDer, pem::PemObject };
+
+    use super::{ certificate_subject, der_element };
+
+    #[test]
+    fn reads_certificate_subjects() {
+        let fixture = |name: &str| {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+            CertificateDer::from_pem_file(path).unwrap()
+        };
+        assert_eq!(certificate_subject(&fixture("client_cert.pem")).as_deref(), Some("CN=ada,O=Glote Tests"));
+        assert_eq!(certificate_subject(&fixture("tls_cert.pem")).as_deref(), Some("CN=localhost"));
+        assert_eq!(certificate_subject(b"\x30\x05\x30"), None);
+    }
+
+    #[test]
+    fn escapes_subject_values() {
+        fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+            [&[tag, contents.len() as u8][..], contents].concat()
+        }
+        let attribute = |oid: u8, value: &str| der(0x30, &[der(0x06, &[0x55, 0x04, oid]), der(0x0c, value.as_bytes())].concat());
+        let subject = der(0x30, &[
+            der(0x31, &attribute(0x0a, "Acme, Inc.")),
+            // One name with two values
+            der(0x31, &[attribute(0x0b, "ops"), attribute(0x0b, "#1 team ")].concat()),
+            der(0x31, &attribute(0x03, "Lovelace, Ada+admin")),
+        ].concat());
+        let tbs = der(0x30, &[
+            der(0x02, &[1]),
+            der(0x30, &[]),
+            der(0x30, &[]),
+            der(0x30, &[]),
+            subject,
+        ].concat());
+        let certificate = der(0x30, &tbs);
+
+        assert_eq!(
+            certificate_subject(&certificate).as_deref(),
+            Some(r"CN=Lovelace\, Ada\+admin,OU=ops+OU=\#1 team\ ,O=Acme\, Inc.")
+        );
+    }
+
+    #[test]
+    fn splits_der_elements() {
+        assert_eq!(der_element(&[0x02, 0x01, 0x05, 0xff]), Some((0x02, &[0x05][..], &[0xff][..])));
+        let long = [&[0x04, 0x81, 0x80][..], &[0u8; 0x80]].concat();
+        assert_eq!(der_element(&long).map(|(_, contents, _)| contents.len()), Some(0x80));
+        assert_eq!(der_element(&[0x04, 0x05, 0x00]), None);
+    }
+}
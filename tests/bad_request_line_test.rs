@@ -0,0 +1,39 @@
+mod common;
+
+use std::net::TcpStream;
+
+use glote::{ Glote, ResponseExt };
+
+const PORT: u16 = 41_390;
+
+// (name, raw request)
+const GARBAGE: &[(&str, &[u8])] = &[
+    ("telnet typo", b"hello\r\n\r\n"),
+    ("missing version", b"GET /\r\nHost: x\r\n\r\n"),
+    ("unknown version", b"GET / HTTP/9.9\r\nHost: x\r\n\r\n"),
+    ("relative target", b"GET index.html HTTP/1.1\r\nHost: x\r\n\r\n"),
+    ("binary line", b"\x16\x03\x01\x02\x00\x01\xff\xfe\r\n\r\n"),
+];
+
+#[test]
+fn test_malformed_request_lines_get_400() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |_req, res| async move {
+            res.send("home").await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+
+    for (name, raw) in GARBAGE {
+        let response = common::raw_request(PORT, raw);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{name}: {response}");
+        assert!(response.contains("Connection: close\r\n"), "{name}: {response}");
+    }
+
+    // Connect and hang up straight away
+    drop(TcpStream::connect(("127.0.0.1", PORT)).unwrap());
+
+    let response = common::raw_request(PORT, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with("home"), "{response}");
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use glote::{ AuthenticatedUser, BasicAuth, BasicAuthExt, Glote, Next, Req, RequestExt, Res, ResponseExt, TestClient, mid };
+
+fn check(user: &str, password: &str) -> bool {
+    user == "ada" && password == "secret"
+}
+
+// "ada:secret" and "ada:wrong"
+const GOOD: &str = "Basic YWRhOnNlY3JldA==";
+const WRONG: &str = "Basic YWRhOndyb25n";
+
+async fn whoami(req: Req, res: Res) {
+    let user = req.ext::<AuthenticatedUser>().await.map(|user| user.username.clone());
+    res.send(&format!("hello {}", user.unwrap_or_default())).await;
+}
+
+#[test]
+fn test_global_basic_auth() {
+    let server = Glote::new();
+    server.block_on(async {
+        let auth = BasicAuth::new("admin area", check).build();
+        server.use_middleware(move |req, res, next| {
+            let auth = Arc::clone(&auth);
+            async move {
+                auth.run_middleware(req, res, next).await;
+            }
+        }).await;
+        server.get("/", whoami).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.request("GET", "/", &[("Authorization", GOOD)], b"").await;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "hello ada");
+
+        let response = client.request("GET", "/", &[("Authorization", WRONG)], b"").await;
+        assert_eq!(response.status, 401);
+        assert_eq!(response.header("WWW-Authenticate"), Some("Basic realm=\"admin area\""));
+
+        let response = client.get("/").await;
+        assert_eq!(response.status, 401);
+        assert_eq!(response.header("WWW-Authenticate"), Some("Basic realm=\"admin area\""));
+
+        let response = client.request("GET", "/", &[("Authorization", "Basic !!not base64")], b"").await;
+        assert_eq!(response.status, 401);
+    });
+}
+
+#[test]
+fn test_route_basic_auth() {
+    let server = Glote::new();
+    server.block_on(async {
+        // Route middleware can't capture, so the guard lives in app state
+        server.state(BasicAuth::new("admin", check)).await;
+        let guard = mid!(req, res, next, {
+            let auth = req.state::<BasicAuth>().await.unwrap();
+            auth.run_middleware(req, res, next).await;
+        });
+        server.get_with_middleware("/admin", vec![guard], whoami).await;
+        server.get("/public", |_req, res| async move { res.send("open").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        assert_eq!(client.get("/admin").await.status, 401);
+        assert_eq!(client.get("/public").await.text(), "open");
+
+        let response = client.request("GET", "/admin", &[("Authorization", GOOD)], b"").await;
+        assert_eq!(response.text(), "hello ada");
+    });
+}
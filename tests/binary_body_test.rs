@@ -0,0 +1,42 @@
+mod common;
+
+use std::io::{ Read, Write };
+use glote::{ Glote, RequestExt };
+
+#[test]
+fn test_binary_body_round_trips() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.post("/echo", |req, res| async move {
+            let bytes = req.body_bytes().await.unwrap_or_default();
+            res.read().await.send_bytes(&bytes, "application/octet-stream").await;
+        }).await;
+        server.post("/text", |req, res| async move {
+            let text = req.body().await.unwrap_or_default();
+            res.read().await.send_bytes(text.as_bytes(), "text/plain").await;
+        }).await;
+    });
+    common::spawn_server(server, 41_180);
+
+    let body: &[u8] = b"{\"a\":\"x\r\ny\"}\0\xff\x00line\r\n\r\ntrailing\n";
+    let mut stream = common::connect(41_180);
+    stream.write_all(
+        format!("POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes()
+    ).unwrap();
+    stream.write_all(body).unwrap();
+    let mut response = vec![0u8; 0];
+    let mut chunk = [0u8; 1024];
+    while !response.ends_with(body) {
+        let n = stream.read(&mut chunk).unwrap();
+        assert!(n > 0, "{}", String::from_utf8_lossy(&response));
+        response.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&response[..response.len() - body.len()]).to_string();
+    assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", body.len())), "{head}");
+
+    let text = common::raw_request(
+        41_180,
+        b"POST /text HTTP/1.1\r\nHost: x\r\nContent-Length: 6\r\n\r\na\r\nb\xffc"
+    );
+    assert!(text.ends_with("a\r\nb\u{fffd}c"), "{text}");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::{ io::{ Read, Write }, net::{ SocketAddr, TcpStream }, sync::{ Arc, mpsc }, thread, time::Duration };
+use glote::{ Glote, ResponseExt };
+
+fn hello_server() -> Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |_req, res| async move {
+            res.send("hello").await;
+        }).await;
+    });
+    server
+}
+
+fn wait_for_addr(server: &Arc<Glote>) -> SocketAddr {
+    for _ in 0..100 {
+        if let Some(addr) = server.block_on(server.local_addr()) {
+            return addr;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("server never bound");
+}
+
+fn get(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_listen_on_os_assigned_port() {
+    let server = hello_server();
+    let listener = server.clone();
+    thread::spawn(move || {
+        let _ = listener.block_on(listener.clone().listen_on("127.0.0.1:0"));
+    });
+
+    let addr = wait_for_addr(&server);
+    assert!(addr.ip().is_loopback());
+    assert_ne!(addr.port(), 0);
+    assert!(get(addr).ends_with("hello"));
+}
+
+#[test]
+fn test_listen_on_ipv6_loopback() {
+    // Sandboxes without IPv6 can't run this
+    if std::net::TcpListener::bind("[::1]:0").is_err() {
+        return;
+    }
+    let server = hello_server();
+    let listener = server.clone();
+    thread::spawn(move || {
+        let _ = listener.block_on(listener.clone().listen_on(("::1", 0)));
+    });
+
+    let addr = wait_for_addr(&server);
+    assert!(addr.is_ipv6());
+    assert!(get(addr).ends_with("hello"));
+}
+
+#[test]
+fn test_rebind_right_after_shutdown() {
+    let server = hello_server();
+    let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+    let (done_tx, done) = mpsc::channel();
+    let listener = server.clone();
+    thread::spawn(move || {
+        let result = listener.block_on(
+            listener.clone().listen_with_shutdown("127.0.0.1:0", async {
+                let _ = signal.await;
+            })
+        );
+        done_tx.send(result.is_ok()).unwrap();
+    });
+
+    let addr = wait_for_addr(&server);
+    // The server closes first, leaving its side of the connection in TIME_WAIT
+    assert!(get(addr).ends_with("hello"));
+    trigger.send(()).unwrap();
+    assert!(done.recv_timeout(Duration::from_secs(3)).unwrap());
+    assert_eq!(server.block_on(server.local_addr()), None);
+
+    let restarted = hello_server();
+    let listener = restarted.clone();
+    thread::spawn(move || {
+        let _ = listener.block_on(listener.clone().listen_on(addr));
+    });
+    assert_eq!(wait_for_addr(&restarted), addr);
+    assert!(get(addr).ends_with("hello"));
+}
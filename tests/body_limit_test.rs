@@ -0,0 +1,85 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+fn start(port: u16, max_body_size: Option<usize>) {
+    let server = Glote::new();
+    server.block_on(async {
+        if let Some(bytes) = max_body_size {
+            server.max_body_size(bytes).await;
+        }
+        server.post("/upload", |req, res| async move {
+            let len = req.body_bytes().await.map(|body| body.len()).unwrap_or_default();
+            res.send(&len.to_string()).await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+// Resident memory of the test process (the server runs in it too), in kB
+#[cfg(target_os = "linux")]
+fn resident_kb() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap()
+}
+
+#[test]
+fn test_huge_content_length_is_refused_without_allocating() {
+    start(41_290, None);
+    common::raw_request(41_290, b"POST /upload HTTP/1.1\r\nHost: x\r\nContent-Length: 1\r\n\r\na");
+
+    #[cfg(target_os = "linux")]
+    let before = resident_kb();
+    let response = common::raw_request(
+        41_290,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nContent-Length: 10000000000\r\n\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{response}");
+    assert!(response.contains("Connection: close\r\n"), "{response}");
+    #[cfg(target_os = "linux")]
+    assert!(resident_kb() < before + 64 * 1024);
+
+    // Just under the 2 MB default is fine
+    let body = vec![b'a'; 2 * 1024 * 1024];
+    let mut request = format!(
+        "POST /upload HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    ).into_bytes();
+    request.extend_from_slice(&body);
+    let response = common::raw_request(41_290, &request);
+    assert!(response.ends_with("\r\n\r\n2097152"), "{response}");
+}
+
+#[test]
+fn test_configured_limit_covers_both_framings() {
+    start(41_291, Some(16));
+
+    let response = common::raw_request(
+        41_291,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nContent-Length: 16\r\n\r\n0123456789abcdef"
+    );
+    assert!(response.ends_with("\r\n\r\n16"), "{response}");
+
+    let response = common::raw_request(
+        41_291,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nContent-Length: 17\r\n\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{response}");
+
+    // Chunks are counted as they arrive
+    let response = common::raw_request(
+        41_291,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n01234567\r\n8\r\n89abcdef\r\n0\r\n\r\n"
+    );
+    assert!(response.ends_with("\r\n\r\n16"), "{response}");
+
+    let response = common::raw_request(
+        41_291,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n01234567\r\n9\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{response}");
+}
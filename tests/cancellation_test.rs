@@ -0,0 +1,202 @@
+mod common;
+
+use std::{
+    io::{ Read, Write },
+    net::Shutdown,
+    sync::{ Arc, atomic::{ AtomicBool, Ordering } },
+    thread,
+    time::{ Duration, Instant },
+};
+use glote::{ Glote, RequestExt, ResponseExt, Router };
+
+fn wait_for(flag: &AtomicBool) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(3) {
+        if flag.load(Ordering::SeqCst) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+fn hang_up(port: u16) {
+    let mut stream = common::connect(port);
+    stream.write_all(b"GET /slow HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    thread::sleep(Duration::from_millis(150));
+    // A plain close, like a closed tab
+    drop(stream);
+}
+
+// Same, but the client is killed and the connection reset
+fn crash(port: u16) {
+    let mut stream = common::connect(port);
+    stream.write_all(b"GET /slow HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    thread::sleep(Duration::from_millis(150));
+    common::reset(stream);
+}
+
+#[test]
+fn test_handler_observes_disconnect() {
+    let server = Glote::new();
+    let observed = Arc::new(AtomicBool::new(false));
+
+    let flag = observed.clone();
+    server.block_on(async {
+        server.get("/slow", move |req, res| {
+            let flag = flag.clone();
+            async move {
+                tokio::select! {
+                    _ = req.cancelled() => {
+                        flag.store(req.is_cancelled().await, Ordering::SeqCst);
+                    }
+                    _ = tokio::time::sleep(Duration::from_secs(10)) => {
+                        res.send("done").await;
+                    }
+                }
+            }
+        }).await;
+    });
+
+    common::spawn_server(server, 40_890);
+    hang_up(40_890);
+
+    assert!(wait_for(&observed), "handler never saw the disconnect");
+}
+
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_cancel_on_disconnect_drops_handler() {
+    let server = Glote::new();
+    let dropped = Arc::new(AtomicBool::new(false));
+    let finished = Arc::new(AtomicBool::new(false));
+
+    let (drop_flag, done_flag) = (dropped.clone(), finished.clone());
+    server.block_on(async {
+        let mut router = Router::new("/");
+        router.cancel_on_disconnect();
+        router.get("/slow", move |_req, res| {
+            let guard = DropFlag(drop_flag.clone());
+            let done = done_flag.clone();
+            async move {
+                let _guard = guard;
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                done.store(true, Ordering::SeqCst);
+                res.send("done").await;
+            }
+        });
+        server.mount(router).await;
+    });
+
+    common::spawn_server(server, 40_891);
+    crash(40_891);
+
+    assert!(wait_for(&dropped), "handler future was not dropped");
+    assert!(!finished.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_connected_client_is_not_cancelled() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/wait", |req, res| async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            res.send(&format!("cancelled={}", req.is_cancelled().await)).await;
+        }).await;
+    });
+
+    common::spawn_server(server, 40_892);
+    let response = common::raw_request(40_892, b"GET /wait HTTP/1.1\r\nHost: x\r\n\r\n");
+
+    assert!(response.ends_with("cancelled=false"), "{response}");
+}
+
+#[test]
+fn test_other_routes_are_not_dropped() {
+    let server = Glote::new();
+    let finished = Arc::new(AtomicBool::new(false));
+
+    let done_flag = finished.clone();
+    server.block_on(async {
+        let mut router = Router::new("/reports");
+        router.cancel_on_disconnect();
+        router.get("/slow", |_req, res| async move { res.send("report").await });
+        server.mount(router).await;
+        server.get("/slow", move |_req, res| {
+            let done = done_flag.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                done.store(true, Ordering::SeqCst);
+                res.send("done").await;
+            }
+        }).await;
+    });
+
+    common::spawn_server(server, 40_893);
+    hang_up(40_893);
+
+    assert!(wait_for(&finished), "handler outside the router was dropped");
+}
+
+#[test]
+fn test_half_closed_client_gets_its_answer() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.allow_half_close(true).await;
+        let mut router = Router::new("/");
+        router.cancel_on_disconnect();
+        router.get("/wait", |req, res| async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            res.send(&format!("cancelled={}", req.is_cancelled().await)).await;
+        });
+        server.mount(router).await;
+    });
+
+    common::spawn_server(server, 40_894);
+    let mut stream = common::connect(40_894);
+    stream.write_all(b"GET /wait HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").unwrap();
+    // Done sending, still reading
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("cancelled=false"), "{response}");
+}
+
+#[test]
+fn test_failed_write_cancels() {
+    let server = Glote::new();
+    let observed = Arc::new(AtomicBool::new(false));
+
+    let flag = observed.clone();
+    server.block_on(async {
+        server.get("/feed", move |req, res| {
+            let flag = flag.clone();
+            async move {
+                res.begin(200, &[]).await.unwrap();
+                // Keeps writing until the closed socket refuses
+                while res.write_chunk(b"tick\n").await.is_ok() {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                flag.store(req.is_cancelled().await, Ordering::SeqCst);
+            }
+        }).await;
+    });
+
+    common::spawn_server(server, 40_895);
+    let mut stream = common::connect(40_895);
+    stream.write_all(b"GET /feed HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    let mut chunk = [0u8; 256];
+    let _ = stream.read(&mut chunk).unwrap();
+    drop(stream);
+
+    assert!(wait_for(&observed), "handler never saw the failed write");
+}
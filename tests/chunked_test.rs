@@ -0,0 +1,71 @@
+mod common;
+
+use std::io::Write;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+const PORT: u16 = 41_190;
+
+fn start() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.post("/echo", |req, res| async move {
+            let body = req.body_bytes().await.unwrap_or_default();
+            res.send(&format!("{}:{}", body.len(), String::from_utf8_lossy(&body))).await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+}
+
+#[test]
+fn test_chunked_bodies() {
+    start();
+
+    // Several chunks, an extension and a trailer
+    let response = common::raw_request(
+        PORT,
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+          5\r\nhello\r\n1;ext=1\r\n \r\nA\r\nchunked wo\r\n3\r\nrld\r\n0\r\nX-Checksum: 1\r\n\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\n19:hello chunked world"), "{response}");
+
+    // Empty chunked body
+    let response = common::raw_request(
+        PORT,
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: Chunked\r\nConnection: close\r\n\r\n0\r\n\r\n"
+    );
+    assert!(response.ends_with("\r\n\r\n0:"), "{response}");
+
+    // The connection stays usable for the next request
+    let mut stream = common::connect(PORT);
+    stream
+        .write_all(
+            b"POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n0\r\n\r\n"
+        )
+        .unwrap();
+    let first = common::read_response(&mut stream);
+    assert!(first.ends_with("\r\n\r\n2:ab"), "{first}");
+    stream
+        .write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\nConnection: close\r\n\r\ncd")
+        .unwrap();
+    let second = common::read_response(&mut stream);
+    assert!(second.ends_with("\r\n\r\n2:cd"), "{second}");
+
+    // Malformed framing is answered with 400 instead of waiting for more data
+    for (name, raw) in [
+        ("non-hex size", "zz\r\nhello\r\n0\r\n\r\n"),
+        ("signed size", "+5\r\nhello\r\n0\r\n\r\n"),
+        ("empty size", "\r\nhello\r\n0\r\n\r\n"),
+        ("missing CRLF after data", "5\r\nhelloXX0\r\n\r\n"),
+        ("bare LF size line", "5\nhello\r\n0\r\n\r\n"),
+        ("oversized size", "fffffffffffffffff\r\nhello\r\n0\r\n\r\n"),
+    ] {
+        let request = format!(
+            "POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n{raw}"
+        );
+        let response = common::raw_request(PORT, request.as_bytes());
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{name}: {response}");
+        assert!(response.contains("Connection: close\r\n"), "{name}: {response}");
+    }
+}
@@ -0,0 +1,69 @@
+#![allow(dead_code)]
+
+use std::{ io::{ Read, Write }, net::TcpStream, sync::Arc, thread, time::Duration };
+
+use glote::Glote;
+
+// Run `listen` on its own thread so the test thread can talk to it over raw TCP
+pub fn spawn_server(server: Arc<Glote>, port: u16) {
+    thread::spawn(move || {
+        let listener = server.clone();
+        let _ = server.block_on(listener.listen(("127.0.0.1", port)));
+    });
+}
+
+// Connect, retrying while the server is still binding
+pub fn connect(port: u16) -> TcpStream {
+    for _ in 0..100 {
+        if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            return stream;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("server on port {port} never came up");
+}
+
+// Close with a reset instead of a FIN, like a client that was killed
+pub fn reset(stream: TcpStream) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+    let _entered = runtime.enter();
+    stream.set_nonblocking(true).unwrap();
+    let stream = tokio::net::TcpStream::from_std(stream).unwrap();
+    stream.set_linger(Some(Duration::ZERO)).unwrap();
+}
+
+// Send raw bytes and read one response, or everything until the server closes the socket
+pub fn raw_request(port: u16, raw: &[u8]) -> String {
+    let mut stream = connect(port);
+    stream.write_all(raw).unwrap();
+    read_response(&mut stream)
+}
+
+// Read a single response off a (possibly kept-alive) connection, framed by Content-Length
+pub fn read_response(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(head_end) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&response[..head_end]).to_ascii_lowercase();
+            let length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse::<usize>().ok());
+            let bodyless = head.starts_with("http/1.1 304") || head.starts_with("http/1.1 1");
+            let expected = if bodyless { Some(0) } else { length };
+            if let Some(expected) = expected
+                && response.len() >= head_end + 4 + expected
+            {
+                response.truncate(head_end + 4 + expected);
+                break;
+            }
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => response.extend_from_slice(&chunk[..n]),
+        }
+    }
+    String::from_utf8_lossy(&response).to_string()
+}
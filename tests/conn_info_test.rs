@@ -0,0 +1,26 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+#[test]
+fn test_handler_sees_connection_addresses() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/whoami", |req, res| async move {
+            let info = req.conn_info().await;
+            res.send(
+                &format!(
+                    "{}|{}|{}|{}",
+                    info.local_addr.map(|a| a.port()).unwrap_or_default(),
+                    info.peer_addr.map(|a| a.ip().to_string()).unwrap_or_default(),
+                    info.tls.is_some(),
+                    info.alpn_protocol.is_some()
+                )
+            ).await;
+        }).await;
+    });
+    common::spawn_server(server, 40_920);
+
+    let response = common::raw_request(40_920, b"GET /whoami HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\n40920|127.0.0.1|false|false"), "{response}");
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use glote::{ CookieOptions, Glote, RequestExt, ResponseExt, SameSite, TestClient };
+
+#[test]
+fn test_request_cookies_and_set_cookie_lines() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |req, res| async move {
+            let cookies = req.cookies().await;
+            let session = req.cookie("session").await.unwrap_or_default();
+
+            res.set_cookie("session", "new", &CookieOptions {
+                path: Some("/".into()),
+                max_age: Some(3600),
+                secure: true,
+                http_only: true,
+                same_site: Some(SameSite::Lax),
+                ..Default::default()
+            }).await.unwrap();
+            res.set_cookie("theme", "dark", &CookieOptions::default()).await.unwrap();
+            res.send(&format!("{} {} {:?}", cookies.len(), session, cookies.get("theme"))).await;
+        }).await;
+    });
+    common::spawn_server(server, 41_090);
+
+    let response = common::raw_request(
+        41_090,
+        b"GET / HTTP/1.1\r\nHost: x\r\nCookie:  session=abc ;theme=\"light\";; flag\r\n\r\n"
+    );
+    assert!(
+        response.contains(
+            "\r\nSet-Cookie: session=new; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax\r\n"
+        ),
+        "{response}"
+    );
+    assert!(response.contains("\r\nSet-Cookie: theme=dark\r\n"), "{response}");
+    assert!(response.ends_with(r#"2 abc Some("light")"#), "{response}");
+}
+
+#[test]
+fn test_set_cookie_refuses_injection() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/remember", |req, res| async move {
+            let theme = req.query("theme").await.unwrap_or_default();
+            let outcome = res.set_cookie("theme", &theme, &CookieOptions::default()).await;
+            res.send(if outcome.is_ok() { "set" } else { "refused" }).await;
+        }).await;
+
+        server.get("/options", |_req, res| async move {
+            let path = CookieOptions { path: Some("/; Domain=evil.com".into()), ..Default::default() };
+            let domain = CookieOptions { domain: Some("example.com\r\nX-Evil: 1".into()), ..Default::default() };
+            let refused = [
+                res.set_cookie("bad name", "a", &CookieOptions::default()).await.is_err(),
+                res.set_cookie("id", "a", &path).await.is_err(),
+                res.set_cookie("id", "a", &domain).await.is_err(),
+            ];
+            res.json(&refused).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        for theme in ["dark;%20Domain=evil.com", "dark%0d%0aX-Evil:%201", "dark%20mode"] {
+            let response = client.get(&format!("/remember?theme={theme}")).await;
+            assert_eq!(response.text(), "refused", "{theme}");
+            assert_eq!(response.header("Set-Cookie"), None);
+            assert_eq!(response.header("X-Evil"), None);
+        }
+        let response = client.get("/remember?theme=dark").await;
+        assert_eq!(response.text(), "set");
+        assert_eq!(response.header("Set-Cookie"), Some("theme=dark"));
+
+        let response = client.get("/options").await;
+        assert_eq!(response.text(), "[true,true,true]");
+        assert_eq!(response.header("Set-Cookie"), None);
+        assert_eq!(response.header("X-Evil"), None);
+    });
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use std::sync::Arc;
+
+use glote::{ Cors, CorsExt, Glote, ResponseExt };
+
+fn start(port: u16, cors: Cors) {
+    let cors = cors.build();
+    let server = Glote::new();
+    server.block_on(async {
+        server.use_middleware(move |req, res, next| {
+            let cors = Arc::clone(&cors);
+            async move {
+                cors.run_middleware(req, res, next).await;
+            }
+        }).await;
+        server.put("/items", |_req, res| async move {
+            res.send("saved").await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+fn put(port: u16, origin: Option<&str>) -> String {
+    let origin = origin.map(|origin| format!("Origin: {origin}\r\n")).unwrap_or_default();
+    let request = format!("PUT /items HTTP/1.1\r\nHost: x\r\n{origin}Content-Length: 0\r\n\r\n");
+    common::raw_request(port, request.as_bytes())
+}
+
+#[test]
+fn test_cors_builder_headers() {
+    start(
+        41_320,
+        Cors::new()
+            .origins(&["https://app.example"])
+            .methods(&["GET", "PUT", "DELETE"])
+            .headers(&["Authorization", "Content-Type"])
+            .credentials(true)
+            .max_age(600)
+    );
+
+    let response = put(41_320, Some("https://app.example"));
+    assert!(response.ends_with("\r\n\r\nsaved"), "{response}");
+    for header in [
+        "Vary: Origin",
+        "Access-Control-Allow-Origin: https://app.example",
+        "Access-Control-Allow-Methods: GET, PUT, DELETE",
+        "Access-Control-Allow-Headers: Authorization, Content-Type",
+        "Access-Control-Allow-Credentials: true",
+        "Access-Control-Max-Age: 600",
+    ] {
+        assert!(response.contains(&format!("\r\n{header}\r\n")), "{header}: {response}");
+    }
+
+    // Unlisted origins are served without CORS headers, the browser blocks them
+    let response = put(41_320, Some("https://evil.example"));
+    assert!(response.ends_with("\r\n\r\nsaved"), "{response}");
+    assert!(response.contains("\r\nVary: Origin\r\n"), "{response}");
+    assert!(!response.contains("Access-Control-"), "{response}");
+
+    // Same-origin and non-browser clients don't send Origin
+    let response = put(41_320, None);
+    assert!(response.ends_with("\r\n\r\nsaved"), "{response}");
+}
+
+#[test]
+fn test_cors_wildcard_and_rejection() {
+    start(41_321, Cors::new().origins(&["*"]));
+    let response = put(41_321, Some("https://any.example"));
+    assert!(response.contains("\r\nAccess-Control-Allow-Origin: *\r\n"), "{response}");
+    assert!(response.contains("\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS\r\n"), "{response}");
+    assert!(!response.contains("Access-Control-Allow-Credentials"), "{response}");
+
+    // Credentials can't go with "*", the origin is echoed
+    start(41_322, Cors::new().origins(&["*"]).credentials(true));
+    let response = put(41_322, Some("https://any.example"));
+    assert!(response.contains("\r\nAccess-Control-Allow-Origin: https://any.example\r\n"), "{response}");
+
+    start(41_323, Cors::new().origins(&["https://app.example"]).reject_unlisted(403));
+    let response = put(41_323, Some("https://evil.example"));
+    assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{response}");
+    assert!(!response.contains("saved"), "{response}");
+}
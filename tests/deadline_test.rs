@@ -0,0 +1,158 @@
+mod common;
+
+use std::{ sync::{ Arc, atomic::{ AtomicBool, Ordering } }, time::{ Duration, Instant } };
+use glote::{ Glote, Req, Request, RequestExt, ResponseExt, Router, TestClient };
+
+fn request() -> Request {
+    Request::new(&["GET / HTTP/1.1".to_string()]).unwrap()
+}
+
+#[test]
+fn test_deadline_starts_unset() {
+    let req = request();
+    assert!(req.deadline().is_none());
+    assert!(req.remaining_time().is_none());
+    assert!(!req.is_expired());
+}
+
+#[test]
+fn test_deadline_only_tightens() {
+    let mut req = request();
+    let now = Instant::now();
+
+    req.tighten_deadline(now + Duration::from_secs(10));
+    assert_eq!(req.deadline(), Some(now + Duration::from_secs(10)));
+
+    req.tighten_deadline(now + Duration::from_secs(2));
+    assert_eq!(req.deadline(), Some(now + Duration::from_secs(2)));
+
+    // A looser layer must not extend it again
+    req.tighten_deadline(now + Duration::from_secs(30));
+    assert_eq!(req.deadline(), Some(now + Duration::from_secs(2)));
+
+    let remaining = req.remaining_time().unwrap();
+    assert!(remaining <= Duration::from_secs(2));
+    assert!(remaining > Duration::from_secs(1));
+}
+
+#[test]
+fn test_remaining_time_saturates_when_expired() {
+    let mut req = request();
+    req.tighten_deadline(Instant::now() - Duration::from_millis(5));
+
+    assert!(req.is_expired());
+    assert_eq!(req.remaining_time(), Some(Duration::ZERO));
+}
+
+#[test]
+fn test_expired_deadline_responds_504() {
+    let server = Glote::new();
+    let handler_ran = Arc::new(AtomicBool::new(false));
+
+    let ran = handler_ran.clone();
+    server.block_on(async {
+        server.use_middleware(|req, _res, next| async move {
+            req.write().await.tighten_timeout(Duration::from_secs(60));
+            req.write().await.tighten_timeout(Duration::ZERO);
+            next().await;
+        }).await;
+
+        server.get("/slow", move |req, res| {
+            let ran = ran.clone();
+            async move {
+                ran.store(true, Ordering::SeqCst);
+                let left = req.remaining_time().await;
+                res.send(&format!("{left:?}")).await;
+            }
+        }).await;
+    });
+
+    common::spawn_server(server, 40_820);
+    let response = common::raw_request(40_820, b"GET /slow HTTP/1.1\r\nHost: x\r\n\r\n");
+
+    assert!(response.starts_with("HTTP/1.1 504 Gateway Timeout\r\n"), "{response}");
+    assert!(!handler_ran.load(Ordering::SeqCst));
+}
+
+// Remaining time as the handler sees it, in whole seconds rounded up
+async fn left(req: &Req) -> String {
+    let left = req.remaining_time().await.unwrap();
+    format!("{}", left.as_secs() + u64::from(left.subsec_nanos() > 0))
+}
+
+#[test]
+fn test_tightest_layer_sets_the_deadline() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.read_body_timeout(Duration::from_secs(40)).await;
+        server.request_timeout(Duration::from_secs(30)).await;
+
+        let mut reports = Router::new("/reports");
+        reports.timeout(Duration::from_secs(20));
+        reports.get("/plain", |req, res| async move {
+            res.send(&left(&req).await).await;
+        });
+        reports.get_with_middleware("/rushed", vec![|req, _res, next| async move {
+            req.write().await.tighten_timeout(Duration::from_secs(5));
+            next().await;
+        }], |req, res| async move {
+            res.send(&left(&req).await).await;
+        });
+        server.mount(reports).await;
+
+        server.get("/server", |req, res| async move {
+            res.send(&left(&req).await).await;
+        }).await;
+        server.get("/route", |req, res| async move {
+            res.send(&left(&req).await).await;
+        }).await;
+        assert!(server.route_timeout("GET", "/route", Duration::from_secs(10)).await);
+        assert!(!server.route_timeout("GET", "/nothing", Duration::from_secs(10)).await);
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        // Server timeout under the connection's read deadline
+        assert_eq!(client.get("/server").await.text(), "30");
+        // Route timeouts under the server's
+        assert_eq!(client.get("/reports/plain").await.text(), "20");
+        assert_eq!(client.get("/route").await.text(), "10");
+        // Middleware under all of them
+        assert_eq!(client.get("/reports/rushed").await.text(), "5");
+    });
+
+    // Without a request timeout the connection's read deadline is the cap
+    let server = Glote::new();
+    server.block_on(async {
+        server.read_body_timeout(Duration::from_secs(40)).await;
+        server.get("/", |req, res| async move {
+            res.send(&left(&req).await).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        assert_eq!(client.get("/").await.text(), "40");
+    });
+}
+
+#[test]
+fn test_route_timeout_answers_504() {
+    let server = Glote::new();
+    server.block_on(async {
+        let mut slow = Router::new("/");
+        slow.timeout(Duration::from_millis(50));
+        slow.get("/slow", |_req, res| async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            res.send("late").await;
+        });
+        server.mount(slow).await;
+        server.get("/fast", |_req, res| async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            res.send("on time").await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        assert_eq!(client.get("/slow").await.status, 504);
+        assert_eq!(client.get("/fast").await.text(), "on time");
+    });
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "compression")]
+
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+const WEBHOOK_GZ: &[u8] = include_bytes!("fixtures/webhook.json.gz");
+const WEBHOOK_JSON: &str = r#"{"event":"invoice.paid","id":"evt_1479","amount":4200}"#;
+
+fn start(port: u16, limit: usize) {
+    let server = Glote::new();
+    server.block_on(async {
+        server.decompress_requests(limit).await;
+        server.post("/hook", |req, res| async move {
+            let encoding = req.read().await.headers.get("content-encoding").cloned();
+            let body = req.body().await.unwrap_or_default();
+            res.send(&format!("{encoding:?}|{body}")).await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+fn post(port: u16, encoding: &str, body: &[u8]) -> String {
+    let mut raw = format!(
+        "POST /hook HTTP/1.1\r\nHost: x\r\nContent-Encoding: {encoding}\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    ).into_bytes();
+    raw.extend_from_slice(body);
+    common::raw_request(port, &raw)
+}
+
+#[test]
+fn test_gzip_body_is_decompressed() {
+    start(40_850, 1024);
+    let response = post(40_850, "gzip", WEBHOOK_GZ);
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with(&format!("None|{WEBHOOK_JSON}")), "{response}");
+}
+
+#[test]
+fn test_decompressed_size_limit() {
+    start(40_851, 16);
+    let response = post(40_851, "gzip", WEBHOOK_GZ);
+
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{response}");
+}
+
+#[test]
+fn test_unsupported_encoding() {
+    start(40_852, 1024);
+    let response = post(40_852, "br", b"whatever");
+
+    assert!(response.starts_with("HTTP/1.1 415 Unsupported Media Type\r\n"), "{response}");
+}
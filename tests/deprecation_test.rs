@@ -0,0 +1,56 @@
+use std::time::{ Duration, UNIX_EPOCH };
+
+use glote::{ Deprecation, Glote, MatchedRoute, RequestExt, ResponseExt, Router, TestClient };
+
+#[test]
+fn test_deprecated_group_sends_notice_headers() {
+    let server = Glote::new();
+    server.block_on(async {
+        let mut v1 = Router::new("/v1");
+        v1.deprecated(
+            Deprecation::new(UNIX_EPOCH + Duration::from_secs(1_688_169_599))
+                .sunset(UNIX_EPOCH + Duration::from_secs(1_735_689_600))
+                .link("https://example.com/migrate")
+        );
+        v1.get("/users/:id", |req, res| async move {
+            let matched = req.ext::<MatchedRoute>().await.unwrap();
+            let sunset = matched.deprecation.as_ref().and_then(|deprecation| deprecation.sunset);
+            res.send(&format!("{} sunset={}", matched.pattern, sunset.is_some())).await;
+        });
+        v1.get("/gone", |req, res| async move {
+            res.send_error(410, &req).await;
+        });
+        server.mount(v1).await;
+
+        server.get("/v2/users/:id", |req, res| async move {
+            let matched = req.ext::<MatchedRoute>().await.unwrap();
+            res.send(&format!("{} deprecated={}", matched.pattern, matched.deprecation.is_some())).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.get("/v1/users/7").await;
+        assert_eq!(response.text(), "/v1/users/:id sunset=true");
+        let notice: Vec<_> = response.headers
+            .iter()
+            .filter(|(name, _)| ["Deprecation", "Sunset", "Link"].contains(&name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(notice, [
+            ("Deprecation", "@1688169599"),
+            ("Sunset", "Wed, 01 Jan 2025 00:00:00 GMT"),
+            ("Link", "<https://example.com/migrate>; rel=\"deprecation\""),
+        ]);
+
+        // Errors from the route carry the notice too
+        let gone = client.get("/v1/gone").await;
+        assert_eq!(gone.status, 410);
+        assert_eq!(gone.header("Deprecation"), Some("@1688169599"));
+
+        let current = client.get("/v2/users/7").await;
+        assert_eq!(current.text(), "/v2/users/:id deprecated=false");
+        for name in ["Deprecation", "Sunset", "Link"] {
+            assert_eq!(current.header(name), None, "{name}");
+        }
+    });
+}
@@ -0,0 +1,105 @@
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+
+use glote::{ Error, Glote, RequestLog, TestClient };
+
+fn panicking_server() -> Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/orders/:id", |_req, _res| async move {
+            panic!("order <7> has no customer");
+        }).await;
+    });
+    server
+}
+
+// The hook runs right after the response is written, wait for it
+async fn last_log(logs: &Mutex<Vec<RequestLog>>) -> RequestLog {
+    for _ in 0..100 {
+        if let Some(log) = logs.lock().unwrap().pop() {
+            return log;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("request was never logged");
+}
+
+#[test]
+fn test_dev_page_shows_the_panic() {
+    let server = panicking_server();
+    server.block_on(server.dev_errors(true)).unwrap();
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.get("/orders/7").await;
+        assert_eq!(response.status, 500);
+        let page = response.text();
+        assert!(page.contains("order &lt;7&gt; has no customer"), "{page}");
+        assert!(page.contains("/orders/:id"), "{page}");
+        assert!(page.contains("GET"), "{page}");
+        // Down to the handler that panicked
+        assert!(page.contains("<h2>Backtrace</h2>"), "{page}");
+        assert!(page.contains("dev_errors_test"), "{page}");
+
+        let response = client.request("GET", "/orders/7", &[("Accept", "application/json")], b"").await;
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+        let body: serde_json::Value = response.json().unwrap();
+        assert_eq!(body["panic"], "order <7> has no customer");
+        assert_eq!(body["route"], "/orders/:id");
+        assert_eq!(body["path"], "/orders/7");
+        assert!(body["backtrace"].as_str().is_some_and(|backtrace| backtrace.contains("dev_errors_test")));
+
+        // Errors without a panic get the page too
+        let response = client.get("/missing").await;
+        assert_eq!(response.status, 404);
+        assert!(response.text().contains("/missing"));
+    });
+}
+
+#[test]
+fn test_default_page_hides_the_panic() {
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let server = panicking_server();
+    let seen = Arc::clone(&logs);
+    server.block_on(server.on_request(move |log: RequestLog| seen.lock().unwrap().push(log)));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.get("/orders/7").await;
+        assert_eq!(response.status, 500);
+        assert!(!response.text().contains("no customer"));
+
+        // The details go to the log under the id the client got
+        let id = response.header("X-Request-Id").unwrap().to_string();
+        let log = last_log(&logs).await;
+        assert_eq!(log.request_id, id);
+        assert_eq!(log.panic.as_deref(), Some("order <7> has no customer"));
+
+        // Turned off again
+        server.dev_errors(true).await.unwrap();
+        server.dev_errors(false).await.unwrap();
+        assert!(!client.get("/orders/7").await.text().contains("no customer"));
+        last_log(&logs).await;
+
+        // A client's own id is kept
+        let response = client.request("GET", "/orders/7", &[("X-Request-Id", "trace-42")], b"").await;
+        assert_eq!(response.header("X-Request-Id"), Some("trace-42"));
+        assert_eq!(last_log(&logs).await.request_id, "trace-42");
+    });
+}
+
+#[test]
+fn test_production_refuses_dev_errors() {
+    let server = panicking_server();
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        server.dev_errors(true).await.unwrap();
+        server.production(true).await;
+        assert_eq!(server.dev_errors(true).await, Err(Error::DevErrorsInProduction));
+
+        // Turning production on switched the dev pages off
+        let response = client.get("/orders/7").await;
+        assert_eq!(response.status, 500);
+        assert!(!response.text().contains("no customer"));
+
+        server.dev_errors(false).await.unwrap();
+    });
+}
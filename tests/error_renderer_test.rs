@@ -0,0 +1,88 @@
+mod common;
+
+use glote::{ ErrorRenderer, Glote, Request, ResponseExt, TestClient };
+
+// Picks the message language from Accept-Language and always answers JSON
+struct LocalizedJson;
+
+impl ErrorRenderer for LocalizedJson {
+    fn render(&self, status: u16, req: &Request) -> (String, Vec<u8>) {
+        let lang = req.headers.get("accept-language").cloned().unwrap_or_default();
+        let message = match (status, lang.starts_with("de")) {
+            (404, true) => "Nicht gefunden",
+            (404, false) => "Not found",
+            _ => "Error",
+        };
+        let body = serde_json::json!({ "status": status, "error": message });
+        ("application/json".into(), body.to_string().into_bytes())
+    }
+}
+
+// Tells what it learnt about the request, to check what refused requests hand over
+struct EchoJson;
+
+impl ErrorRenderer for EchoJson {
+    fn render(&self, status: u16, req: &Request) -> (String, Vec<u8>) {
+        let body = serde_json::json!({ "status": status, "method": req.method, "path": req.path });
+        ("application/json".into(), body.to_string().into_bytes())
+    }
+}
+
+#[test]
+fn test_custom_renderer_handles_404() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.set_error_renderer(LocalizedJson).await;
+        server.get("/", |_req, res| async move {
+            res.send("home").await;
+        }).await;
+    });
+    common::spawn_server(server, 40_910);
+
+    let response = common::raw_request(
+        40_910,
+        b"GET /missing HTTP/1.1\r\nHost: x\r\nAccept-Language: de-DE,de;q=0.9\r\n\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{response}");
+    assert!(response.contains("Content-Type: application/json\r\n"), "{response}");
+    assert!(response.ends_with(r#"{"error":"Nicht gefunden","status":404}"#), "{response}");
+
+    let response = common::raw_request(40_910, b"GET /missing HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with(r#"{"error":"Not found","status":404}"#), "{response}");
+}
+
+#[test]
+fn test_default_renderer_keeps_plain_404() {
+    let server = Glote::new();
+    common::spawn_server(server, 40_911);
+
+    let response = common::raw_request(40_911, b"GET /missing HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\n404 Not Found"), "{response}");
+}
+
+#[test]
+fn test_renderer_answers_refused_requests() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.set_error_renderer(EchoJson).await;
+        server.max_body_size(4).await;
+        server.max_header_count(3).await;
+        server.post("/upload", |_req, res| async move { res.send("stored").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.post("/upload?name=a", b"far too large").await;
+        assert_eq!(response.status, 413);
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+        assert_eq!(response.header("Connection"), Some("close"));
+        assert_eq!(response.text(), r#"{"method":"POST","path":"/upload","status":413}"#);
+
+        let headers = [("X-A", "1"), ("X-B", "2"), ("X-C", "3"), ("X-D", "4")];
+        let response = client.request("GET", "/upload", &headers, b"").await;
+        assert_eq!(response.status, 431);
+        assert_eq!(response.header("Content-Type"), Some("application/json"));
+        assert_eq!(response.header("Connection"), Some("close"));
+        assert_eq!(response.text(), r#"{"method":"GET","path":"/upload","status":431}"#);
+    });
+}
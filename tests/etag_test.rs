@@ -0,0 +1,79 @@
+mod common;
+
+use std::io::{ Read, Write };
+
+use glote::{ Glote, ResponseExt, TestClient };
+
+fn etag_server() -> std::sync::Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/report", |req, res| async move {
+            res.send_with_etag(&req, b"quarterly numbers", "text/plain; charset=UTF-8").await;
+        }).await;
+        server.post("/report", |req, res| async move {
+            res.send_with_etag(&req, b"quarterly numbers", "text/plain; charset=UTF-8").await;
+        }).await;
+        server.get("/user", |req, res| async move {
+            res.json_with_etag(&req, &serde_json::json!({ "name": "ada" })).await;
+        }).await;
+    });
+    server
+}
+
+#[test]
+fn test_second_identical_get_is_304() {
+    let server = etag_server();
+    common::spawn_server(server, 41_540);
+
+    let first = common::raw_request(41_540, b"GET /report HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(first.starts_with("HTTP/1.1 200 OK\r\n"), "{first}");
+    assert!(first.ends_with("\r\n\r\nquarterly numbers"), "{first}");
+    let etag = first
+        .lines()
+        .find_map(|line| line.strip_prefix("ETag: "))
+        .expect("no ETag")
+        .to_string();
+    assert!(etag.starts_with('"'), "{etag}");
+
+    let mut stream = common::connect(41_540);
+    let again = format!("GET /report HTTP/1.1\r\nHost: x\r\nIf-None-Match: {etag}\r\nConnection: close\r\n\r\n");
+    stream.write_all(again.as_bytes()).unwrap();
+    let mut second = String::new();
+    stream.read_to_string(&mut second).unwrap();
+    assert!(second.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{second}");
+    assert!(second.contains(&format!("\r\nETag: {etag}\r\n")), "{second}");
+    // Nothing after the head
+    assert!(second.ends_with("\r\n\r\n"), "{second}");
+}
+
+#[test]
+fn test_if_none_match_forms() {
+    let server = etag_server();
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let first = client.get("/user").await;
+        assert_eq!(first.text(), r#"{"name":"ada"}"#);
+        let etag = first.header("ETag").unwrap().to_string();
+
+        let client = &client;
+        let revalidate = |if_none_match: String| async move {
+            client.request("GET", "/user", &[("If-None-Match", &if_none_match)], b"").await.status
+        };
+        assert_eq!(revalidate(etag.clone()).await, 304);
+        // Weak comparison
+        assert_eq!(revalidate(format!("W/{etag}")).await, 304);
+        assert_eq!(revalidate(format!("\"other\", {etag}")).await, 304);
+        assert_eq!(revalidate("*".to_string()).await, 304);
+        assert_eq!(revalidate("\"other\"".to_string()).await, 200);
+
+        // Same body, same tag
+        assert_eq!(client.get("/user").await.header("ETag"), Some(etag.as_str()));
+
+        // Only reads are answered with 304
+        let report = client.get("/report").await;
+        let etag = report.header("ETag").unwrap();
+        let response = client.request("POST", "/report", &[("If-None-Match", etag)], b"").await;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "quarterly numbers");
+    });
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::io::{ Read, Write };
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+const PORT: u16 = 41_420;
+
+#[test]
+fn test_expect_continue() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.max_body_size(64).await;
+        server.post("/upload", |req, res| async move {
+            res.send(&format!("got {}", req.body().await.unwrap_or_default())).await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+
+    // The interim response comes while the body is still held back
+    let mut stream = common::connect(PORT);
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: x\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n")
+        .unwrap();
+    let mut interim = [0u8; 25];
+    stream.read_exact(&mut interim).unwrap();
+    assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+    stream.write_all(b"hello").unwrap();
+    let response = common::read_response(&mut stream);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("got hello"), "{response}");
+
+    // Too large: the final status comes right away and the body is never asked for
+    let response = common::raw_request(
+        PORT,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nExpect: 100-continue\r\nContent-Length: 1000\r\n\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{response}");
+
+    let response = common::raw_request(
+        PORT,
+        b"POST /upload HTTP/1.1\r\nHost: x\r\nExpect: something-else\r\nContent-Length: 5\r\n\r\n"
+    );
+    assert!(response.starts_with("HTTP/1.1 417 Expectation Failed\r\n"), "{response}");
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+struct User {
+    id: u32,
+    name: String,
+}
+
+struct Trace(&'static str);
+
+#[test]
+fn test_middleware_passes_values_to_handler() {
+    let server = Glote::new();
+    server.block_on(async {
+        // Auth middleware resolves the user from a header
+        server.use_middleware(|req, _res, next| async move {
+            let token = req.read().await.headers.get("authorization").cloned();
+            if token.as_deref() == Some("Bearer secret") {
+                req.set_ext(User { id: 7, name: "ana".into() }).await;
+            }
+            req.write().await.extensions.insert(Trace("mw"));
+            next().await;
+        }).await;
+
+        server.get("/me/:tab", |req, res| async move {
+            let tab = req.params("tab").await.unwrap_or_default();
+            let trace = req.read().await.extensions.get::<Trace>().map(|t| t.0).unwrap_or_default();
+            let body = match req.ext::<User>().await {
+                Some(user) => format!("{} {} {tab} {trace}", user.id, user.name),
+                None => format!("anonymous {tab} {trace}"),
+            };
+            res.send(&body).await;
+        }).await;
+    });
+    common::spawn_server(server, 41_270);
+
+    let response = common::raw_request(
+        41_270,
+        b"GET /me/profile HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer secret\r\n\r\n"
+    );
+    assert!(response.ends_with("\r\n\r\n7 ana profile mw"), "{response}");
+
+    let response = common::raw_request(41_270, b"GET /me/profile HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nanonymous profile mw"), "{response}");
+}
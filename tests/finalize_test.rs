@@ -0,0 +1,119 @@
+mod common;
+
+use glote::{ Error, Glote, ResponseExt };
+
+#[test]
+fn test_finalize_accepts_distinct_routes() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/users", |_req, res| async move { res.send("list").await }).await;
+        server.get("/users/:id", |_req, res| async move { res.send("one").await }).await;
+        server.delete("/users/:id", |_req, res| async move { res.send("gone").await }).await;
+
+        assert_eq!(server.finalize().await, Ok(()));
+    });
+}
+
+#[test]
+fn test_finalize_reports_duplicate_route() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/users/:id", |_req, res| async move { res.send("a").await }).await;
+        server.get("/users/:uid/", |_req, res| async move { res.send("b").await }).await;
+
+        let err = server.finalize().await.unwrap_err();
+        assert_eq!(err, Error::DuplicateRoute {
+            method: "GET".into(),
+            pattern: "/users/:uid/".into(),
+            existing: "/users/:id".into(),
+        });
+        assert_eq!(
+            err.to_string(),
+            "duplicate route GET '/users/:uid/' conflicts with '/users/:id'"
+        );
+    });
+}
+
+#[test]
+fn test_finalize_reports_invalid_patterns() {
+    let cases = [
+        ("users", "must start with '/'"),
+        ("/users/:", "parameter without a name"),
+        ("/a/:id/b/:id", "parameter ':id' appears more than once"),
+        ("/files/*rest/edit", "wildcard '*rest' must be the last segment"),
+        ("/files/*", "wildcard without a name"),
+    ];
+
+    for (pattern, reason) in cases {
+        let server = Glote::new();
+        server.block_on(async {
+            server.post(pattern, |_req, res| async move { res.send("x").await }).await;
+
+            assert_eq!(
+                server.finalize().await,
+                Err(Error::InvalidPattern { pattern: pattern.into(), reason: reason.into() })
+            );
+        });
+    }
+}
+
+#[test]
+fn test_listen_refuses_invalid_table() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/a", |_req, res| async move { res.send("1").await }).await;
+        server.get("/a", |_req, res| async move { res.send("2").await }).await;
+
+        let err = server.clone().listen(("127.0.0.1", 40_930)).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        // Refused before the port was taken
+        assert_eq!(server.local_addr().await, None);
+    });
+}
+
+#[test]
+fn test_middleware_added_after_listen_is_applied() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |_req, res| async move { res.send("handler").await }).await;
+    });
+    common::spawn_server(server.clone(), 40_931);
+
+    let before = common::raw_request(40_931, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(before.ends_with("handler"), "{before}");
+
+    // Registration after finalize triggers a rebuild on the next connection
+    server.block_on(async {
+        server.use_middleware(|_req, res, _next| async move {
+            res.send("middleware").await;
+        }).await;
+    });
+
+    let after = common::raw_request(40_931, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(after.ends_with("middleware"), "{after}");
+}
+
+#[test]
+fn test_bad_route_after_listen_is_rejected_alone() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/a", |_req, res| async move { res.send("first").await }).await;
+    });
+    common::spawn_server(server.clone(), 40_932);
+    let first = common::raw_request(40_932, b"GET /a HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(first.ends_with("first"), "{first}");
+
+    server.block_on(async {
+        // Duplicate and invalid, neither is kept
+        server.get("/a/", |_req, res| async move { res.send("second").await }).await;
+        server.get("b", |_req, res| async move { res.send("b").await }).await;
+        // Still registered and served after them
+        server.get("/c", |_req, res| async move { res.send("c").await }).await;
+        assert_eq!(server.finalize().await, Ok(()));
+    });
+
+    let a = common::raw_request(40_932, b"GET /a HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(a.ends_with("first"), "{a}");
+    let c = common::raw_request(40_932, b"GET /c HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(c.ends_with("c"), "{c}");
+}
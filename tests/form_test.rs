@@ -0,0 +1,33 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+#[test]
+fn test_urlencoded_form_is_decoded() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.post("/signup", |req, res| async move {
+            let name = req.form("name").await.unwrap_or_default();
+            let tags = req.form("tags").await.unwrap_or_default();
+            let fields = req.read().await.form().map(|form| form.len());
+            res.send(&format!("{name}|{tags}|{fields:?}")).await;
+        }).await;
+    });
+    common::spawn_server(server, 41_210);
+
+    let body = "name=J%C3%BCrgen&tags=a+b";
+    let request = format!(
+        "POST /signup HTTP/1.1\r\nHost: x\r\nContent-Type: application/x-www-form-urlencoded; charset=UTF-8\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let response = common::raw_request(41_210, request.as_bytes());
+    assert!(response.ends_with("\r\n\r\nJürgen|a b|Some(2)"), "{response}");
+
+    // Other content types aren't treated as forms
+    let request = format!(
+        "POST /signup HTTP/1.1\r\nHost: x\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let response = common::raw_request(41_210, request.as_bytes());
+    assert!(response.ends_with("\r\n\r\n||None"), "{response}");
+}
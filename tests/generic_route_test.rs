@@ -0,0 +1,51 @@
+use glote::{ Glote, Next, Req, Res, RequestExt, ResponseExt, TestClient, mid };
+
+#[test]
+fn test_route_and_all_registration() {
+    let server = Glote::new();
+    server.block_on(async {
+        // Method strings coming from a config table, any case
+        for (method, path) in [("get", "/config/a"), ("Post", "/config/b"), ("PURGE", "/cache")] {
+            server.route(method, path, |req, res| async move {
+                let method = req.read().await.method.clone();
+                res.send(&format!("{method} ok")).await;
+            }).await;
+        }
+
+        server.all("/any", |req, res| async move {
+            let method = req.read().await.method.clone();
+            res.send(&format!("any {method}")).await;
+        }).await;
+
+        server.all_with_middleware(
+            "/guarded",
+            vec![
+                mid!(req, res, next, {
+                    req.write().await.path_params.insert("seen".into(), "yes".into());
+                    next().await;
+                })
+            ],
+            |req, res| async move {
+                let seen = req.params("seen").await.unwrap_or_default();
+                res.send(&format!("seen={seen}")).await;
+            }
+        ).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let send = |method: &'static str, path: &'static str| client.request(method, path, &[], b"");
+
+        assert_eq!(send("GET", "/config/a").await.text(), "GET ok");
+        assert_eq!(send("POST", "/config/b").await.text(), "POST ok");
+        assert_eq!(send("PURGE", "/cache").await.text(), "PURGE ok");
+
+        // Registered for POST only
+        assert_eq!(send("GET", "/config/b").await.status, 405);
+
+        for method in ["GET", "DELETE", "PATCH"] {
+            assert_eq!(send(method, "/any").await.text(), format!("any {method}"));
+        }
+
+        assert_eq!(send("PUT", "/guarded").await.text(), "seen=yes");
+    });
+}
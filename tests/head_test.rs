@@ -0,0 +1,70 @@
+mod common;
+
+use std::io::{ Read, Write };
+
+use glote::{ Glote, ResponseExt };
+
+const PORT: u16 = 41_410;
+
+fn header<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+}
+
+#[test]
+fn test_head_uses_get_routes_without_a_body() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/hello", |_req, res| async move {
+            res.read().await.set_header("X-Route", "get").await.unwrap();
+            res.send("hello world").await;
+        }).await;
+        server.get("/both", |_req, res| async move {
+            res.send("from get").await;
+        }).await;
+        server.head("/both", |_req, res| async move {
+            res.read().await.set_header("X-Route", "head").await.unwrap();
+            res.send("").await;
+        }).await;
+        server.get("/stream", |_req, res| async move {
+            let _ = res.begin(200, &[]).await;
+            let _ = res.write_chunk(b"streamed").await;
+            let _ = res.end().await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+
+    let get = common::raw_request(PORT, b"GET /hello HTTP/1.1\r\nHost: x\r\n\r\n");
+    let head = common::raw_request(PORT, b"HEAD /hello HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+    assert_eq!(header(&head, "Content-Length"), Some("11"));
+    assert_eq!(header(&head, "Content-Length"), header(&get, "Content-Length"));
+    assert_eq!(header(&head, "X-Route"), Some("get"));
+    assert!(head.ends_with("\r\n\r\n"), "{head}");
+
+    // An explicit HEAD route wins over the GET fallback
+    let head = common::raw_request(PORT, b"HEAD /both HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n");
+    assert_eq!(header(&head, "X-Route"), Some("head"));
+
+    // Streams and generated errors keep their headers and lose the body too, so the
+    // next response on the connection starts right after each head
+    let mut stream = common::connect(PORT);
+    stream
+        .write_all(
+            b"HEAD /stream HTTP/1.1\r\nHost: x\r\n\r\n\
+              HEAD /missing HTTP/1.1\r\nHost: x\r\n\r\n\
+              GET /hello HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+    let mut all = String::new();
+    stream.read_to_string(&mut all).unwrap();
+    let heads: Vec<&str> = all.split("\r\n\r\n").collect();
+    assert_eq!(heads.len(), 4, "{all}");
+    assert!(heads[0].contains("Transfer-Encoding: chunked"), "{all}");
+    assert!(heads[1].starts_with("HTTP/1.1 404 Not Found\r\n"), "{all}");
+    assert!(heads[1].contains("Content-Length: "), "{all}");
+    assert!(heads[2].starts_with("HTTP/1.1 200 OK\r\n"), "{all}");
+    assert_eq!(heads[3], "hello world");
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use std::{ io::Write, time::{ Duration, Instant } };
+
+use glote::{ Glote, ResponseExt, TestClient };
+
+#[test]
+fn test_header_flood_gets_431() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |_req, res| async move { res.send("ok").await }).await;
+    });
+    common::spawn_server(server, 41_470);
+
+    let started = Instant::now();
+    let mut stream = common::connect(41_470);
+    let mut raw = b"GET / HTTP/1.1\r\nHost: x\r\n".to_vec();
+    for _ in 0..10_000 {
+        raw.extend_from_slice(b"X-A: b\r\n");
+    }
+    raw.extend_from_slice(b"\r\n");
+    stream.write_all(&raw).unwrap();
+    let response = common::read_response(&mut stream);
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"), "{response}");
+    assert!(started.elapsed() < Duration::from_secs(3));
+
+    // A single endless line is cut off too
+    let long = format!("GET / HTTP/1.1\r\nHost: x\r\nX-Long: {}\r\n\r\n", "a".repeat(20_000));
+    let response = common::raw_request(41_470, long.as_bytes());
+    assert!(response.starts_with("HTTP/1.1 431 "), "{response}");
+
+    let response = common::raw_request(41_470, b"GET / HTTP/1.1\r\nHost: x\r\nX-A: b\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nok"), "{response}");
+}
+
+#[test]
+fn test_configured_header_limits() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.max_header_count(3).await;
+        server.max_header_size(120).await;
+        server.max_header_line_length(64).await;
+        server.get("/", |_req, res| async move { res.send("ok").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        // Host plus two more is right at the limit
+        let response = client.request("GET", "/", &[("X-A", "1"), ("X-B", "2")], b"").await;
+        assert_eq!(response.text(), "ok");
+
+        let response = client.request("GET", "/", &[("X-A", "1"), ("X-B", "2"), ("X-C", "3")], b"").await;
+        assert_eq!(response.status, 431);
+
+        let value = "v".repeat(60);
+        let response = client.request("GET", "/", &[("X-Long", &value)], b"").await;
+        assert_eq!(response.status, 431);
+
+        // Each line fits, all of them together don't
+        let value = "v".repeat(40);
+        let response = client.request("GET", "/", &[("X-A", &value), ("X-B", &value)], b"").await;
+        assert_eq!(response.text(), "ok");
+        let value = "v".repeat(50);
+        let response = client.request("GET", "/", &[("X-A", &value), ("X-B", &value)], b"").await;
+        assert_eq!(response.status, 431);
+    });
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+const PORT: u16 = 41_400;
+
+fn start() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.post("/echo", |req, res| async move {
+            res.send(&req.body().await.unwrap_or_default()).await;
+        }).await;
+        server.get("/tags", |req, res| async move {
+            let joined = req.header("X-TAG").await.unwrap_or_default();
+            let all = req.header_all("x-tag").await.join("|");
+            let session = req.cookie("session").await.unwrap_or_default();
+            let theme = req.cookie("theme").await.unwrap_or_default();
+            res.send(&format!("{joined} / {all} / {session} {theme}")).await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+}
+
+#[test]
+fn test_header_variants() {
+    start();
+
+    // No space after the colon is legal
+    let response = common::raw_request(
+        PORT,
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length:5\r\n\r\nhello"
+    );
+    assert!(response.ends_with("\r\n\r\nhello"), "{response}");
+
+    let response = common::raw_request(
+        PORT,
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nCONTENT-LENGTH: 5\r\n\r\nworld"
+    );
+    assert!(response.ends_with("\r\n\r\nworld"), "{response}");
+
+    // Repeated headers in mixed case, Cookie lines joined the way cookies are
+    let response = common::raw_request(
+        PORT,
+        b"GET /tags HTTP/1.1\r\nHost: x\r\nX-Tag: a\r\nCookie: session=s1\r\nx-tag:b, c \r\nCookie: theme=dark\r\n\r\n"
+    );
+    assert!(response.ends_with("\r\n\r\na, b, c / a|b, c / s1 dark"), "{response}");
+}
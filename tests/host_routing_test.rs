@@ -0,0 +1,65 @@
+use glote::{ Glote, ResponseExt, Router, TestClient };
+
+#[test]
+fn test_same_path_on_two_hosts() {
+    let server = Glote::new();
+    server.block_on(async {
+        let mut api = Router::new("/");
+        api.host("api.example.com");
+        api.get("/", |_req, res| async move { res.send("api").await });
+        api.get("/status", |_req, res| async move { res.send("api status").await });
+        server.mount(api).await;
+
+        let mut www = Router::new("/");
+        www.host("www.example.com");
+        www.get("/", |_req, res| async move { res.send("www").await });
+        server.mount(www).await;
+
+        let mut tenants = Router::new("/");
+        tenants.host("*.example.com");
+        tenants.get("/", |_req, res| async move { res.send("tenant").await });
+        server.mount(tenants).await;
+
+        // Any other host
+        server.get("/", |_req, res| async move { res.send("fallback").await }).await;
+        server.post("/status", |_req, res| async move { res.send("posted").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let on = |host: &'static str| [("Host", host)];
+        assert_eq!(client.request("GET", "/", &on("api.example.com"), b"").await.text(), "api");
+        // Case and port don't matter
+        assert_eq!(client.request("GET", "/", &on("WWW.Example.com:8080"), b"").await.text(), "www");
+        assert_eq!(client.request("GET", "/", &on("acme.example.com"), b"").await.text(), "tenant");
+        assert_eq!(client.request("GET", "/", &on("example.com"), b"").await.text(), "fallback");
+        assert_eq!(client.get("/").await.text(), "fallback");
+
+        // Host routes are only reachable on their host
+        assert_eq!(client.get("/status").await.status, 405);
+        let response = client.request("DELETE", "/status", &on("api.example.com"), b"").await;
+        assert_eq!(response.status, 405);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, POST, OPTIONS"));
+        assert_eq!(client.request("POST", "/status", &on("api.example.com"), b"").await.text(), "posted");
+    });
+}
+
+#[test]
+fn test_static_files_per_host() {
+    let root = std::env::temp_dir().join("glote_host_static_test");
+    for (dir, body) in [("docs", "docs index"), ("shared", "shared index")] {
+        std::fs::create_dir_all(root.join(dir)).unwrap();
+        std::fs::write(root.join(dir).join("index.txt"), body).unwrap();
+    }
+
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(root.join("shared").to_str().unwrap()).await;
+        server.static_path_for_host("docs.example.com", root.join("docs").to_str().unwrap()).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.request("GET", "/index.txt", &[("Host", "docs.example.com")], b"").await;
+        assert_eq!(response.text(), "docs index");
+        assert_eq!(client.get("/index.txt").await.text(), "shared index");
+    });
+}
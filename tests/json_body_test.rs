@@ -0,0 +1,78 @@
+mod common;
+
+use glote::{ Glote, JsonError, RequestExt, ResponseExt };
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Signup {
+    name: String,
+    bio: String,
+}
+
+fn start(port: u16) {
+    let server = Glote::new();
+    server.block_on(async {
+        server.post("/signup", |req, res| async move {
+            let body = match req.json::<Signup>().await {
+                Ok(signup) => format!("{}|{}", signup.name, signup.bio),
+                Err(JsonError::ContentType(_)) => {
+                    res.status(415).await;
+                    "json only".to_string()
+                }
+                Err(JsonError::MissingBody) => {
+                    res.status(400).await;
+                    "missing body".to_string()
+                }
+                Err(JsonError::Parse(e)) => {
+                    res.status(400).await;
+                    format!("bad json at line {}", e.line())
+                }
+            };
+            res.send(&body).await;
+        }).await;
+        server.post("/any", |req, res| async move {
+            let signup = req.json_unchecked::<Signup>().await;
+            res.send(&signup.map(|s| s.name).unwrap_or_default()).await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+fn post(path: &str, content_type: &str, body: &[u8]) -> String {
+    let mut request = format!(
+        "POST {path} HTTP/1.1\r\nHost: x\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    ).into_bytes();
+    request.extend_from_slice(body);
+    common::raw_request(41_220, &request)
+}
+
+#[test]
+fn test_json_body_is_deserialized() {
+    start(41_220);
+
+    // Embedded newlines and a BOM
+    let response = post(
+        "/signup",
+        "application/json; charset=utf-8",
+        "\u{feff}{\n  \"name\": \"Jürgen\",\n  \"bio\": \"line one\\nline two\"\n}".as_bytes()
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\nJürgen|line one\nline two"), "{response}");
+
+    let response = post("/signup", "application/vnd.api+json", br#"{"name":"a","bio":"b"}"#);
+    assert!(response.ends_with("\r\n\r\na|b"), "{response}");
+
+    let response = post("/signup", "text/plain", br#"{"name":"a","bio":"b"}"#);
+    assert!(response.starts_with("HTTP/1.1 415 "), "{response}");
+
+    let response = post("/signup", "application/json", b"");
+    assert!(response.ends_with("\r\n\r\nmissing body"), "{response}");
+
+    let response = post("/signup", "application/json", b"{\n\"name\": 1}");
+    assert!(response.ends_with("\r\n\r\nbad json at line 2"), "{response}");
+
+    // The escape hatch ignores the Content-Type
+    let response = post("/any", "text/plain", br#"{"name":"a","bio":"b"}"#);
+    assert!(response.ends_with("\r\n\r\na"), "{response}");
+}
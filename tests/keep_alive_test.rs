@@ -0,0 +1,103 @@
+mod common;
+
+use std::{ io::{ Read, Write }, time::{ Duration, Instant } };
+use glote::{ Glote, RequestExt, ResponseExt };
+
+fn server(port: u16) {
+    let server = Glote::new();
+    server.block_on(async {
+        server.keep_alive_timeout(Duration::from_millis(300)).await;
+        server.post("/echo", |req, res| async move {
+            let body = req.body().await.unwrap_or_default();
+            res.send(&body).await;
+        }).await;
+        server.get("/close", |_req, res| async move {
+            res.read().await.set_header("Connection", "close").await.unwrap();
+            res.send("bye").await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+fn closed(stream: &mut std::net::TcpStream) -> bool {
+    let mut byte = [0u8; 1];
+    matches!(stream.read(&mut byte), Ok(0))
+}
+
+#[test]
+fn test_sequential_requests_share_a_connection() {
+    server(41_140);
+    let mut stream = common::connect(41_140);
+
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\none").unwrap();
+    let first = common::read_response(&mut stream);
+    assert!(first.starts_with("HTTP/1.1 200 OK\r\n"), "{first}");
+    assert!(first.contains("\r\nConnection: keep-alive\r\n"), "{first}");
+    assert!(first.ends_with("one"), "{first}");
+
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\ntwo").unwrap();
+    let second = common::read_response(&mut stream);
+    assert!(second.ends_with("two"), "{second}");
+
+    // A 404 keeps the connection usable too
+    stream.write_all(b"GET /missing HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    let missing = common::read_response(&mut stream);
+    assert!(missing.starts_with("HTTP/1.1 404 "), "{missing}");
+
+    stream.write_all(b"GET /close HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    let last = common::read_response(&mut stream);
+    assert!(last.contains("\r\nConnection: close\r\n"), "{last}");
+    assert!(closed(&mut stream));
+}
+
+#[test]
+fn test_pipelined_requests_get_answered_in_order() {
+    server(41_141);
+    let mut stream = common::connect(41_141);
+
+    stream.write_all(
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 1\r\n\r\naPOST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 1\r\nConnection: close\r\n\r\nb"
+    ).unwrap();
+    let mut both = String::new();
+    stream.read_to_string(&mut both).unwrap();
+    let first = both.find("\r\n\r\na").expect(&both);
+    let second = both.find("\r\n\r\nb").expect(&both);
+    assert!(first < second, "{both}");
+    assert_eq!(both.matches("HTTP/1.1 200 OK").count(), 2, "{both}");
+}
+
+#[test]
+fn test_connection_close_and_http_1_0() {
+    server(41_142);
+
+    let mut close = common::connect(41_142);
+    close.write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nConnection: close\r\nContent-Length: 1\r\n\r\nx").unwrap();
+    let response = common::read_response(&mut close);
+    assert!(response.contains("\r\nConnection: close\r\n"), "{response}");
+    assert!(closed(&mut close));
+
+    let mut old = common::connect(41_142);
+    old.write_all(b"POST /echo HTTP/1.0\r\nContent-Length: 1\r\n\r\nx").unwrap();
+    let response = common::read_response(&mut old);
+    assert!(response.contains("\r\nConnection: close\r\n"), "{response}");
+    assert!(closed(&mut old));
+
+    let mut kept = common::connect(41_142);
+    kept.write_all(b"POST /echo HTTP/1.0\r\nConnection: keep-alive\r\nContent-Length: 1\r\n\r\nx").unwrap();
+    let response = common::read_response(&mut kept);
+    assert!(response.contains("\r\nConnection: keep-alive\r\n"), "{response}");
+    kept.write_all(b"POST /echo HTTP/1.0\r\nContent-Length: 1\r\n\r\ny").unwrap();
+    assert!(common::read_response(&mut kept).ends_with("y"));
+}
+
+#[test]
+fn test_idle_connections_are_closed() {
+    server(41_143);
+    let mut stream = common::connect(41_143);
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 1\r\n\r\nx").unwrap();
+    common::read_response(&mut stream);
+
+    let started = Instant::now();
+    assert!(closed(&mut stream));
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
@@ -1,5 +1,5 @@
-use std::{ sync::{ Arc }, thread, time::Duration };
-use glote::{ Glote, ResponseExt };
+use std::sync::{ Arc };
+use glote::Glote;
 
 #[test]
 fn test_server_instantiation() {
@@ -116,12 +116,6 @@ fn test_server_instantiation() {
 //     });
 // }
 
-// #[test]
-// fn test_set_workers() {
-//     let mut server = Glote::new();
-//     Arc::get_mut(&mut server).unwrap().set_warkers(8);
-// }
-
 // #[test]
 // fn _test_server_working() {
 //     let server = Glote::new();
@@ -0,0 +1,25 @@
+use glote::{ Glote, ResponseExt, TestClient };
+
+#[test]
+fn test_wrong_method_gets_405_with_allow() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/users/:id", |_req, res| async move { res.send("get").await }).await;
+        server.post("/users/:id", |_req, res| async move { res.send("post").await }).await;
+        server.put("/other", |_req, res| async move { res.send("put").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.request("DELETE", "/users/42", &[], b"").await;
+        assert_eq!(response.status, 405);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, POST, OPTIONS"));
+
+        // Registered methods still work
+        let response = client.request("POST", "/users/42", &[], b"").await;
+        assert_eq!(response.text(), "post");
+
+        // Unknown paths are still 404
+        let response = client.request("DELETE", "/nothing", &[], b"").await;
+        assert_eq!(response.status, 404);
+    });
+}
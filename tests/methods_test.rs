@@ -0,0 +1,38 @@
+use glote::{ Glote, RequestExt, ResponseExt, TestClient };
+
+#[test]
+fn test_patch_head_options_dispatch() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.patch("/users/:id", |req, res| async move {
+            let id = req.params("id").await.unwrap_or_default();
+            let body = req.body().await.unwrap_or_default();
+            res.send(&format!("patched {id}: {body}")).await;
+        }).await;
+        server.head("/users/:id", |_req, res| async move {
+            res.send("").await;
+        }).await;
+        server.options("/users/:id", |_req, res| async move {
+            res.send("options").await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let body = r#"{"name":"ada"}"#;
+        let response = client.request(
+            "PATCH",
+            "/users/7",
+            &[("Content-Type", "application/json")],
+            body.as_bytes()
+        ).await;
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), format!("patched 7: {body}"));
+
+        let response = client.request("HEAD", "/users/7", &[], b"").await;
+        assert_eq!(response.status, 200);
+        assert!(response.body.is_empty());
+
+        let response = client.request("OPTIONS", "/users/7", &[], b"").await;
+        assert_eq!(response.text(), "options");
+    });
+}
@@ -0,0 +1,63 @@
+use glote::{ Glote, ResponseExt, TestClient };
+
+#[test]
+fn test_custom_not_found_handler() {
+    let static_dir = std::env::temp_dir().join("glote_not_found_test");
+    std::fs::create_dir_all(&static_dir).unwrap();
+    std::fs::write(static_dir.join("hello.txt"), "static hello").unwrap();
+
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(static_dir.to_str().unwrap()).await;
+
+        server.use_middleware(|req, _res, next| async move {
+            req.write().await.path_params.insert("global".into(), "ran".into());
+            next().await;
+        }).await;
+
+        server.get("/", |_req, res| async move {
+            res.send("home").await;
+        }).await;
+
+        server.not_found(|req, res| async move {
+            let req = req.read().await;
+            res.json(
+                &serde_json::json!({
+                    "error": "not_found",
+                    "path": req.path,
+                    "q": req.query("q"),
+                    "agent": req.headers.get("user-agent"),
+                    "global": req.params("global"),
+                })
+            ).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let missing = client.request("GET", "/nope?q=1", &[("User-Agent", "probe")], b"").await;
+        assert_eq!(missing.status, 404);
+        assert_eq!(
+            missing.text(),
+            r#"{"agent":"probe","error":"not_found","global":"ran","path":"/nope","q":"1"}"#
+        );
+
+        // Static files are still looked up first
+        let file = client.get("/hello.txt").await;
+        assert_eq!(file.status, 200);
+        assert_eq!(file.text(), "static hello");
+    });
+}
+
+#[test]
+fn test_not_found_handler_can_change_status() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.not_found(|_req, res| async move {
+            res.status(410).await;
+            res.send("gone").await;
+        }).await;
+    });
+    let response = server.block_on(TestClient::new(server.clone()).get("/old"));
+    assert_eq!(response.status, 410);
+    assert_eq!(response.text(), "gone");
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use glote::{ Cors, CorsExt, Glote, ResponseExt, TestClient };
+
+#[test]
+fn test_automatic_options_lists_methods() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/users/:id", |_req, res| async move { res.send("get").await }).await;
+        server.put("/users/:id", |_req, res| async move { res.send("put").await }).await;
+        server.delete("/users/:id", |_req, res| async move { res.send("delete").await }).await;
+        server.get("/posts", |_req, res| async move { res.send("posts").await }).await;
+        server.options("/posts", |_req, res| async move { res.send("custom").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.request("OPTIONS", "/users/42", &[], b"").await;
+        assert_eq!(response.status, 204);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, PUT, DELETE, OPTIONS"));
+        assert_eq!(response.header("Content-Length"), None);
+        assert!(response.body.is_empty());
+
+        // Registered OPTIONS routes answer themselves
+        let response = client.request("OPTIONS", "/posts", &[], b"").await;
+        assert_eq!(response.text(), "custom");
+
+        let response = client.request("OPTIONS", "/nothing", &[], b"").await;
+        assert_eq!(response.status, 404);
+    });
+}
+
+#[test]
+fn test_automatic_options_runs_cors() {
+    let server = Glote::new();
+    server.block_on(async {
+        let cors = Cors::new().origins(&["https://app.example"]).methods(&["GET", "POST"]).build();
+        server.use_middleware(move |req, res, next| {
+            let cors = Arc::clone(&cors);
+            async move {
+                cors.run_middleware(req, res, next).await;
+            }
+        }).await;
+        server.post("/items", |_req, res| async move { res.send("created").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    let response = server.block_on(
+        client.request("OPTIONS", "/items", &[("Origin", "https://app.example")], b"")
+    );
+    assert_eq!(response.status, 204);
+    assert_eq!(response.header("Allow"), Some("POST, OPTIONS"));
+    assert_eq!(response.header("Access-Control-Allow-Origin"), Some("https://app.example"));
+    assert_eq!(response.header("Access-Control-Allow-Methods"), Some("GET, POST"));
+}
+
+#[test]
+fn test_options_allow_lists_head_and_options() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/files/:name", |_req, res| async move { res.send("file").await }).await;
+        server.delete("/files/:name", |_req, res| async move { res.send("deleted").await }).await;
+        server.post("/uploads", |_req, res| async move { res.send("uploaded").await }).await;
+        server.patch("/uploads", |_req, res| async move { res.send("patched").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.request("OPTIONS", "/files/a.txt", &[], b"").await;
+        assert_eq!(response.status, 204);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, DELETE, OPTIONS"));
+
+        let response = client.request("PUT", "/files/a.txt", &[], b"").await;
+        assert_eq!(response.status, 405);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, DELETE, OPTIONS"));
+
+        // Server-wide set
+        let response = client.request("OPTIONS", "*", &[], b"").await;
+        assert_eq!(response.status, 204);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, DELETE, POST, PATCH, OPTIONS"));
+    });
+}
+
+#[test]
+fn test_auto_options_can_be_turned_off() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.auto_options(false).await;
+        server.get("/files/:name", |_req, res| async move { res.send("file").await }).await;
+        server.delete("/files/:name", |_req, res| async move { res.send("deleted").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.request("OPTIONS", "/files/a.txt", &[], b"").await;
+        assert_eq!(response.status, 405);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD, DELETE"));
+
+        let response = client.request("OPTIONS", "*", &[], b"").await;
+        assert_eq!(response.status, 404);
+    });
+}
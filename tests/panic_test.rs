@@ -0,0 +1,66 @@
+mod common;
+
+use std::{ io::{ Read, Write }, sync::{ Arc, Mutex }, thread, time::Duration };
+
+use glote::{ Glote, RequestLog, ResponseExt };
+
+#[test]
+fn test_panics_are_answered_with_500() {
+    let logs = Arc::new(Mutex::new(Vec::<RequestLog>::new()));
+    let server = Glote::new();
+    server.block_on(async {
+        let sink = Arc::clone(&logs);
+        server.on_request(move |log| sink.lock().unwrap().push(log)).await;
+
+        server.post("/parse", |req, _res| async move {
+            // No body was sent
+            req.read().await.body_bytes.clone().unwrap();
+        }).await;
+        server.get_with_middleware(
+            "/guarded",
+            vec![|_req, _res, _next| async move {
+                panic!("middleware gave up on {}", 42);
+            }],
+            |_req, res| async move {
+                res.send("unreachable").await;
+            }
+        ).await;
+        server.get("/ok", |_req, res| async move {
+            res.send("still serving").await;
+        }).await;
+    });
+    common::spawn_server(server, 41_380);
+
+    // Well-formed 500 on a kept-alive request, then the server hangs up
+    let mut stream = common::connect(41_380);
+    stream
+        .write_all(b"POST /parse HTTP/1.1\r\nHost: x\r\nContent-Length: 0\r\n\r\n")
+        .unwrap();
+    let response = common::read_response(&mut stream);
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "{response}");
+    assert!(response.to_ascii_lowercase().contains("connection: close\r\n"), "{response}");
+    let mut rest = Vec::new();
+    assert_eq!(stream.read_to_end(&mut rest).unwrap(), 0);
+
+    let response = common::raw_request(41_380, b"GET /guarded HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 500 "), "{response}");
+
+    let response = common::raw_request(41_380, b"GET /ok HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with("still serving"), "{response}");
+
+    for _ in 0..100 {
+        if logs.lock().unwrap().len() == 3 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let logs = logs.lock().unwrap();
+    let seen: Vec<_> = logs
+        .iter()
+        .map(|log| (log.path.as_str(), log.status, log.panic.as_deref()))
+        .collect();
+    assert_eq!(seen[0].0, "/parse");
+    assert_eq!(seen[0].1, 500);
+    assert!(seen[0].2.is_some_and(|message| message.contains("None")), "{seen:?}");
+    assert_eq!(seen[1..], [("/guarded", 500, Some("middleware gave up on 42")), ("/ok", 200, None)]);
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+#[test]
+fn test_repeated_query_keys() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/search", |req, res| async move {
+            let tags = req.query_all("tag").await;
+            let first = req.query("tag[]").await.unwrap_or_default();
+            let page = req.query("page").await.unwrap_or_default();
+            let missing = req.query_all("missing").await;
+            res.send(&format!("{}|{first}|{page}|{}", tags.join(","), missing.len())).await;
+        }).await;
+    });
+    common::spawn_server(server, 41_240);
+
+    let response = common::raw_request(
+        41_240,
+        b"GET /search?tag=a&page=2&tag[]=b+c&tag=d HTTP/1.1\r\nHost: x\r\n\r\n"
+    );
+    assert!(response.ends_with("\r\n\r\na,b c,d|a|2|0"), "{response}");
+}
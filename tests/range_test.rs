@@ -0,0 +1,90 @@
+mod common;
+
+use glote::Glote;
+
+fn range(port: u16, value: &str) -> String {
+    common::raw_request(
+        port,
+        format!("GET /video.bin HTTP/1.1\r\nHost: x\r\nRange: {value}\r\n\r\n").as_bytes()
+    )
+}
+
+#[test]
+fn test_static_range_requests() {
+    let root = std::env::temp_dir().join("glote_range_test");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("video.bin"), "0123456789").unwrap();
+
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(root.to_str().unwrap()).await;
+    });
+    common::spawn_server(server, 41_130);
+
+    let full = common::raw_request(41_130, b"GET /video.bin HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(full.starts_with("HTTP/1.1 200 OK\r\n"), "{full}");
+    assert!(full.contains("\r\nAccept-Ranges: bytes\r\n"), "{full}");
+    assert!(full.ends_with("\r\n\r\n0123456789"), "{full}");
+
+    for (value, content_range, body) in [
+        ("bytes=2-4", "bytes 2-4/10", "234"),
+        ("bytes=7-", "bytes 7-9/10", "789"),
+        ("bytes=-3", "bytes 7-9/10", "789"),
+        ("bytes=-30", "bytes 0-9/10", "0123456789"),
+        ("bytes=8-100", "bytes 8-9/10", "89"),
+    ] {
+        let response = range(41_130, value);
+        assert!(response.starts_with("HTTP/1.1 206 Partial Content\r\n"), "{value}: {response}");
+        assert!(
+            response.contains(&format!("\r\nContent-Range: {content_range}\r\n")),
+            "{value}: {response}"
+        );
+        assert!(
+            response.contains(&format!("\r\nContent-Length: {}\r\n", body.len())),
+            "{value}: {response}"
+        );
+        assert!(response.ends_with(&format!("\r\n\r\n{body}")), "{value}: {response}");
+    }
+
+    for value in ["bytes=10-", "bytes=20-30", "bytes=-0"] {
+        let response = range(41_130, value);
+        assert!(response.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"), "{value}: {response}");
+        assert!(response.contains("\r\nContent-Range: bytes */10\r\n"), "{value}: {response}");
+    }
+
+    for value in ["items=0-1", "bytes=5-2", "bytes=a-b", "bytes=0-1,4-5", "bytes=+1-2"] {
+        let response = range(41_130, value);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{value}: {response}");
+        assert!(response.ends_with("0123456789"), "{value}: {response}");
+    }
+}
+
+#[test]
+fn test_if_range_with_a_weak_etag_gets_the_whole_file() {
+    let root = std::env::temp_dir().join("glote_if_range_test");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("video.bin"), "0123456789").unwrap();
+
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(root.to_str().unwrap()).await;
+    });
+    common::spawn_server(server, 41_131);
+
+    let full = common::raw_request(41_131, b"GET /video.bin HTTP/1.1\r\nHost: x\r\n\r\n");
+    let etag = full
+        .lines()
+        .find_map(|line| line.strip_prefix("ETag: "))
+        .unwrap()
+        .to_string();
+    assert!(etag.starts_with("W/"), "{full}");
+
+    // Even the current tag is only weak, it can't vouch for the bytes of a partial copy
+    let response = common::raw_request(
+        41_131,
+        format!("GET /video.bin HTTP/1.1\r\nHost: x\r\nRange: bytes=2-4\r\nIf-Range: {etag}\r\n\r\n").as_bytes()
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(!response.contains("Content-Range"), "{response}");
+    assert!(response.ends_with("\r\n\r\n0123456789"), "{response}");
+}
@@ -0,0 +1,69 @@
+use std::{ sync::Arc, time::Duration };
+
+use tokio::task::JoinSet;
+
+use glote::{ Glote, RateLimiter, RateLimiterExt, ResponseExt, TestClient };
+
+fn limited_server(limiter: RateLimiter) -> Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        let limiter = limiter.build();
+        server.use_middleware(move |req, res, next| {
+            let limiter = Arc::clone(&limiter);
+            async move {
+                limiter.run_middleware(req, res, next).await;
+            }
+        }).await;
+        server.get("/", |_req, res| async move { res.send("ok").await }).await;
+    });
+    server
+}
+
+#[test]
+fn test_101st_request_gets_429() {
+    let server = limited_server(RateLimiter::new(100, Duration::from_secs(60)));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        for i in 0..100 {
+            let response = client.get("/").await;
+            assert_eq!(response.status, 200, "request {i}");
+            assert_eq!(response.header("X-RateLimit-Remaining"), Some((99 - i).to_string().as_str()));
+        }
+        let response = client.get("/").await;
+        assert_eq!(response.status, 429);
+        assert_eq!(response.header("X-RateLimit-Remaining"), Some("0"));
+        // 100 per minute refills one every 0.6s
+        assert_eq!(response.header("Retry-After"), Some("1"));
+    });
+}
+
+#[test]
+fn test_concurrent_requests_share_the_budget() {
+    let server = limited_server(RateLimiter::new(50, Duration::from_secs(60)));
+    let client = TestClient::new(server.clone());
+    let statuses = server.block_on(async {
+        let mut requests = JoinSet::new();
+        for _ in 0..120 {
+            let client = client.clone();
+            requests.spawn(async move { client.get("/").await.status });
+        }
+        requests.join_all().await
+    });
+    assert_eq!(statuses.iter().filter(|&&status| status == 200).count(), 50);
+    assert_eq!(statuses.iter().filter(|&&status| status == 429).count(), 70);
+}
+
+#[test]
+fn test_custom_key() {
+    let limiter = RateLimiter::new(1, Duration::from_secs(60))
+        .key_by(|req| req.header("x-api-key").cloned());
+    let server = limited_server(limiter);
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let a = [("X-Api-Key", "a")];
+        let b = [("X-Api-Key", "b")];
+        assert_eq!(client.request("GET", "/", &a, b"").await.status, 200);
+        assert_eq!(client.request("GET", "/", &a, b"").await.status, 429);
+        assert_eq!(client.request("GET", "/", &b, b"").await.status, 200);
+    });
+}
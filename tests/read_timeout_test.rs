@@ -0,0 +1,74 @@
+mod common;
+
+use std::{ io::{ Read, Write }, time::{ Duration, Instant } };
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+fn start(port: u16) {
+    let server = Glote::new();
+    server.block_on(async {
+        server.read_header_timeout(Duration::from_millis(300)).await;
+        server.read_body_timeout(Duration::from_millis(300)).await;
+        server.request_timeout(Duration::from_millis(300)).await;
+        server.post("/echo", |req, res| async move {
+            res.send(&req.body().await.unwrap_or_default()).await;
+        }).await;
+        server.get("/slow", |req, res| async move {
+            let left = req.remaining_time().await.unwrap_or_default();
+            tokio::time::sleep(left + Duration::from_secs(2)).await;
+            res.send("too late").await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+// Everything the server sends until it closes, plus how long that took
+fn read_until_closed(stream: &mut std::net::TcpStream) -> (String, Duration) {
+    let started = Instant::now();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    (response, started.elapsed())
+}
+
+#[test]
+fn test_slow_clients_are_cut_off() {
+    start(41_300);
+
+    // Headers trickling in
+    let mut stream = common::connect(41_300);
+    stream.write_all(b"GET /slow HTTP/1.1\r\nHost").unwrap();
+    let (response, elapsed) = read_until_closed(&mut stream);
+    assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "{response}");
+    assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+
+    // Connected but silent: closed without a response
+    let mut stream = common::connect(41_300);
+    let (response, elapsed) = read_until_closed(&mut stream);
+    assert_eq!(response, "");
+    assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+
+    // Body shorter than promised
+    let mut stream = common::connect(41_300);
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 10\r\n\r\nabc").unwrap();
+    let (response, elapsed) = read_until_closed(&mut stream);
+    assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "{response}");
+    assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+
+    // A prompt client is unaffected
+    let response = common::raw_request(
+        41_300,
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\nabc"
+    );
+    assert!(response.ends_with("\r\n\r\nabc"), "{response}");
+}
+
+#[test]
+fn test_request_timeout_answers_504() {
+    start(41_301);
+
+    let started = Instant::now();
+    let response = common::raw_request(41_301, b"GET /slow HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 504 Gateway Timeout\r\n"), "{response}");
+    assert!(!response.contains("too late"), "{response}");
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt, TestClient };
+
+#[test]
+fn test_redirect_sets_status_and_location() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/old", |_req, res| async move {
+            res.redirect("/login?next=a&b").await;
+        }).await;
+
+        server.get("/moved", |_req, res| async move {
+            res.redirect_with_status(308, "https://example.com/new").await;
+        }).await;
+    });
+    common::spawn_server(server, 41_080);
+
+    let found = common::raw_request(41_080, b"GET /old HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(found.starts_with("HTTP/1.1 302 Found\r\n"), "{found}");
+    assert!(found.contains("\r\nLocation: /login?next=a&b\r\n"), "{found}");
+    assert!(found.ends_with(r#"<a href="/login?next=a&amp;b">302 Found</a>"#), "{found}");
+
+    let moved = common::raw_request(41_080, b"GET /moved HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(moved.starts_with("HTTP/1.1 308 Permanent Redirect\r\n"), "{moved}");
+    assert!(moved.contains("\r\nLocation: https://example.com/new\r\n"), "{moved}");
+}
+
+#[test]
+fn test_redirect_refuses_crlf_in_location() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/login", |req, res| async move {
+            let next = req.query("next").await.unwrap_or_default();
+            res.redirect(&next).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let response = client.get("/login?next=/home%0d%0aSet-Cookie:%20evil=1").await;
+        assert_eq!(response.status, 500);
+        assert_eq!(response.header("Location"), None);
+        assert_eq!(response.header("Set-Cookie"), None);
+
+        let response = client.get("/login?next=/home").await;
+        assert_eq!(response.status, 302);
+        assert_eq!(response.header("Location"), Some("/home"));
+    });
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::{ sync::{ Arc, Mutex }, thread, time::Duration };
+
+use glote::{ Glote, RequestLog, ResponseExt, TestClient };
+
+#[test]
+fn test_on_request_sees_every_request() {
+    let logs = Arc::new(Mutex::new(Vec::<RequestLog>::new()));
+    let server = Glote::new();
+    server.block_on(async {
+        let sink = Arc::clone(&logs);
+        server.on_request(move |log| sink.lock().unwrap().push(log)).await;
+
+        server.post("/users/:id", |_req, res| async move {
+            res.status(201).await;
+            res.send("created").await;
+        }).await;
+        server.get("/old", |_req, res| async move {
+            res.redirect("/new").await;
+        }).await;
+    });
+    common::spawn_server(server, 41_280);
+
+    common::raw_request(41_280, b"POST /users/7 HTTP/1.1\r\nHost: x\r\nContent-Length: 0\r\n\r\n");
+    common::raw_request(41_280, b"GET /old HTTP/1.1\r\nHost: x\r\n\r\n");
+    common::raw_request(41_280, b"GET /users/7 HTTP/1.1\r\nHost: x\r\n\r\n");
+    common::raw_request(41_280, b"GET /missing HTTP/1.1\r\nHost: x\r\n\r\n");
+
+    // The hook runs right after the response is written
+    for _ in 0..100 {
+        if logs.lock().unwrap().len() == 4 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let logs = logs.lock().unwrap();
+    let seen: Vec<_> = logs
+        .iter()
+        .map(|log| (log.method.as_str(), log.path.as_str(), log.route.as_deref(), log.status))
+        .collect();
+    assert_eq!(seen, [
+        ("POST", "/users/7", Some("/users/:id"), 201),
+        ("GET", "/old", Some("/old"), 302),
+        ("GET", "/users/7", None, 405),
+        ("GET", "/missing", None, 404),
+    ]);
+    assert!(logs.iter().all(|log| log.peer_addr.is_some_and(|addr| addr.ip().is_loopback())));
+}
+
+#[test]
+fn test_refused_requests_are_logged_with_their_reason() {
+    let logs = Arc::new(Mutex::new(Vec::<RequestLog>::new()));
+    let server = Glote::new();
+    server.block_on(async {
+        let sink = Arc::clone(&logs);
+        server.on_request(move |log| sink.lock().unwrap().push(log)).await;
+        server.max_body_size(4).await;
+        server.post("/upload", |_req, res| async move { res.send("ok").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        assert_eq!(client.post("/upload", b"too large").await.status, 413);
+        let response = client.request("POST", "/upload", &[("Expect", "magic")], b"x").await;
+        assert_eq!(response.status, 417);
+    });
+
+    let logs = logs.lock().unwrap();
+    let seen: Vec<_> = logs
+        .iter()
+        .map(|log| (log.path.as_str(), log.status, log.error.as_deref()))
+        .collect();
+    assert_eq!(seen, [
+        ("/upload", 413, Some("body of 9 bytes, limit is 4")),
+        ("/upload", 417, Some("unsupported Expect: magic")),
+    ]);
+}
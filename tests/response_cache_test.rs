@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration;
+
+use glote::{ Glote, ResponseCache, ResponseCacheExt, ResponseExt, TestClient };
+
+fn cached_server(cache: Arc<ResponseCache>, runs: Arc<AtomicUsize>) -> Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        server.use_middleware(move |req, res, next| {
+            let cache = Arc::clone(&cache);
+            async move {
+                cache.run_middleware(req, res, next).await;
+            }
+        }).await;
+        let page_runs = Arc::clone(&runs);
+        server.get("/catalog/:page", move |req, res| {
+            let runs = Arc::clone(&page_runs);
+            async move {
+                let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                let page = req.read().await.params("page").cloned().unwrap_or_default();
+                res.read().await.set_header("X-Run", &run.to_string()).await.unwrap();
+                res.send(&format!("page {page}")).await;
+            }
+        }).await;
+        let private_runs = Arc::clone(&runs);
+        server.get("/account", move |_req, res| {
+            let runs = Arc::clone(&private_runs);
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                res.read().await.set_header("Cache-Control", "no-store").await.unwrap();
+                res.send("account").await;
+            }
+        }).await;
+        server.post("/catalog/:page", move |_req, res| {
+            let runs = Arc::clone(&runs);
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                res.send("saved").await;
+            }
+        }).await;
+    });
+    server
+}
+
+#[test]
+fn test_second_get_is_served_from_the_cache() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = cached_server(ResponseCache::new(Duration::from_secs(30)).build(), Arc::clone(&runs));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let first = client.get("/catalog/1").await;
+        assert_eq!(first.text(), "page 1");
+        assert_eq!(first.header("Age"), None);
+
+        let second = client.get("/catalog/1").await;
+        assert_eq!(second.status, 200);
+        assert_eq!(second.text(), "page 1");
+        assert_eq!(second.header("X-Run"), Some("1"));
+        assert_eq!(second.header("Content-Type"), first.header("Content-Type"));
+        assert_eq!(second.header("Age"), Some("0"));
+
+        // Other pages and query strings are their own entries
+        assert_eq!(client.get("/catalog/2").await.header("X-Run"), Some("2"));
+        assert_eq!(client.get("/catalog/1?sort=asc").await.header("X-Run"), Some("3"));
+    });
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_posts_and_no_store_bypass_the_cache() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = cached_server(ResponseCache::new(Duration::from_secs(30)).build(), Arc::clone(&runs));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        for _ in 0..2 {
+            assert_eq!(client.post("/catalog/1", b"x").await.text(), "saved");
+            assert_eq!(client.get("/account").await.text(), "account");
+            // Not found pages aren't kept either
+            assert_eq!(client.get("/missing").await.status, 404);
+        }
+    });
+    assert_eq!(runs.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_purge_drops_matching_paths() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let cache = ResponseCache::new(Duration::from_secs(30)).build();
+    let server = cached_server(Arc::clone(&cache), Arc::clone(&runs));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        client.get("/catalog/1").await;
+        client.get("/account").await;
+        cache.purge("/catalog");
+        assert_eq!(client.get("/catalog/1").await.header("X-Run"), Some("3"));
+        assert_eq!(client.get("/catalog/1").await.header("X-Run"), Some("3"));
+    });
+}
+
+#[test]
+fn test_server_purges_registered_caches() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let cache = ResponseCache::new(Duration::from_secs(30)).build();
+    let server = cached_server(Arc::clone(&cache), Arc::clone(&runs));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        server.register_cache(&cache).await;
+        // Registering twice doesn't matter
+        server.register_cache(&cache).await;
+        client.get("/catalog/1").await;
+        client.get("/catalog/2").await;
+        assert_eq!(client.get("/catalog/1").await.header("X-Run"), Some("1"));
+
+        server.purge_cache("/catalog/1").await;
+        assert_eq!(client.get("/catalog/1").await.header("X-Run"), Some("3"));
+        assert_eq!(client.get("/catalog/2").await.header("X-Run"), Some("2"));
+    });
+}
+
+#[test]
+fn test_entries_expire() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = cached_server(ResponseCache::new(Duration::from_millis(100)).build(), Arc::clone(&runs));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        client.get("/catalog/1").await;
+        assert_eq!(client.get("/catalog/1").await.header("X-Run"), Some("1"));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(client.get("/catalog/1").await.header("X-Run"), Some("2"));
+    });
+}
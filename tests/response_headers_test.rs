@@ -0,0 +1,93 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt, TestClient };
+
+#[test]
+fn test_headers_set_in_middleware_reach_the_wire() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.use_middleware(|_req, res, next| async move {
+            {
+                let res = res.read().await;
+                res.set_header("X-Request-Id", "abc123").await.unwrap();
+                res.set_header("X-Removed", "nope").await.unwrap();
+            }
+            next().await;
+        }).await;
+
+        server.get("/", |_req, res| async move {
+            {
+                let res = res.read().await;
+                res.remove_header("x-removed").await;
+                res.set_header("Content-Type", "text/plain").await.unwrap();
+            }
+            res.send("hi").await;
+        }).await;
+
+        server.get("/json", |_req, res| async move {
+            res.json(&serde_json::json!({ "ok": true })).await;
+        }).await;
+    });
+    common::spawn_server(server, 41_070);
+
+    let response = common::raw_request(41_070, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.contains("\r\nX-Request-Id: abc123"), "{response}");
+    assert!(!head.contains("X-Removed"), "{response}");
+    assert!(head.contains("\r\nContent-Type: text/plain"), "{response}");
+    assert!(!head.contains("text/html"), "{response}");
+    assert!(head.contains("\r\nContent-Length: 2"), "{response}");
+    assert_eq!(body, "hi");
+
+    let json = common::raw_request(41_070, b"GET /json HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(json.contains("\r\nX-Request-Id: abc123\r\n"), "{json}");
+    assert!(json.contains("\r\nContent-Type: application/json; charset=UTF-8\r\n"), "{json}");
+    assert!(json.ends_with(r#"{"ok":true}"#), "{json}");
+}
+
+#[test]
+fn test_header_injection_is_refused() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/echo", |req, res| async move {
+            let next = req.query("next").await.unwrap_or_default();
+            let result = res.read().await.set_header("X-Next", &next).await;
+            res.send(if result.is_ok() { "set" } else { "refused" }).await;
+        }).await;
+        server.get("/names", |_req, res| async move {
+            let outcome = {
+                let res = res.read().await;
+                [
+                    res.set_header("Bad Name", "x").await.is_ok(),
+                    res.append_header("X-Nul", "a\0b").await.is_ok(),
+                    res.set_header("X-Tab", "a\tb").await.is_ok(),
+                ]
+            };
+            res.send(&format!("{outcome:?}")).await;
+        }).await;
+        server.get("/stream", |_req, res| async move {
+            let _ = res.begin(200, &[("X-Split", "a\r\nSet-Cookie: evil=1"), ("X-Fine", "ok")]).await;
+            let _ = res.end().await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        // Percent-decoded into a real CR LF before it reaches the handler
+        let response = client.get("/echo?next=%0d%0aSet-Cookie:%20evil=1").await;
+        assert_eq!(response.text(), "refused");
+        assert_eq!(response.header("Set-Cookie"), None);
+        assert_eq!(response.header("X-Next"), None);
+
+        let response = client.get("/echo?next=%2Fhome").await;
+        assert_eq!(response.text(), "set");
+        assert_eq!(response.header("X-Next"), Some("/home"));
+
+        assert_eq!(client.get("/names").await.text(), "[false, false, true]");
+
+        // Headers handed straight to the head are filtered too
+        let response = client.get("/stream").await;
+        assert_eq!(response.header("Set-Cookie"), None);
+        assert_eq!(response.header("X-Split"), None);
+        assert_eq!(response.header("X-Fine"), Some("ok"));
+    });
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::{ sync::{ Arc, Mutex }, thread, time::Duration };
+
+use glote::{ Glote, ResponseExt };
+
+const PORT: u16 = 41_430;
+
+#[test]
+fn test_response_helpers() {
+    let seen = Arc::new(Mutex::new(Vec::<u16>::new()));
+    let server = Glote::new();
+    server.block_on(async {
+        let sink = Arc::clone(&seen);
+        server.use_middleware(move |_req, res, next| {
+            let sink = Arc::clone(&sink);
+            async move {
+                next().await;
+                // Status the handler chose, read back after it ran
+                let status = res.status_code().await;
+                sink.lock().unwrap().push(status);
+            }
+        }).await;
+
+        server.post("/users", |_req, res| async move {
+            res.json_with_status(201, &serde_json::json!({ "id": 7 })).await;
+        }).await;
+        server.get("/text", |_req, res| async move {
+            res.text("plain").await;
+        }).await;
+        server.get("/html", |_req, res| async move {
+            res.html("<b>bold</b>").await;
+        }).await;
+        server.get("/gone", |_req, res| async move {
+            res.send_status(410, "gone").await;
+        }).await;
+        server.get("/guard", |_req, res| async move {
+            // One read guard sets the status and sends
+            res.read().await.status(202).await.send("accepted").await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+
+    let response = common::raw_request(PORT, b"POST /users HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 201 Created\r\n"), "{response}");
+    assert!(response.contains("Content-Type: application/json"), "{response}");
+    assert!(response.ends_with(r#"{"id":7}"#), "{response}");
+
+    let response = common::raw_request(PORT, b"GET /text HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.contains("Content-Type: text/plain; charset=UTF-8\r\n"), "{response}");
+    assert!(response.ends_with("plain"), "{response}");
+
+    let response = common::raw_request(PORT, b"GET /html HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.contains("Content-Type: text/html; charset=UTF-8\r\n"), "{response}");
+
+    let response = common::raw_request(PORT, b"GET /gone HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 410 "), "{response}");
+    assert!(response.ends_with("gone"), "{response}");
+
+    let response = common::raw_request(PORT, b"GET /guard HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 202 "), "{response}");
+
+    // The middleware records after the response went out
+    for _ in 0..100 {
+        if seen.lock().unwrap().len() == 5 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(*seen.lock().unwrap(), [201, 200, 200, 410, 202]);
+}
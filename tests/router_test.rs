@@ -0,0 +1,84 @@
+use glote::{ Glote, Next, Req, Res, RequestExt, ResponseExt, Router, TestClient, mid };
+
+async fn trace(req: &Req, step: &str) {
+    let mut req = req.write().await;
+    let trace = req.path_params.entry("trace".into()).or_default();
+    trace.push_str(step);
+}
+
+async fn reply_with_trace(req: Req, res: Res) {
+    let trace = req.params("trace").await.unwrap_or_default();
+    res.send(&trace).await;
+}
+
+#[test]
+fn test_group_prefix_and_middleware_order() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.use_middleware(|req, _res, next| async move {
+            trace(&req, "global>").await;
+            next().await;
+        }).await;
+
+        let mut api = Router::new("/api/v1");
+        api.use_middleware(|req, _res, next| async move {
+            trace(&req, "group>").await;
+            next().await;
+        });
+        api.get("/", |req, res| async move {
+            trace(&req, "index").await;
+            reply_with_trace(req, res).await;
+        });
+        api.get_with_middleware(
+            "/users/:id",
+            vec![
+                mid!(req, res, next, {
+                    trace(&req, "route>").await;
+                    next().await;
+                })
+            ],
+            |req, res| async move {
+                let id = req.params("id").await.unwrap_or_default();
+                trace(&req, &format!("user {id}")).await;
+                reply_with_trace(req, res).await;
+            }
+        );
+
+        // Nested group inherits the prefix and middleware of its parent
+        let mut admin = Router::new("/admin");
+        admin.use_middleware(|req, _res, next| async move {
+            trace(&req, "admin>").await;
+            next().await;
+        });
+        admin.delete("/users/:id", |req, res| async move {
+            trace(&req, "delete").await;
+            reply_with_trace(req, res).await;
+        });
+        api.mount(admin);
+
+        server.mount(api).await;
+
+        server.get("/health", |req, res| async move {
+            trace(&req, "health").await;
+            reply_with_trace(req, res).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let user = client.get("/api/v1/users/9").await;
+        assert_eq!(user.text(), "global>group>route>user 9");
+
+        let index = client.get("/api/v1").await;
+        assert_eq!(index.text(), "global>group>index");
+
+        let delete = client.request("DELETE", "/api/v1/admin/users/3", &[], b"").await;
+        assert_eq!(delete.text(), "global>group>admin>delete");
+
+        // Routes outside the group don't get its middleware
+        let health = client.get("/health").await;
+        assert_eq!(health.text(), "global>health");
+
+        let unprefixed = client.get("/users/9").await;
+        assert_eq!(unprefixed.status, 404);
+    });
+}
@@ -0,0 +1,46 @@
+use tokio::runtime::{ Handle, Runtime, RuntimeFlavor };
+
+use glote::{ Glote, ResponseExt, TestClient };
+
+#[test]
+fn test_worker_count_and_flavor() {
+    let server = Glote::with_workers(2);
+    let workers = server.block_on(async { Handle::current().metrics().num_workers() });
+    assert_eq!(workers, 2);
+
+    let server = Glote::current_thread();
+    assert_eq!(server.runtime_handle().runtime_flavor(), RuntimeFlavor::CurrentThread);
+    server.block_on(async {
+        server.get("/", |_req, res| async move { res.send("single").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    let response = server.block_on(client.get("/"));
+    assert_eq!(response.text(), "single");
+}
+
+#[test]
+fn test_borrowed_runtime_handle() {
+    let runtime = Runtime::new().unwrap();
+    let server = Glote::with_runtime_handle(runtime.handle().clone());
+    let client = TestClient::new(server.clone());
+
+    // The app drives its own runtime, the server just uses it
+    let response = runtime.block_on(async {
+        server.get("/", |_req, res| async move { res.send("embedded").await }).await;
+        client.get("/").await
+    });
+    assert_eq!(response.text(), "embedded");
+
+    // block_on from outside the runtime goes through the handle
+    let workers = server.block_on(async { Handle::current().metrics().num_workers() });
+    assert_eq!(workers, runtime.metrics().num_workers());
+}
+
+#[test]
+fn test_spawn_blocking() {
+    let server = Glote::new();
+    let sum = server.block_on(async {
+        server.spawn_blocking(|| (1..=100u64).sum::<u64>()).await.unwrap()
+    });
+    assert_eq!(sum, 5050);
+}
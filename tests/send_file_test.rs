@@ -0,0 +1,69 @@
+mod common;
+
+use glote::{ Glote, ResponseExt };
+
+#[test]
+fn test_send_file_and_download() {
+    let dir = std::env::temp_dir().join("glote_send_file_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    // Bigger than one copy buffer so the file goes out in several chunks
+    let report: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(dir.join("report.pdf"), &report).unwrap();
+
+    let server = Glote::new();
+    let path = dir.join("report.pdf");
+    let missing = dir.join("missing.pdf");
+    server.block_on(async {
+        let file = path.clone();
+        server.get("/report", move |_req, res| {
+            let file = file.clone();
+            async move {
+                res.send_file(file).await;
+            }
+        }).await;
+
+        let file = path.clone();
+        server.get("/download", move |_req, res| {
+            let file = file.clone();
+            async move {
+                res.download(file, "Q3 \"final\".pdf").await;
+            }
+        }).await;
+
+        server.get("/missing", move |_req, res| {
+            let file = missing.clone();
+            async move {
+                res.send_file(file).await;
+            }
+        }).await;
+    });
+    common::spawn_server(server, 41_100);
+
+    let response = read_bytes(41_100, "/report");
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8_lossy(&response[..split]).to_string();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+    assert!(head.contains("\r\nContent-Type: application/pdf"), "{head}");
+    assert!(head.contains("\r\nContent-Length: 100000"), "{head}");
+    assert_eq!(&response[split + 4..], &report[..]);
+
+    let download = read_bytes(41_100, "/download");
+    let download = String::from_utf8_lossy(&download);
+    assert!(
+        download.contains("\r\nContent-Disposition: attachment; filename=\"Q3 _final_.pdf\"; filename*=UTF-8''Q3%20%22final%22.pdf\r\n"),
+        "{}",
+        &download[..200]
+    );
+
+    let missing = common::raw_request(41_100, b"GET /missing HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"), "{missing}");
+}
+
+fn read_bytes(port: u16, path: &str) -> Vec<u8> {
+    use std::io::{ Read, Write };
+    let mut stream = common::connect(port);
+    stream.write_all(format!("GET {path} HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").as_bytes()).unwrap();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    response
+}
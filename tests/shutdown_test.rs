@@ -0,0 +1,85 @@
+mod common;
+
+use std::{ io::Write, sync::mpsc, thread, time::{ Duration, Instant } };
+use glote::{ Glote, ResponseExt };
+
+#[test]
+fn test_listen_returns_after_shutdown_and_drains_requests() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |_req, res| async move {
+            res.send("hi").await;
+        }).await;
+        server.get("/slow", |_req, res| async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            res.send("done").await;
+        }).await;
+    });
+
+    let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+    let (finished_tx, finished) = mpsc::channel();
+    thread::spawn(move || {
+        let listener = server.clone();
+        let result = server.block_on(
+            listener.listen_with_shutdown(("127.0.0.1", 41_150), async {
+                let _ = signal.await;
+            })
+        );
+        finished_tx.send(result.is_ok()).unwrap();
+    });
+
+    let response = common::raw_request(41_150, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with("hi"), "{response}");
+
+    // An idle keep-alive connection must not hold shutdown up
+    let mut idle = common::connect(41_150);
+    idle.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    common::read_response(&mut idle);
+
+    let mut slow = common::connect(41_150);
+    slow.write_all(b"GET /slow HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    let started = Instant::now();
+    trigger.send(()).unwrap();
+
+    // The in-flight request still gets its answer, and is told the connection closes
+    let response = common::read_response(&mut slow);
+    assert!(response.ends_with("done"), "{response}");
+    assert!(response.contains("\r\nConnection: close\r\n"), "{response}");
+
+    assert!(finished.recv_timeout(Duration::from_secs(3)).unwrap());
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert!(std::net::TcpStream::connect(("127.0.0.1", 41_150)).is_err());
+}
+
+#[test]
+fn test_grace_period_aborts_stuck_requests() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.shutdown_grace_period(Duration::from_millis(100)).await;
+        server.get("/stuck", |_req, res| async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            res.send("never").await;
+        }).await;
+    });
+
+    let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+    let (finished_tx, finished) = mpsc::channel();
+    thread::spawn(move || {
+        let listener = server.clone();
+        let result = server.block_on(
+            listener.listen_with_shutdown(("127.0.0.1", 41_151), async {
+                let _ = signal.await;
+            })
+        );
+        finished_tx.send(result.is_ok()).unwrap();
+    });
+
+    let mut stuck = common::connect(41_151);
+    stuck.write_all(b"GET /stuck HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    trigger.send(()).unwrap();
+    assert!(finished.recv_timeout(Duration::from_secs(3)).unwrap());
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::time::Duration;
+
+use tokio::task::JoinSet;
+
+use glote::{ Glote, ResponseExt, SingleFlight, SingleFlightExt, TestClient };
+
+fn coalescing_server(single_flight: SingleFlight, runs: Arc<AtomicUsize>, delay: Duration) -> Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        let single_flight = single_flight.build();
+        server.use_middleware(move |req, res, next| {
+            let single_flight = Arc::clone(&single_flight);
+            async move {
+                single_flight.run_middleware(req, res, next).await;
+            }
+        }).await;
+        let handler_runs = Arc::clone(&runs);
+        server.get("/report", move |_req, res| {
+            let runs = Arc::clone(&handler_runs);
+            async move {
+                let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(delay).await;
+                res.read().await.set_header("X-Run", &run.to_string()).await.unwrap();
+                res.send(&"report ".repeat(100)).await;
+            }
+        }).await;
+        server.post("/report", move |_req, res| {
+            let runs = Arc::clone(&runs);
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(delay).await;
+                res.send("saved").await;
+            }
+        }).await;
+    });
+    server
+}
+
+#[test]
+fn test_concurrent_gets_run_the_handler_once() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = coalescing_server(SingleFlight::new(), Arc::clone(&runs), Duration::from_millis(300));
+    let client = TestClient::new(server.clone());
+    let responses = server.block_on(async {
+        let mut requests = JoinSet::new();
+        for _ in 0..10 {
+            let client = client.clone();
+            requests.spawn(async move { client.get("/report").await });
+        }
+        requests.join_all().await
+    });
+
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    for response in &responses {
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "report ".repeat(100));
+        assert_eq!(response.header("X-Run"), Some("1"));
+        assert_eq!(response.header("Content-Type"), responses[0].header("Content-Type"));
+    }
+
+    // The flight is over, the next request runs again
+    let response = server.block_on(client.get("/report"));
+    assert_eq!(response.header("X-Run"), Some("2"));
+}
+
+#[test]
+fn test_posts_are_never_coalesced() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = coalescing_server(SingleFlight::new(), Arc::clone(&runs), Duration::from_millis(100));
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let mut requests = JoinSet::new();
+        for _ in 0..5 {
+            let client = client.clone();
+            requests.spawn(async move { client.post("/report", b"x").await });
+        }
+        for response in requests.join_all().await {
+            assert_eq!(response.text(), "saved");
+        }
+    });
+    assert_eq!(runs.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn test_oversized_responses_are_not_shared() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = coalescing_server(
+        SingleFlight::new().max_size(64),
+        Arc::clone(&runs),
+        Duration::from_millis(200)
+    );
+    let client = TestClient::new(server.clone());
+    let responses = server.block_on(async {
+        let mut requests = JoinSet::new();
+        for _ in 0..3 {
+            let client = client.clone();
+            requests.spawn(async move { client.get("/report").await });
+        }
+        requests.join_all().await
+    });
+
+    // The leader still gets the whole response, followers run on their own
+    for response in &responses {
+        assert_eq!(response.text(), "report ".repeat(100));
+    }
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_followers_stop_waiting_after_the_timeout() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let server = coalescing_server(
+        SingleFlight::new().timeout(Duration::from_millis(50)),
+        Arc::clone(&runs),
+        Duration::from_millis(300)
+    );
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let leader = tokio::spawn({
+            let client = client.clone();
+            async move { client.get("/report").await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let follower = client.get("/report").await;
+        assert_eq!(follower.header("X-Run"), Some("2"));
+        assert_eq!(leader.await.unwrap().header("X-Run"), Some("1"));
+    });
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+const PORT: u16 = 40_900;
+
+// (name, raw request, expected status line)
+const FIXTURES: &[(&str, &str, &str)] = &[
+    (
+        "duplicate differing Content-Length",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\nhello!",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "Content-Length list with differing values",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5, 6\r\n\r\nhello!",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "CL.TE",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 13\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nSMUGGLED",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "TE.CL",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n8\r\nSMUGGLED\r\n0\r\n\r\n",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "signed Content-Length",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: +5\r\n\r\nhello",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "hex Content-Length",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 0x5\r\n\r\nhello",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "overflowing Content-Length",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 99999999999999999999999999\r\n\r\nhello",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "whitespace before colon",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length : 5\r\n\r\nhello",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "obs-fold Content-Length",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length:\r\n 5\r\n\r\nhello",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "chunked not the final coding",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked, identity\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        "HTTP/1.1 400 Bad Request\r\n",
+    ),
+    (
+        "gzip under chunked",
+        "POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n",
+        "HTTP/1.1 501 Not Implemented\r\n",
+    ),
+];
+
+fn start() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.post("/echo", |req, res| async move {
+            res.send(&req.body().await.unwrap_or_default()).await;
+        }).await;
+    });
+    common::spawn_server(server, PORT);
+}
+
+#[test]
+fn test_malicious_framing_is_rejected() {
+    start();
+
+    for (name, raw, expected) in FIXTURES {
+        // Framing errors always close the connection
+        let response = common::raw_request(PORT, raw.as_bytes());
+        assert!(response.starts_with(expected), "{name}: {response}");
+        assert!(response.contains("Connection: close\r\n"), "{name}: {response}");
+    }
+
+    // Repeated identical values are harmless
+    let response = common::raw_request(
+        PORT,
+        b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\ncontent-length: 5\r\n\r\nhello"
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("\r\n\r\nhello"), "{response}");
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::{
+    io::{ Read, Write },
+    sync::{ Arc, atomic::{ AtomicBool, Ordering } },
+    thread,
+    time::Duration,
+};
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+#[test]
+fn test_events_are_streamed_until_the_client_leaves() {
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let server = Glote::new();
+    server.block_on(async {
+        let flag = Arc::clone(&disconnected);
+        server.get("/events", move |req, res| {
+            let flag = Arc::clone(&flag);
+            async move {
+                let sse = res.sse().await.unwrap();
+                sse.send_event(Some("tick"), "line one\nline two", Some("1")).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                sse.send_event(None, "{\"n\":2}", None).await.unwrap();
+                req.cancelled().await;
+                flag.store(sse.is_closed().await, Ordering::SeqCst);
+            }
+        }).await;
+    });
+    common::spawn_server(server, 41_340);
+
+    let mut stream = common::connect(41_340);
+    stream.write_all(b"GET /events HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    let mut received = String::new();
+    let mut chunk = [0u8; 1024];
+    // Up to the end of the chunk, so the reset below can't cut the handler's write short
+    while !received.contains("data: {\"n\":2}\n\n\r\n") {
+        let n = stream.read(&mut chunk).unwrap();
+        assert!(n > 0, "{received}");
+        received.push_str(&String::from_utf8_lossy(&chunk[..n]));
+    }
+
+    assert!(received.starts_with("HTTP/1.1 200 OK\r\n"), "{received}");
+    assert!(received.contains("\r\nContent-Type: text/event-stream\r\n"), "{received}");
+    assert!(received.contains("\r\nCache-Control: no-cache\r\n"), "{received}");
+    assert!(received.contains("event: tick\nid: 1\ndata: line one\ndata: line two\n\n"), "{received}");
+
+    // The handler notices the client is gone
+    drop(stream);
+    for _ in 0..100 {
+        if disconnected.load(Ordering::SeqCst) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(disconnected.load(Ordering::SeqCst));
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+use glote::{ Glote, RequestExt, ResponseExt };
+
+struct Counter(AtomicUsize);
+
+struct Config {
+    name: &'static str,
+}
+
+#[test]
+fn test_state_is_shared_between_routes() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.state(Counter(AtomicUsize::new(0))).await;
+        server.state(Config { name: "glote" }).await;
+
+        server.post("/count", |req, res| async move {
+            let counter = req.state::<Counter>().await.unwrap();
+            let now = counter.0.fetch_add(1, Ordering::SeqCst) + 1;
+            res.send(&now.to_string()).await;
+        }).await;
+        server.get("/count", |req, res| async move {
+            let counter = req.state::<Counter>().await.unwrap();
+            let config = req.read().await.state::<Config>().unwrap();
+            let missing = req.state::<String>().await.is_none();
+            res.send(&format!("{} {} {missing}", config.name, counter.0.load(Ordering::SeqCst))).await;
+        }).await;
+    });
+    common::spawn_server(server, 41_260);
+
+    for expected in ["1", "2", "3"] {
+        let response = common::raw_request(
+            41_260,
+            b"POST /count HTTP/1.1\r\nHost: x\r\nContent-Length: 0\r\n\r\n"
+        );
+        assert!(response.ends_with(&format!("\r\n\r\n{expected}")), "{response}");
+    }
+
+    let response = common::raw_request(41_260, b"GET /count HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(response.ends_with("\r\n\r\nglote 3 true"), "{response}");
+}
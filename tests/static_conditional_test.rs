@@ -0,0 +1,59 @@
+mod common;
+
+use glote::Glote;
+
+fn header<'a>(response: &'a str, name: &str) -> &'a str {
+    response
+        .split("\r\n")
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+        .unwrap_or_else(|| panic!("no {name} in {response}"))
+}
+
+#[test]
+fn test_static_files_answer_conditional_requests() {
+    let root = std::env::temp_dir().join("glote_static_conditional_test");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("style.css"), "body{}").unwrap();
+
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(root.to_str().unwrap()).await;
+        server.static_cache_control("public, max-age=60").await;
+    });
+    common::spawn_server(server, 41_120);
+
+    let first = common::raw_request(41_120, b"GET /style.css HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(first.starts_with("HTTP/1.1 200 OK\r\n"), "{first}");
+    assert!(first.ends_with("body{}"), "{first}");
+    assert_eq!(header(&first, "Cache-Control"), "public, max-age=60");
+    let etag = header(&first, "ETag");
+    assert!(etag.starts_with("W/\""), "{first}");
+    let last_modified = header(&first, "Last-Modified");
+
+    let cached = common::raw_request(
+        41_120,
+        format!("GET /style.css HTTP/1.1\r\nHost: x\r\nIf-None-Match: \"other\", {etag}\r\n\r\n").as_bytes()
+    );
+    assert!(cached.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{cached}");
+    assert!(cached.ends_with("\r\n\r\n"), "{cached}");
+    assert_eq!(header(&cached, "ETag"), etag);
+    assert_eq!(header(&cached, "Cache-Control"), "public, max-age=60");
+
+    let since = common::raw_request(
+        41_120,
+        format!("GET /style.css HTTP/1.1\r\nHost: x\r\nIf-Modified-Since: {last_modified}\r\n\r\n").as_bytes()
+    );
+    assert!(since.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{since}");
+
+    let stale = common::raw_request(
+        41_120,
+        b"GET /style.css HTTP/1.1\r\nHost: x\r\nIf-None-Match: \"other\"\r\n\r\n"
+    );
+    assert!(stale.starts_with("HTTP/1.1 200 OK\r\n"), "{stale}");
+
+    let old = common::raw_request(
+        41_120,
+        b"GET /style.css HTTP/1.1\r\nHost: x\r\nIf-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n"
+    );
+    assert!(old.starts_with("HTTP/1.1 200 OK\r\n"), "{old}");
+}
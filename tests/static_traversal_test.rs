@@ -0,0 +1,74 @@
+mod common;
+
+use glote::{ Glote, TestClient };
+
+#[test]
+fn test_static_path_cannot_escape_root() {
+    let base = std::env::temp_dir().join("glote_static_traversal_test");
+    let root = base.join("public");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("app.js"), "console.log(1)").unwrap();
+    std::fs::write(base.join("secret.txt"), "top secret").unwrap();
+    #[cfg(unix)]
+    {
+        let link = root.join("leak.txt");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(base.join("secret.txt"), &link).unwrap();
+    }
+
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(root.to_str().unwrap()).await;
+    });
+    common::spawn_server(server, 41_110);
+
+    let ok = common::raw_request(41_110, b"GET /app.js HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"), "{ok}");
+    assert!(ok.ends_with("console.log(1)"), "{ok}");
+
+    let encoded_ok = common::raw_request(41_110, b"GET /%61pp.js HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(encoded_ok.ends_with("console.log(1)"), "{encoded_ok}");
+
+    for target in [
+        "/../secret.txt",
+        "/./../public/../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/%2E%2E%2Fsecret.txt",
+        "/..%2fsecret.txt",
+    ] {
+        let response = common::raw_request(
+            41_110,
+            format!("GET {target} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes()
+        );
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{target}: {response}");
+        assert!(!response.contains("top secret"), "{target}: {response}");
+    }
+
+    #[cfg(unix)]
+    {
+        let link = common::raw_request(41_110, b"GET /leak.txt HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert!(link.starts_with("HTTP/1.1 403 Forbidden\r\n"), "{link}");
+    }
+
+    let nul = common::raw_request(41_110, b"GET /app.js%00.png HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(nul.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{nul}");
+
+    let missing = common::raw_request(41_110, b"GET /nope.js HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"), "{missing}");
+}
+
+#[test]
+fn test_unresolvable_static_path_serves_nothing() {
+    let missing = std::env::temp_dir().join("glote_static_missing_root");
+    let _ = std::fs::remove_dir_all(&missing);
+    let server = Glote::new();
+    server.block_on(async {
+        server.static_path(missing.to_str().unwrap()).await;
+    });
+    // Created after configuration, it was never resolved so it isn't served
+    std::fs::create_dir_all(&missing).unwrap();
+    std::fs::write(missing.join("late.txt"), "late").unwrap();
+
+    let response = server.block_on(TestClient::new(server.clone()).get("/late.txt"));
+    assert_eq!(response.status, 404);
+}
@@ -0,0 +1,42 @@
+use std::sync::{ Arc, atomic::{ AtomicBool, Ordering } };
+
+use glote::{ Glote, ResponseExt, TestClient };
+
+#[test]
+fn test_chain_stops_after_a_response_is_sent() {
+    let reached = Arc::new(AtomicBool::new(false));
+    let server = Glote::new();
+    server.block_on(async {
+        // Answers and then calls next() anyway
+        server.use_middleware(|_req, res, next| async move {
+            res.status(403).await;
+            res.send("forbidden").await;
+            res.send("second body").await;
+            next().await;
+        }).await;
+
+        let reached_mw = Arc::clone(&reached);
+        server.use_middleware(move |_req, _res, next| {
+            let reached = Arc::clone(&reached_mw);
+            async move {
+                reached.store(true, Ordering::SeqCst);
+                next().await;
+            }
+        }).await;
+
+        let reached_handler = Arc::clone(&reached);
+        server.get("/", move |_req, res| {
+            let reached = Arc::clone(&reached_handler);
+            async move {
+                reached.store(true, Ordering::SeqCst);
+                res.send("handler").await;
+            }
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    let response = server.block_on(client.get("/"));
+
+    assert_eq!(response.status, 403);
+    assert_eq!(response.text(), "forbidden");
+    assert!(!reached.load(Ordering::SeqCst));
+}
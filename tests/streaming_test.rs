@@ -0,0 +1,74 @@
+mod common;
+
+use std::io::{ Read, Write };
+
+use glote::{ Glote, ResponseExt, StreamError };
+
+// Body of a chunked response, None if the framing is off
+fn dechunk(mut body: &str) -> Option<String> {
+    let mut out = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n")?;
+        let size = usize::from_str_radix(size, 16).ok()?;
+        if size == 0 {
+            return (rest == "\r\n").then_some(out);
+        }
+        out.push_str(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix("\r\n")?;
+    }
+}
+
+fn get(path: &str) -> String {
+    let mut stream = common::connect(41_330);
+    stream
+        .write_all(format!("GET {path} HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").as_bytes())
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_streamed_chunks_are_reassembled() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/export", |_req, res| async move {
+            // Chunks can't be written before the head
+            let early = res.write_chunk(b"x").await;
+            res.begin(200, &[("Content-Type", "text/csv")]).await.unwrap();
+            for row in ["id,name\n", "1,ana\n", "2,bo\n"] {
+                res.write_chunk(row.as_bytes()).await.unwrap();
+            }
+            // One-shot sends and a second head can't be mixed in
+            res.send("ignored").await;
+            let late = res.begin(200, &[]).await;
+            let misuse = format!(
+                "{},{}\n",
+                matches!(early, Err(StreamError::NotStreaming)),
+                matches!(late, Err(StreamError::AlreadySent))
+            );
+            res.write_chunk(misuse.as_bytes()).await.unwrap();
+            res.end().await.unwrap();
+        }).await;
+        server.get("/sized", |_req, res| async move {
+            res.begin(200, &[("Content-Length", "10")]).await.unwrap();
+            res.write_chunk(b"01234").await.unwrap();
+            res.write_chunk(b"56789").await.unwrap();
+            // Not ended, the server finishes it
+        }).await;
+    });
+    common::spawn_server(server, 41_330);
+
+    let response = get("/export");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(head.contains("\r\nTransfer-Encoding: chunked"), "{response}");
+    assert!(head.contains("\r\nContent-Type: text/csv"), "{response}");
+    assert!(!head.contains("Content-Length"), "{response}");
+    assert_eq!(dechunk(body).as_deref(), Some("id,name\n1,ana\n2,bo\ntrue,true\n"), "{response}");
+
+    let response = get("/sized");
+    assert!(response.contains("\r\nContent-Length: 10\r\n"), "{response}");
+    assert!(!response.contains("Transfer-Encoding"), "{response}");
+    assert!(response.ends_with("\r\n\r\n0123456789"), "{response}");
+}
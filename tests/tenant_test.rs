@@ -0,0 +1,116 @@
+use std::sync::{ Arc, Mutex };
+
+use glote::{ ErrorRenderer, Glote, Request, RequestExt, ResponseExt, Router, TenantSelector, TestClient };
+
+struct Brand(&'static str);
+
+struct Shared(&'static str);
+
+// Paths the tenant's middleware saw
+#[derive(Clone, Default)]
+struct Seen(Arc<Mutex<Vec<String>>>);
+
+struct PlainErrors(&'static str);
+
+impl ErrorRenderer for PlainErrors {
+    fn render(&self, status: u16, _req: &Request) -> (String, Vec<u8>) {
+        ("text/plain".into(), format!("{} {status}", self.0).into_bytes())
+    }
+}
+
+fn tenant(name: &'static str, seen: &Seen) -> Router {
+    let mut router = Router::new("/");
+    router.state(Brand(name));
+    let seen = seen.clone();
+    router.use_middleware(move |req, _res, next| {
+        let seen = seen.clone();
+        async move {
+            let path = req.read().await.path.clone();
+            seen.0.lock().unwrap().push(path);
+            next().await;
+        }
+    });
+    router.get("/orders", |req, res| async move {
+        let brand = req.state::<Brand>().await.unwrap();
+        let shared = req.state::<Shared>().await.unwrap();
+        res.send(&format!("{} orders ({})", brand.0, shared.0)).await;
+    });
+    router
+}
+
+#[test]
+fn test_host_tenants_are_isolated() {
+    let seen_a = Seen::default();
+    let seen_b = Seen::default();
+    let server = Glote::new();
+    server.block_on(async {
+        server.state(Shared("pool")).await;
+        server.state(Brand("server")).await;
+
+        let mut a = tenant("acme", &seen_a);
+        a.get("/only-a", |_req, res| async move { res.send("a").await });
+        a.not_found(|_req, res| async move { res.send("acme has no such page").await });
+        server.mount_tenant(TenantSelector::Host("A.example.com".into()), a).await;
+
+        let mut b = tenant("globex", &seen_b);
+        b.set_error_renderer(PlainErrors("globex"));
+        server.mount_tenant(TenantSelector::Host("b.example.com".into()), b).await;
+
+        server.get("/fallback", |req, res| async move {
+            let brand = req.state::<Brand>().await.unwrap();
+            res.send(brand.0).await;
+        }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        let get = |path: &'static str, host: &'static str| {
+            let client = client.clone();
+            async move { client.request("GET", path, &[("Host", host)], b"").await }
+        };
+
+        // Same path, each tenant's own handler and state, server state underneath
+        assert_eq!(get("/orders", "a.example.com").await.text(), "acme orders (pool)");
+        assert_eq!(get("/orders", "b.example.com").await.text(), "globex orders (pool)");
+
+        // No fallthrough to the other tenant or the server's routes
+        let response = get("/only-a", "b.example.com").await;
+        assert_eq!((response.status, response.text().as_str()), (404, "globex 404"));
+        let response = get("/fallback", "a.example.com").await;
+        assert_eq!((response.status, response.text().as_str()), (404, "acme has no such page"));
+
+        // Everyone else gets the server's routes and state
+        assert_eq!(get("/fallback", "c.example.com").await.text(), "server");
+        assert_eq!(get("/orders", "c.example.com").await.status, 404);
+    });
+
+    // Each tenant's middleware sees its own requests, its not_found included, and nothing else
+    assert_eq!(*seen_a.0.lock().unwrap(), ["/orders", "/fallback"]);
+    assert_eq!(*seen_b.0.lock().unwrap(), ["/orders"]);
+}
+
+#[test]
+fn test_prefix_tenants_mount_under_their_path() {
+    let seen_a = Seen::default();
+    let seen_b = Seen::default();
+    let server = Glote::new();
+    server.block_on(async {
+        server.state(Shared("pool")).await;
+        server.mount_tenant(TenantSelector::Prefix("/acme".into()), tenant("acme", &seen_a)).await;
+        server.mount_tenant(TenantSelector::Prefix("/globex".into()), tenant("globex", &seen_b)).await;
+        server.get("/acme-news", |_req, res| async move { res.send("news").await }).await;
+        server.get("/globex/about", |_req, res| async move { res.send("server about").await }).await;
+    });
+    let client = TestClient::new(server.clone());
+    server.block_on(async {
+        assert_eq!(client.get("/acme/orders").await.text(), "acme orders (pool)");
+        assert_eq!(client.get("/globex/orders").await.text(), "globex orders (pool)");
+        // Shadowed by the tenant, it owns everything under its prefix
+        assert_eq!(client.get("/globex/about").await.status, 404);
+        // Only whole segments select a tenant
+        assert_eq!(client.get("/acme-news").await.text(), "news");
+        assert_eq!(client.get("/orders").await.status, 404);
+    });
+
+    assert_eq!(*seen_a.0.lock().unwrap(), ["/acme/orders"]);
+    assert_eq!(*seen_b.0.lock().unwrap(), ["/globex/orders"]);
+}
@@ -0,0 +1,167 @@
+#![cfg(feature = "tls")]
+
+mod common;
+
+use std::{ path::PathBuf, sync::Arc, thread };
+use glote::{ ClientAuth, Glote, RequestExt, ResponseExt };
+use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpStream };
+use tokio_rustls::{
+    rustls::{ self, pki_types::{ CertificateDer, PrivateKeyDer, ServerName, pem::PemObject } },
+    TlsConnector,
+};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+#[test]
+fn test_https_request_round_trip() {
+    let server = Glote::new();
+    server.block_on(async {
+        server.get("/", |req, res| async move {
+            let info = req.conn_info().await;
+            let tls = info.tls.clone().unwrap_or_default();
+            res.send(&format!("{} {:?}", tls.version, info.alpn_protocol)).await;
+        }).await;
+    });
+    thread::spawn(move || {
+        let listener = server.clone();
+        let _ = server.block_on(
+            listener.listen_tls(("127.0.0.1", 41_160), fixture("tls_cert.pem"), fixture("tls_key.pem"))
+        );
+    });
+    // Wait for the port to open
+    drop(common::connect(41_160));
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_file(fixture("tls_ca.pem")).unwrap()).unwrap();
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    let response = tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let tcp = TcpStream::connect(("127.0.0.1", 41_160)).await.unwrap();
+        let connector = TlsConnector::from(Arc::new(config));
+        let mut tls = connector
+            .connect(ServerName::try_from("localhost").unwrap(), tcp).await
+            .unwrap();
+        tls.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = Vec::new();
+        let _ = tls.read_to_end(&mut response).await;
+        String::from_utf8_lossy(&response).to_string()
+    });
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("TLSv1_3 Some(\"http/1.1\")"), "{response}");
+}
+
+#[test]
+fn test_bad_certificate_fails_at_startup() {
+    let server = Glote::new();
+    let result = server.block_on(
+        server.clone().listen_tls(("127.0.0.1", 41_161), fixture("missing.pem"), fixture("tls_key.pem"))
+    );
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("missing.pem"), "{err}");
+
+    // Cert and key from different pairs
+    let result = server.block_on(
+        server.clone().listen_tls(("127.0.0.1", 41_161), fixture("tls_ca.pem"), fixture("tls_key.pem"))
+    );
+    assert!(result.unwrap_err().to_string().contains("don't fit"));
+}
+
+// Serve HTTPS on a free port with the given client auth, answering with the client's subject
+fn spawn_mtls_server(client_auth: ClientAuth) -> u16 {
+    let server = Glote::new();
+    server.block_on(async {
+        server.tls_client_auth(client_auth).await;
+        server.get("/", |req, res| async move {
+            let tls = req.conn_info().await.tls.clone().unwrap_or_default();
+            res.send(&format!("{:?}", tls.peer_subject)).await;
+        }).await;
+    });
+    let listener = server.clone();
+    thread::spawn(move || {
+        let _ = listener.block_on(
+            listener.clone().listen_tls(("127.0.0.1", 0), fixture("tls_cert.pem"), fixture("tls_key.pem"))
+        );
+    });
+    for _ in 0..100 {
+        if let Some(addr) = server.block_on(server.local_addr()) {
+            return addr.port();
+        }
+        thread::sleep(std::time::Duration::from_millis(20));
+    }
+    panic!("TLS server never came up");
+}
+
+// GET / over TLS, presenting the client certificate if asked to. Err when the server
+// refuses the handshake.
+fn https_get(port: u16, with_client_cert: bool) -> std::io::Result<String> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_file(fixture("tls_ca.pem")).unwrap()).unwrap();
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+    let config = if with_client_cert {
+        let cert = CertificateDer::from_pem_file(fixture("client_cert.pem")).unwrap();
+        let key = PrivateKeyDer::from_pem_file(fixture("client_key.pem")).unwrap();
+        builder.with_client_auth_cert(vec![cert], key).unwrap()
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let tcp = TcpStream::connect(("127.0.0.1", port)).await?;
+        let connector = TlsConnector::from(Arc::new(config));
+        let mut tls = connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await?;
+        tls.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+        let mut response = Vec::new();
+        // TLS 1.3 reports a refused client certificate on the first read
+        tls.read_to_end(&mut response).await?;
+        Ok(String::from_utf8_lossy(&response).to_string())
+    })
+}
+
+#[test]
+fn test_required_client_auth_exposes_subject() {
+    let port = spawn_mtls_server(ClientAuth::Required(fixture("client_ca.pem")));
+
+    let response = https_get(port, true).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with(r#"Some("CN=ada,O=Glote Tests")"#), "{response}");
+
+    // No certificate, no response
+    let refused = https_get(port, false);
+    assert!(refused.is_err(), "{refused:?}");
+}
+
+#[test]
+fn test_optional_client_auth_lets_anonymous_clients_in() {
+    let port = spawn_mtls_server(ClientAuth::Optional(fixture("client_ca.pem")));
+
+    let response = https_get(port, true).unwrap();
+    assert!(response.ends_with(r#"Some("CN=ada,O=Glote Tests")"#), "{response}");
+
+    let response = https_get(port, false).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.ends_with("None"), "{response}");
+}
+
+#[test]
+fn test_unreadable_client_ca_fails_at_startup() {
+    let server = Glote::new();
+    server.block_on(server.tls_client_auth(ClientAuth::Required(fixture("missing_ca.pem"))));
+    let result = server.block_on(
+        server.clone().listen_tls(("127.0.0.1", 0), fixture("tls_cert.pem"), fixture("tls_key.pem"))
+    );
+    assert!(result.unwrap_err().to_string().contains("missing_ca.pem"));
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use glote::{ Glote, RequestExt, ResponseExt, TestClient, TestResponse, TrailingSlash };
+
+fn start(policy: TrailingSlash) -> Arc<Glote> {
+    let server = Glote::new();
+    server.block_on(async {
+        server.trailing_slash(policy).await;
+        server.get("/users", |req, res| async move {
+            res.send(&format!("users at {}", req.path().await.unwrap_or_default())).await;
+        }).await;
+        server.get("/docs/", |_req, res| async move {
+            res.send("docs").await;
+        }).await;
+        server.get("/users/:id", |req, res| async move {
+            res.send(&format!("user {}", req.params("id").await.unwrap_or_default())).await;
+        }).await;
+        server.post("/users", |_req, res| async move {
+            res.send("created").await;
+        }).await;
+    });
+    server
+}
+
+fn get(server: &Arc<Glote>, path: &str) -> TestResponse {
+    server.block_on(TestClient::new(server.clone()).get(path))
+}
+
+#[test]
+fn test_trim_matches_extra_slashes() {
+    let server = start(TrailingSlash::Trim);
+
+    // Handlers see the path as sent
+    assert_eq!(get(&server, "/users/").text(), "users at /users/");
+    assert_eq!(get(&server, "//users").text(), "users at //users");
+    assert_eq!(get(&server, "/docs").text(), "docs");
+    assert_eq!(get(&server, "/users//7/").text(), "user 7");
+}
+
+#[test]
+fn test_redirect_to_canonical_path() {
+    let server = start(TrailingSlash::Redirect);
+
+    assert_eq!(get(&server, "/users").text(), "users at /users");
+    let response = get(&server, "/users/?page=2");
+    assert_eq!(response.status, 301);
+    assert_eq!(response.header("Location"), Some("/users?page=2"));
+    assert_eq!(get(&server, "/users//7").header("Location"), Some("/users/7"));
+    assert_eq!(get(&server, "/docs/").header("Location"), Some("/docs"));
+
+    // Non-GET requests keep their method
+    let client = TestClient::new(server.clone());
+    let response = server.block_on(client.request("POST", "/users/", &[("Content-Length", "0")], b""));
+    assert_eq!(response.status, 308);
+
+    // Nothing to redirect to
+    assert_eq!(get(&server, "/missing/").status, 404);
+}
+
+#[test]
+fn test_strict_counts_every_slash() {
+    let server = start(TrailingSlash::Strict);
+
+    assert_eq!(get(&server, "/users").text(), "users at /users");
+    assert_eq!(get(&server, "/docs/").text(), "docs");
+    for path in ["/users/", "//users", "/docs", "/users//7"] {
+        assert_eq!(get(&server, path).status, 404, "{path}");
+    }
+}
+
+#[test]
+fn test_strict_allows_both_spellings_as_routes() {
+    let server = Glote::new();
+    let result = server.block_on(async {
+        server.trailing_slash(TrailingSlash::Strict).await;
+        server.get("/a", |_req, res| async move { res.send("a").await }).await;
+        server.get("/a/", |_req, res| async move { res.send("a/").await }).await;
+        server.finalize().await
+    });
+    assert!(result.is_ok(), "{result:?}");
+
+    let server = Glote::new();
+    let result = server.block_on(async {
+        server.get("/a", |_req, res| async move { res.send("a").await }).await;
+        server.get("/a/", |_req, res| async move { res.send("a/").await }).await;
+        server.finalize().await
+    });
+    assert!(result.is_err());
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use std::time::{ Duration, Instant };
+use glote::{ Glote, RequestExt, ResponseExt };
+
+fn start(port: u16, max_uri_length: Option<usize>) {
+    let server = Glote::new();
+    server.block_on(async {
+        if let Some(length) = max_uri_length {
+            server.max_uri_length(length).await;
+        }
+        server.get("/search", |req, res| async move {
+            let q = req.query("q").await.unwrap_or_default();
+            res.send(&format!("len={}", q.len())).await;
+        }).await;
+    });
+    common::spawn_server(server, port);
+}
+
+#[test]
+fn test_oversized_target_gets_414() {
+    start(40_870, None);
+
+    let raw = format!("GET /search?q={} HTTP/1.1\r\nHost: x\r\n\r\n", "a".repeat(100 * 1024));
+    let started = Instant::now();
+    let response = common::raw_request(40_870, raw.as_bytes());
+
+    assert!(response.starts_with("HTTP/1.1 414 URI Too Long\r\n"), "{response}");
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn test_target_within_limit_is_served() {
+    start(40_871, Some(64));
+
+    let ok = common::raw_request(40_871, b"GET /search?q=rust HTTP/1.1\r\nHost: x\r\n\r\n");
+    assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"), "{ok}");
+    assert!(ok.ends_with("len=4"), "{ok}");
+
+    let raw = format!("GET /search?q={} HTTP/1.1\r\nHost: x\r\n\r\n", "a".repeat(80));
+    let too_long = common::raw_request(40_871, raw.as_bytes());
+    assert!(too_long.starts_with("HTTP/1.1 414 URI Too Long\r\n"), "{too_long}");
+}